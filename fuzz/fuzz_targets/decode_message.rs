@@ -0,0 +1,16 @@
+#![no_main]
+
+use dns_resolver::message::DNSMessage;
+use libfuzzer_sys::fuzz_target;
+
+// Decodes arbitrary data as a DNS message, which must never panic. Whatever gets decoded
+// must be renderable and encodable without panicking as well.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(message) = DNSMessage::decode(data) {
+        let _ = message.edns();
+        for record in message.records() {
+            let _ = record.to_string();
+        }
+        let _ = message.encode();
+    }
+});
@@ -1,22 +1,81 @@
 use anyhow::{Ok, Result};
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::rdata::RData;
 use dns_resolver::{resolver::Resolver, rr_types};
 use std::{env, process};
 
+// Renders the data of an answer with the names in it decoded into Unicode. Only the
+// fields that hold names are converted, so character strings and the like are left as
+// they are.
+fn to_unicode(rdata: &RData) -> String {
+    let rendered = rdata.to_string();
+    // the names along with their positions among the space-separated fields of the data,
+    // where none of the fields before a name can contain spaces.
+    let (names, positions): (Vec<&DomainName>, &[usize]) = match rdata {
+        RData::NS(name)
+        | RData::MD(name)
+        | RData::MF(name)
+        | RData::CNAME(name)
+        | RData::MB(name)
+        | RData::MG(name)
+        | RData::MR(name)
+        | RData::PTR(name) => (vec![name], &[0]),
+        RData::SOA(soa) => (vec![soa.mname(), soa.rname()], &[0, 1]),
+        RData::MINFO(minfo) => (vec![minfo.rmailbx(), minfo.emailbx()], &[0, 1]),
+        RData::MX(mx) => (vec![mx.exchange()], &[1]),
+        RData::SRV(srv) => (vec![srv.target()], &[3]),
+        RData::RRSIG(rrsig) => (vec![rrsig.signer_name()], &[7]),
+        RData::NSEC(nsec) => (vec![nsec.next_domain_name()], &[0]),
+        RData::SVCB(svcb) | RData::HTTPS(svcb) => (vec![svcb.target()], &[1]),
+        // the replacement comes last, after character strings that may contain spaces.
+        RData::NAPTR(naptr) => {
+            return match rendered.rsplit_once(' ') {
+                Some((rest, _)) => format!("{} {}", rest, naptr.replacement().to_unicode()),
+                None => rendered,
+            }
+        }
+        _ => return rendered,
+    };
+    let last = positions.last().copied().unwrap_or_default();
+    let mut fields = rendered
+        .splitn(last + 2, ' ')
+        .map(String::from)
+        .collect::<Vec<_>>();
+    for (name, &position) in names.iter().zip(positions) {
+        if let Some(field) = fields.get_mut(position) {
+            *field = name.to_unicode();
+        }
+    }
+    fields.join(" ")
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    // --unicode displays the names in the answers with their U-labels instead of
+    // their A-labels (xn--).
+    let unicode = args.iter().any(|arg| arg == "--unicode");
+    args.retain(|arg| arg != "--unicode");
     if args.len() != 3 {
         eprintln!("expected two arguments; specifying the domain name and record_type");
         process::exit(1);
     }
 
-    let domain = args[1].clone();
+    // non-ASCII names are converted to their A-labels while parsing.
+    let domain = args[1].parse::<DomainName>()?.to_string();
     let record_type = args[2].clone();
     let rr_type = rr_types::str_to_record_type(&record_type)?;
 
     let resolver = Resolver::new("0.0.0.0:3400").await?;
     let message = resolver.resolve(domain, &rr_type).await?;
-    let ips = message.answers_data(&rr_type);
+    let ips = message
+        .answers_of_type(&rr_type)
+        .filter_map(|answer| answer.rdata().as_ref())
+        .map(|rdata| match unicode {
+            true => to_unicode(rdata),
+            false => rdata.to_string(),
+        })
+        .collect::<Vec<_>>();
 
     println!("answer(s): {:?}", ips);
     Ok(())
@@ -1,51 +1,773 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{env, process};
+
 use anyhow::Result;
+use data_encoding::HEXUPPER;
 use dns_resolver::cache::DNSCache;
-use dns_resolver::message::DNSMessage;
-use dns_resolver::query::{DNSHeader, DNSQuestion, QR};
+use dns_resolver::decoder::Decoder;
+use dns_resolver::dnssec::{self, ValidationStatus};
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::edns::Edns;
+use dns_resolver::journal::Journal;
+use dns_resolver::message::{DNSMessage, MessageBuilder};
+use dns_resolver::query::{DNSHeader, DNSQuestion, Opcode, ResponseCode};
+use dns_resolver::rdata::{RData, DS};
 use dns_resolver::resolver::Resolver;
-use tokio::net::UdpSocket;
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::{DNSClass, RRType};
+use dns_resolver::secondary::Secondary;
+use dns_resolver::zone::{Zone, Zones};
+use dns_resolver::{tcp, transfer, update};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::{Mutex, Notify, RwLock, Semaphore};
+use tokio::time::{sleep_until, timeout, timeout_at, Instant};
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let socket = UdpSocket::bind("127.0.0.1:3500").await?;
-    let mut cache = DNSCache::new(100);
-    loop {
-        let mut buf = [0; 1024];
-        let (no, addr) = socket.recv_from(&mut buf).await?;
-        let query = buf[..no].to_vec();
-        let mut query_iter = query.iter();
-
-        let header = DNSHeader::decode(&mut query_iter)?;
-        let question = DNSQuestion::decode(&mut query_iter)?;
-        let mut message: DNSMessage;
-        if let Some(answer) = cache.get(question.name(), question.q_type()) {
-            let mut new_header = DNSHeader::new(
-                header.id(),
-                0,
-                1,
-                answer.data().len().try_into().unwrap(),
-                0,
-                0,
-            );
-            new_header.set_qr(QR::Response);
-            new_header.set_recursion_desired(header.recursion_desired());
-            new_header.set_recursion_available(true);
-            let questions = vec![question];
-            let answers = answer.data();
-            message = DNSMessage::new(new_header, questions, answers, vec![], vec![]);
-        } else {
-            let resolver = Resolver::new("0.0.0.0:3400").await?;
-            let domain = question.name().0.clone();
-            message = resolver.resolve(domain, question.q_type()).await?;
-            message.set_id(header.id());
-            cache.insert(
+// The largest UDP payload that the server accepts, which it advertises using EDNS.
+const UDP_PAYLOAD_SIZE: u16 = 4096;
+
+// The largest UDP payload that clients accept unless they advertise a larger one using
+// EDNS (RFC 1035 section 4.2.1).
+const MIN_UDP_PAYLOAD_SIZE: u16 = 512;
+
+// The number of queries over UDP that are answered at the same time, beyond which
+// queries wait to be received.
+const MAX_UDP_QUERIES: usize = 100;
+
+// The time after which idle TCP connections are closed.
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The time after which TCP connections are closed even if they're busy, which is long
+// enough to transfer a large zone.
+const TCP_CONNECTION_TIMEOUT: Duration = Duration::from_secs(120);
+
+// The number of queries after which TCP connections are closed.
+const MAX_TCP_QUERIES: usize = 100;
+
+// Config is the configuration of the server, provided via command line flags.
+struct Config {
+    // Reported for version.bind CHAOS queries.
+    version: String,
+    // Reported for hostname.bind CHAOS queries.
+    hostname: Option<String>,
+    // Reported for id.server CHAOS queries; defaults to the hostname.
+    server_id: Option<String>,
+    // The trust anchors that answers are validated against using DNSSEC. Answers aren't
+    // validated if there are none.
+    trust_anchors: Vec<DS>,
+    // Whether the resolver minimises the names that it reveals to nameservers.
+    qname_minimisation: bool,
+    // Whether the resolver randomises the case of the names in its queries.
+    case_randomisation: bool,
+    // The nameserver that the resolver starts at instead of a root nameserver, if any.
+    root_server: Option<SocketAddr>,
+    // The address that queries are served on over UDP and TCP.
+    listen: SocketAddr,
+    // The zones that are served authoritatively from master files.
+    zones: Zones,
+    // The journals that the dynamic updates of the zones loaded from master files are
+    // recorded in, by the origin of the zone.
+    journals: HashMap<DomainName, Journal>,
+    // The zones that are served authoritatively as a secondary of another server.
+    secondaries: Vec<Secondary>,
+    // Whether names outside of the served zones are resolved recursively, which is the
+    // case by default unless zones are served.
+    recursion: bool,
+    // The addresses that are allowed to transfer the served zones.
+    transfer_acl: Vec<IpAddr>,
+    // The addresses that are allowed to update the zones loaded from master files.
+    update_acl: Vec<IpAddr>,
+}
+
+impl Config {
+    fn from_args() -> Config {
+        let mut config = Config {
+            version: format!("dns-resolver {}", env!("CARGO_PKG_VERSION")),
+            hostname: None,
+            server_id: None,
+            trust_anchors: vec![],
+            qname_minimisation: false,
+            case_randomisation: false,
+            root_server: None,
+            listen: SocketAddr::from(([127, 0, 0, 1], 3500)),
+            zones: Zones::new(),
+            journals: HashMap::new(),
+            secondaries: vec![],
+            recursion: false,
+            transfer_acl: vec![],
+            update_acl: vec![],
+        };
+
+        let mut dnssec = false;
+        let mut args = env::args().skip(1);
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--dnssec" => {
+                    dnssec = true;
+                    continue;
+                }
+                "--qname-minimisation" => {
+                    config.qname_minimisation = true;
+                    continue;
+                }
+                "--case-randomisation" => {
+                    config.case_randomisation = true;
+                    continue;
+                }
+                "--recursion" => {
+                    config.recursion = true;
+                    continue;
+                }
+                _ => {}
+            }
+            let value = args.next().unwrap_or_else(|| {
+                eprintln!("expected a value for {}", flag);
+                process::exit(1);
+            });
+            match flag.as_str() {
+                "--version" => config.version = value,
+                "--hostname" => config.hostname = Some(value),
+                "--server-id" => config.server_id = Some(value),
+                "--trust-anchor" => match parse_trust_anchor(&value) {
+                    Some(ds) => config.trust_anchors.push(ds),
+                    None => {
+                        eprintln!("invalid trust anchor {}", value);
+                        process::exit(1);
+                    }
+                },
+                "--root-server" => match value.parse() {
+                    Ok(addr) => config.root_server = Some(addr),
+                    Err(_) => {
+                        eprintln!("invalid address {}", value);
+                        process::exit(1);
+                    }
+                },
+                "--listen" => match value.parse() {
+                    Ok(addr) => config.listen = addr,
+                    Err(_) => {
+                        eprintln!("invalid address {}", value);
+                        process::exit(1);
+                    }
+                },
+                "--zone" => match load_zone(&value) {
+                    Ok((zone, journal)) => {
+                        config.journals.insert(zone.origin().clone(), journal);
+                        config.zones.insert(zone);
+                    }
+                    Err(e) => {
+                        eprintln!("invalid zone {}: {}", value, e);
+                        process::exit(1);
+                    }
+                },
+                "--secondary" => match parse_secondary(&value) {
+                    Ok(secondary) => config.secondaries.push(secondary),
+                    Err(e) => {
+                        eprintln!("invalid secondary zone {}: {}", value, e);
+                        process::exit(1);
+                    }
+                },
+                "--allow-transfer" => match value.parse() {
+                    Ok(addr) => config.transfer_acl.push(addr),
+                    Err(_) => {
+                        eprintln!("invalid address {}", value);
+                        process::exit(1);
+                    }
+                },
+                "--allow-update" => match value.parse() {
+                    Ok(addr) => config.update_acl.push(addr),
+                    Err(_) => {
+                        eprintln!("invalid address {}", value);
+                        process::exit(1);
+                    }
+                },
+                _ => {
+                    eprintln!("unknown flag {}", flag);
+                    process::exit(1);
+                }
+            }
+        }
+        if config.server_id.is_none() {
+            config.server_id = config.hostname.clone();
+        }
+        if config.zones.is_empty() && config.secondaries.is_empty() {
+            config.recursion = true;
+        }
+        if dnssec && config.trust_anchors.is_empty() {
+            config.trust_anchors.push(dnssec::root_trust_anchor());
+        }
+        config
+    }
+
+    // Returns a resolver that's configured as per the flags.
+    async fn resolver(&self) -> Result<Resolver> {
+        // every resolver gets a port of its own, since several of them may be resolving
+        // at the same time.
+        let mut resolver = Resolver::new("0.0.0.0:0").await?;
+        resolver.set_trust_anchors(self.trust_anchors.clone());
+        resolver.set_qname_minimisation(self.qname_minimisation);
+        resolver.set_case_randomisation(self.case_randomisation);
+        if let Some(root_server) = self.root_server {
+            resolver.set_root_server(root_server);
+        }
+        Ok(resolver)
+    }
+
+    // Returns the TXT data that identifies this instance for the provided CHAOS name.
+    fn chaos_data(&self, name: &DomainName) -> Option<String> {
+        let name = name.to_string().to_ascii_lowercase();
+        match name.as_str() {
+            "version.bind" | "version.server" => Some(self.version.clone()),
+            "hostname.bind" => self.hostname.clone(),
+            "id.server" => self.server_id.clone(),
+            _ => None,
+        }
+    }
+}
+
+// Parses a trust anchor in the presentation format of a DS record, i.e.
+// <key tag> <algorithm> <digest type> <digest in hex>.
+fn parse_trust_anchor(value: &str) -> Option<DS> {
+    let parts = value.split_whitespace().collect::<Vec<_>>();
+    if parts.len() < 4 {
+        return None;
+    }
+    let digest = HEXUPPER
+        .decode(parts[3..].concat().to_ascii_uppercase().as_bytes())
+        .ok()?;
+    Some(DS::new(
+        parts[0].parse().ok()?,
+        parts[1].parse().ok()?,
+        parts[2].parse().ok()?,
+        digest,
+    ))
+}
+
+// Loads a zone given as <origin>=<path of its master file>, along with the changes made
+// to it by dynamic updates, which are recorded in a journal next to the master file.
+fn load_zone(value: &str) -> Result<(Zone, Journal)> {
+    let Some((origin, path)) = value.split_once('=') else {
+        anyhow::bail!("expected <origin>=<path>");
+    };
+    let path = PathBuf::from(path);
+    let zone = Zone::read(&path, origin.parse()?)?;
+    let mut journal_path = path.into_os_string();
+    journal_path.push(".jnl");
+    let journal = Journal::new(PathBuf::from(journal_path));
+    Ok((journal.replay(zone)?, journal))
+}
+
+// Parses a secondary zone given as <origin>=<address of its primary>.
+fn parse_secondary(value: &str) -> Result<Secondary> {
+    let Some((origin, primary)) = value.split_once('=') else {
+        anyhow::bail!("expected <origin>=<address>");
+    };
+    Ok(Secondary::new(origin.parse()?, primary.parse()?))
+}
+
+// State is what the server keeps track of while it's running, which is shared by the
+// tasks that serve TCP connections.
+struct State {
+    // The zones that are served authoritatively, including the current versions of the
+    // secondary zones.
+    zones: RwLock<Zones>,
+    secondaries: Mutex<Vec<Secondary>>,
+    cache: Mutex<DNSCache>,
+    // Wakes up the main loop when the secondary zones need to be maintained sooner
+    // than it's waiting for.
+    wake: Notify,
+}
+
+impl State {
+    // Returns the state that the server starts with, which takes over the zones of the
+    // configuration.
+    fn new(config: &mut Config) -> State {
+        State {
+            zones: RwLock::new(std::mem::take(&mut config.zones)),
+            secondaries: Mutex::new(std::mem::take(&mut config.secondaries)),
+            cache: Mutex::new(DNSCache::new(100)),
+            wake: Notify::new(),
+        }
+    }
+
+    // Returns when one of the secondary zones next needs to be maintained, if there are
+    // any.
+    async fn deadline(&self) -> Option<Instant> {
+        let secondaries = self.secondaries.lock().await;
+        secondaries.iter().filter_map(Secondary::deadline).min()
+    }
+}
+
+// Expires the secondary zones that are due and starts checking the ones whose refresh is
+// due for changes. Every check runs in a task of its own, which puts the new version of
+// its zone in place once it's done, since a check can take a while.
+async fn maintain_secondaries(state: &Arc<State>) {
+    for secondary in state.secondaries.lock().await.iter_mut() {
+        if secondary.expire() {
+            println!("Zone {} has expired", secondary.origin());
+            state.zones.write().await.remove(secondary.origin());
+        }
+        if !secondary.start_refresh() {
+            continue;
+        }
+        let checked = secondary.clone();
+        let state = Arc::clone(state);
+        tokio::spawn(async move {
+            let current = state.zones.read().await.get(checked.origin()).cloned();
+            let refreshed = checked.refresh(current).await;
+            let mut secondaries = state.secondaries.lock().await;
+            let secondary = secondaries
+                .iter_mut()
+                .find(|secondary| secondary.origin() == checked.origin());
+            if let Some(secondary) = secondary {
+                match refreshed {
+                    Ok(zone) => {
+                        secondary.finish_refresh(Some(&zone));
+                        state.zones.write().await.insert(zone);
+                    }
+                    Err(e) => {
+                        println!("Failed to refresh {}: {}", checked.origin(), e);
+                        secondary.finish_refresh(None);
+                    }
+                }
+            }
+            state.wake.notify_one();
+        });
+    }
+}
+
+// Builds a response to the query without any answers, carrying the provided RCODE.
+fn error_response(query: &DNSMessage, rcode: ResponseCode) -> Result<DNSMessage> {
+    Ok(DNSMessage::response_to(query)
+        .response_code(rcode)
+        .build()?)
+}
+
+// Answers a CHAOS class query about the identity of this instance. Anything other
+// than a TXT query for one of the well-known names is refused.
+fn answer_chaos(query: &DNSMessage, question: &DNSQuestion, config: &Config) -> Result<DNSMessage> {
+    let data = match question.q_type() {
+        RRType::TXT => config.chaos_data(question.name()),
+        _ => None,
+    };
+    let Some(data) = data else {
+        return error_response(query, ResponseCode::Refused);
+    };
+    let rdata = RData::TXT(vec![data.as_bytes().to_vec()]);
+    let record = DNSRecord::new(
+        question.name().clone(),
+        RRType::TXT,
+        DNSClass::CH,
+        Duration::ZERO,
+        rdata,
+    )?;
+    Ok(DNSMessage::response_to(query).answer(record).build()?)
+}
+
+// Returns a builder for a response to the query that carries the sections of the
+// response that the resolver came up with. The OPT record of that response is left
+// out, since EDNS is negotiated separately with every client.
+fn relay(query: &DNSMessage, resolved: DNSMessage) -> MessageBuilder {
+    let rcode = resolved.header().response_code();
+    let (_, _, answers, authorities, additionals) = resolved.into_parts();
+    DNSMessage::response_to(query)
+        .recursion_available(true)
+        .response_code(rcode)
+        .answers(answers)
+        .authorities(authorities)
+        .additionals(
+            additionals
+                .into_iter()
+                .filter(|record| record.r_type() != &RRType::OPT),
+        )
+}
+
+// Answers the query, which has to be a standard query with exactly one question.
+async fn answer(query: &DNSMessage, config: &Config, state: &State) -> Result<DNSMessage> {
+    if query.header().opcode() != Some(Opcode::Query) {
+        return error_response(query, ResponseCode::NotImp);
+    }
+    // hardly any implementation supports more than one question, so neither do we.
+    if query.questions().len() != 1 {
+        return error_response(query, ResponseCode::FormErr);
+    }
+    let question = &query.questions()[0];
+
+    let zones = state.zones.read().await;
+    let zone = zones
+        .find(question.name())
+        .filter(|zone| zone.class() == question.class() || question.class() == &DNSClass::ANY);
+    if question.class() == &DNSClass::CH {
+        return answer_chaos(query, question, config);
+    } else if let Some(zone) = zone {
+        return Ok(zone
+            .answer(query, question)
+            .recursion_available(config.recursion)
+            .build()?);
+    }
+    // the zones aren't locked while names are resolved recursively.
+    drop(zones);
+    if !config.recursion
+        || (question.class() != &DNSClass::IN && question.class() != &DNSClass::ANY)
+    {
+        return error_response(query, ResponseCode::Refused);
+    }
+
+    // AD is only set for clients that show that they understand it, by setting either
+    // the DO bit or AD itself in their query (RFC 6840 section 5.8).
+    let wants_ad =
+        query.header().authentic_data() || query.edns().is_some_and(|edns| edns.dnssec_ok());
+    let cached = state
+        .cache
+        .lock()
+        .await
+        .get(question.name(), question.q_type(), question.class());
+    if let Some(answer) = cached {
+        Ok(DNSMessage::response_to(query)
+            .recursion_available(true)
+            .authentic_data(answer.secure() && wants_ad)
+            .answers(answer.data())
+            .build()?)
+    } else if !config.trust_anchors.is_empty() {
+        let resolver = config.resolver().await?;
+        let domain = question.name().to_string();
+        let answer = resolver
+            .resolve_validated(domain, question.q_type())
+            .await?;
+        if let ValidationStatus::Bogus(reason) = &answer.status {
+            println!("Validation of {} failed: {}", question.name(), reason);
+            return error_response(query, ResponseCode::ServFail);
+        }
+        let secure = answer.status == ValidationStatus::Secure;
+        // negative answers aren't cached, since the cache only holds records.
+        if !answer.message.answers().is_empty() {
+            state.cache.lock().await.insert(
                 question.name(),
                 question.q_type(),
-                message.answers().clone(),
+                question.class(),
+                answer.message.answers().clone(),
+                secure,
             );
         }
+        Ok(relay(query, answer.message)
+            .authentic_data(secure && wants_ad)
+            .build()?)
+    } else {
+        let resolver = config.resolver().await?;
+        let domain = question.name().to_string();
+        let message = resolver.resolve(domain, question.q_type()).await?;
+        state.cache.lock().await.insert(
+            question.name(),
+            question.q_type(),
+            question.class(),
+            message.answers().clone(),
+            false,
+        );
+        Ok(relay(query, message).build()?)
+    }
+}
 
-        let encoded = message.encode()?;
-        socket.send_to(&encoded, addr).await?;
+// Answers a zone transfer, which is only allowed to the addresses given using the
+// --allow-transfer flag. AXFR is only served over TCP, while IXFR over UDP is answered
+// with the SOA record of the zone alone, which tells the client to retry over TCP unless
+// it's up to date (RFC 1995 section 2).
+async fn answer_transfer(
+    query: &DNSMessage,
+    peer: IpAddr,
+    over_tcp: bool,
+    config: &Config,
+    state: &State,
+) -> Result<Vec<DNSMessage>> {
+    let question = &query.questions()[0];
+    let zones = state.zones.read().await;
+    let zone = zones
+        .get(question.name())
+        .filter(|_| config.transfer_acl.contains(&peer));
+    let Some(zone) = zone else {
+        return Ok(vec![error_response(query, ResponseCode::Refused)?]);
+    };
+    match question.q_type() {
+        RRType::AXFR if over_tcp => Ok(transfer::axfr_responses(zone, query)?),
+        RRType::IXFR if over_tcp => {
+            let journal = config.journals.get(question.name());
+            Ok(transfer::ixfr_responses(zone, query, journal)?)
+        }
+        RRType::IXFR => Ok(vec![DNSMessage::response_to(query)
+            .authoritative(true)
+            .answer(zone.soa().clone())
+            .build()?]),
+        _ => Ok(vec![error_response(query, ResponseCode::Refused)?]),
+    }
+}
+
+// Answers a NOTIFY (RFC 1996), which tells that a zone has changed. NOTIFY is only
+// accepted from the primary of a secondary zone, which is then checked for changes
+// right away by the main loop.
+async fn answer_notify(query: &DNSMessage, peer: IpAddr, state: &State) -> Result<DNSMessage> {
+    if query.questions().len() != 1 {
+        return error_response(query, ResponseCode::FormErr);
+    }
+    let question = &query.questions()[0];
+    let mut secondaries = state.secondaries.lock().await;
+    let secondary = secondaries.iter_mut().find(|secondary| {
+        secondary.origin() == question.name() && secondary.primary().ip() == peer
+    });
+    let Some(secondary) = secondary else {
+        return error_response(query, ResponseCode::Refused);
+    };
+    secondary.notify();
+    state.wake.notify_one();
+    Ok(DNSMessage::response_to(query).authoritative(true).build()?)
+}
+
+// Answers a dynamic update (RFC 2136), which is only allowed to the addresses given
+// using the --allow-update flag. Only the zones loaded from master files can be updated,
+// and every update that changes a zone is recorded in its journal before it's served.
+// The zones stay locked throughout, so that concurrent updates are applied one by one.
+async fn answer_update(
+    query: &DNSMessage,
+    peer: IpAddr,
+    config: &Config,
+    state: &State,
+) -> Result<DNSMessage> {
+    if !config.update_acl.contains(&peer) {
+        return error_response(query, ResponseCode::Refused);
+    }
+    // the zone section is a single question for the SOA record of the zone.
+    if query.questions().len() != 1 || query.questions()[0].q_type() != &RRType::SOA {
+        return error_response(query, ResponseCode::FormErr);
+    }
+    let question = &query.questions()[0];
+    let mut zones = state.zones.write().await;
+    let zone = zones
+        .get(question.name())
+        .filter(|zone| zone.class() == question.class());
+    let (Some(zone), Some(journal)) = (zone, config.journals.get(question.name())) else {
+        return error_response(query, ResponseCode::NotAuth);
+    };
+    let change = match update::update(zone, query) {
+        Ok(change) => change,
+        Err(rcode) => return error_response(query, rcode),
+    };
+    if let Some(change) = change {
+        let updated = zone.apply(change.records())?;
+        journal.append(&change)?;
+        zones.insert(updated);
+    }
+    Ok(DNSMessage::response_to(query).build()?)
+}
+
+// Returns the responses to the provided query from the peer, which is a single response
+// apart from zone transfers. Queries that can't be decoded get a FORMERR response if at
+// least their header can be decoded, while failures to answer a query get a SERVFAIL
+// response. Responses are never responded to.
+async fn respond(
+    query: &[u8],
+    peer: IpAddr,
+    over_tcp: bool,
+    config: &Config,
+    state: &State,
+) -> Vec<DNSMessage> {
+    let query = match DNSMessage::decode(query) {
+        Ok(query) => query,
+        Err(e) => {
+            println!("Failed to decode query: {}", e);
+            let Ok(header) = DNSHeader::decode(&mut Decoder::new(query)) else {
+                return vec![];
+            };
+            if header.is_response() {
+                return vec![];
+            }
+            return MessageBuilder::response()
+                .id(header.id())
+                .response_code(ResponseCode::FormErr)
+                .build()
+                .into_iter()
+                .collect();
+        }
+    };
+    if query.header().is_response() {
+        return vec![];
+    }
+
+    let transfer = query.header().opcode() == Some(Opcode::Query)
+        && query.questions().len() == 1
+        && matches!(query.questions()[0].q_type(), RRType::AXFR | RRType::IXFR);
+    let answered = if transfer {
+        answer_transfer(&query, peer, over_tcp, config, state).await
+    } else if query.header().opcode() == Some(Opcode::Notify) {
+        answer_notify(&query, peer, state)
+            .await
+            .map(|response| vec![response])
+    } else if query.header().opcode() == Some(Opcode::Update) {
+        answer_update(&query, peer, config, state)
+            .await
+            .map(|response| vec![response])
+    } else {
+        answer(&query, config, state)
+            .await
+            .map(|response| vec![response])
+    };
+    let mut responses = match answered {
+        Ok(responses) => responses,
+        Err(e) => {
+            println!("Failed to answer query: {}", e);
+            error_response(&query, ResponseCode::ServFail)
+                .into_iter()
+                .collect()
+        }
+    };
+    // responses only carry an OPT record if the query did (RFC 6891 section 7).
+    if query.edns().is_some() {
+        let Ok(edns) = Edns::new(UDP_PAYLOAD_SIZE).to_record() else {
+            return vec![];
+        };
+        for response in &mut responses {
+            response.additionals_mut().push(edns.clone());
+        }
+    }
+    if !over_tcp {
+        let limit = query.edns().map_or(MIN_UDP_PAYLOAD_SIZE, |edns| {
+            edns.udp_payload_size()
+                .clamp(MIN_UDP_PAYLOAD_SIZE, UDP_PAYLOAD_SIZE)
+        });
+        for response in &mut responses {
+            truncate(response, limit);
+        }
+    }
+    responses
+}
+
+// Makes the response fit within the provided UDP payload size. The additional records
+// apart from the OPT record are dropped first, which doesn't need to be signalled. If the
+// response still doesn't fit, its answers and authorities are dropped as well and the TC
+// bit is set, which tells the client to retry over TCP (RFC 2181 section 9). Responses
+// that can't be encoded are left for the caller to report.
+fn truncate(response: &mut DNSMessage, limit: u16) {
+    let fits = |response: &DNSMessage| {
+        response
+            .encode()
+            .map_or(true, |encoded| encoded.len() <= usize::from(limit))
+    };
+    if fits(response) {
+        return;
+    }
+    response
+        .additionals_mut()
+        .retain(|record| record.r_type() == &RRType::OPT);
+    if fits(response) {
+        return;
+    }
+    response.answers_mut().clear();
+    response.authorities_mut().clear();
+    response.header_mut().set_truncated(true);
+}
+
+// Serves a query that the peer sent over UDP, sending the responses from the socket that
+// it was received on.
+async fn serve_udp(
+    query: &[u8],
+    peer: SocketAddr,
+    socket: &UdpSocket,
+    config: &Config,
+    state: &State,
+) {
+    for response in respond(query, peer.ip(), false, config, state).await {
+        let sent = match response.encode() {
+            Ok(encoded) => socket.send_to(&encoded, peer).await.map(|_| ()),
+            Err(e) => {
+                println!("Failed to encode response: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = sent {
+            println!("Failed to send response to {}: {}", peer, e);
+        }
+    }
+}
+
+// Serves the queries that the peer sends over a TCP connection until it closes the
+// connection, leaves it idle for too long, keeps it open for too long or sends too many
+// queries.
+async fn serve_tcp(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    config: &Config,
+    state: &State,
+) -> Result<()> {
+    let serve = async {
+        for _ in 0..MAX_TCP_QUERIES {
+            let read = timeout(TCP_IDLE_TIMEOUT, tcp::read_message(&mut stream)).await??;
+            let Some(query) = read else {
+                break;
+            };
+            for response in respond(&query, peer.ip(), true, config, state).await {
+                tcp::write_message(&mut stream, &response.encode()?).await?;
+            }
+        }
+        Ok(())
+    };
+    timeout_at(Instant::now() + TCP_CONNECTION_TIMEOUT, serve).await?
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut config = Config::from_args();
+    let state = Arc::new(State::new(&mut config));
+    let config = Arc::new(config);
+    let socket = Arc::new(UdpSocket::bind(config.listen).await?);
+    let udp_queries = Arc::new(Semaphore::new(MAX_UDP_QUERIES));
+    // TCP is served on the same port, which is only picked when UDP is bound if it's 0.
+    let addr = socket.local_addr()?;
+    let listener = TcpListener::bind(addr).await?;
+    println!("Listening on {}", addr);
+    loop {
+        let mut buf = [0; UDP_PAYLOAD_SIZE as usize];
+        let deadline = state.deadline().await;
+        tokio::select! {
+            received = socket.recv_from(&mut buf) => {
+                let (no, addr) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        println!("Failed to receive query: {}", e);
+                        continue;
+                    }
+                };
+                // every query is answered by a task of its own, so that queries that
+                // need to be resolved don't hold up the others, but only so many at once.
+                let Ok(permit) = Arc::clone(&udp_queries).acquire_owned().await else {
+                    continue;
+                };
+                let query = buf[..no].to_vec();
+                let socket = Arc::clone(&socket);
+                let config = Arc::clone(&config);
+                let state = Arc::clone(&state);
+                tokio::spawn(async move {
+                    serve_udp(&query, addr, &socket, &config, &state).await;
+                    drop(permit);
+                });
+            }
+            accepted = listener.accept() => match accepted {
+                // every connection is served by a task of its own, so that slow clients
+                // don't hold up the others.
+                Ok((stream, addr)) => {
+                    let config = Arc::clone(&config);
+                    let state = Arc::clone(&state);
+                    tokio::spawn(async move {
+                        if let Err(e) = serve_tcp(stream, addr, &config, &state).await {
+                            println!("Failed to serve {} over TCP: {}", addr, e);
+                        }
+                    });
+                }
+                Err(e) => println!("Failed to accept connection: {}", e),
+            },
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                maintain_secondaries(&state).await;
+            }
+            // the deadline is worked out again.
+            _ = state.wake.notified() => {}
+        }
     }
 }
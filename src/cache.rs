@@ -2,10 +2,14 @@ use std::{cmp::Reverse, collections::HashMap, time::Instant};
 
 use priority_queue::PriorityQueue;
 
-use crate::{domain_name::DomainName, resource_record::DNSRecord, rr_types::RRType};
+use crate::{
+    domain_name::DomainName,
+    resource_record::DNSRecord,
+    rr_types::{DNSClass, RRType},
+};
 
 // A LRU cache with a fixed capacity that stores DNS answer records indexed by the
-// domain name and record type.
+// domain name, record type and class. Domain names are matched case-insensitively.
 #[derive(Debug, Clone)]
 pub struct DNSCache {
     cache: HashMap<CachedAnswerKey, CachedAnswer>,
@@ -19,12 +23,15 @@ pub struct DNSCache {
 pub struct CachedAnswerKey {
     domain_name: DomainName,
     q_type: RRType,
+    class: DNSClass,
 }
 
 // The answer thats stored in the cache.
 #[derive(Debug, Clone)]
 pub struct CachedAnswer {
     records: Vec<DNSRecord>,
+    // Whether the records were validated as secure using DNSSEC.
+    secure: bool,
     last_read: Instant,
     inserted_at: Instant,
 }
@@ -44,6 +51,10 @@ impl CachedAnswer {
     pub fn data(&self) -> Vec<DNSRecord> {
         self.records.clone()
     }
+
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
 }
 
 impl DNSCache {
@@ -57,12 +68,18 @@ impl DNSCache {
         }
     }
 
-    // Gets the cached answer for the provided domain name and record type. Returns None if
-    // the answer does not exist or it has expired.
-    pub fn get(&mut self, domain_name: &DomainName, q_type: &RRType) -> Option<CachedAnswer> {
+    // Gets the cached answer for the provided domain name, record type and class. Returns None
+    // if the answer does not exist or it has expired.
+    pub fn get(
+        &mut self,
+        domain_name: &DomainName,
+        q_type: &RRType,
+        class: &DNSClass,
+    ) -> Option<CachedAnswer> {
         let key = CachedAnswerKey {
             domain_name: domain_name.clone(),
             q_type: q_type.clone(),
+            class: class.clone(),
         };
 
         let mut expired = false;
@@ -85,16 +102,26 @@ impl DNSCache {
         None
     }
 
-    // Insert the answer records mapped to the provided domain name and record type. If a fresh
-    // record already exists for the provided key in the cache, then its a no-op.
-    pub fn insert(&mut self, domain_name: &DomainName, q_type: &RRType, records: Vec<DNSRecord>) {
+    // Insert the answer records mapped to the provided domain name, record type and class,
+    // along with whether they were validated as secure. If a fresh record already exists for
+    // the provided key in the cache, then its a no-op.
+    pub fn insert(
+        &mut self,
+        domain_name: &DomainName,
+        q_type: &RRType,
+        class: &DNSClass,
+        records: Vec<DNSRecord>,
+        secure: bool,
+    ) {
         let key = CachedAnswerKey {
             domain_name: domain_name.clone(),
             q_type: q_type.clone(),
+            class: class.clone(),
         };
         let now = Instant::now();
         let answer = CachedAnswer {
             records,
+            secure,
             last_read: now,
             inserted_at: now,
         };
@@ -0,0 +1,186 @@
+use crate::domain_name::{DomainName, MAX_NAME_LEN};
+use crate::error::{DNSResolverError, Result};
+
+// The maximum number of compression pointers followed while decoding a single name.
+// Every pointer has to point before the labels that precede it, so this is more than
+// enough for any name that a sane encoder would produce.
+const MAX_POINTER_HOPS: usize = 32;
+
+// Decoder is a cursor over a DNS message in its wire format. It reads the message from
+// its current offset up to a limit, which is the end of the message unless the decoder
+// is restricted to a part of it, such as the data of a resource record. Compressed
+// domain names can still point anywhere before them in the message.
+#[derive(Debug, Clone)]
+pub struct Decoder<'a> {
+    message: &'a [u8],
+    offset: usize,
+    limit: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(message: &'a [u8]) -> Decoder<'a> {
+        Decoder {
+            message,
+            offset: 0,
+            limit: message.len(),
+        }
+    }
+
+    // Returns the offset of the cursor from the start of the message.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // Returns the number of octets left before the limit.
+    pub fn remaining(&self) -> usize {
+        self.limit - self.offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    // Returns a decoder restricted to the next len octets and moves the cursor past them.
+    pub fn split(&mut self, len: usize, what: &str) -> Result<Decoder<'a>> {
+        if len > self.remaining() {
+            return Err(self.truncated(what));
+        }
+        let decoder = Decoder {
+            message: self.message,
+            offset: self.offset,
+            limit: self.offset + len,
+        };
+        self.offset += len;
+        Ok(decoder)
+    }
+
+    // Fails unless all the octets up to the limit have been read.
+    pub fn finish(&self, what: &str) -> Result<()> {
+        if !self.is_empty() {
+            return Err(DNSResolverError::Decode(
+                String::from(what),
+                format!(
+                    "{} unexpected octets at offset {}",
+                    self.remaining(),
+                    self.offset
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn read_bytes(&mut self, len: usize, what: &str) -> Result<&'a [u8]> {
+        if len > self.remaining() {
+            return Err(self.truncated(what));
+        }
+        let bytes = &self.message[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    // Reads all the octets up to the limit.
+    pub fn read_rest(&mut self) -> &'a [u8] {
+        let bytes = &self.message[self.offset..self.limit];
+        self.offset = self.limit;
+        bytes
+    }
+
+    pub fn read_u8(&mut self, what: &str) -> Result<u8> {
+        Ok(self.read_bytes(1, what)?[0])
+    }
+
+    pub fn read_u16(&mut self, what: &str) -> Result<u16> {
+        let bytes = self.read_bytes(2, what)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u32(&mut self, what: &str) -> Result<u32> {
+        let bytes = self.read_bytes(4, what)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // Reads a <character-string>, i.e. a length octet followed by that many octets.
+    pub fn read_character_string(&mut self, what: &str) -> Result<Vec<u8>> {
+        let len = usize::from(self.read_u8(what)?);
+        Ok(self.read_bytes(len, what)?.to_vec())
+    }
+
+    // Reads a domain name, which may be compressed (RFC 1035 section 4.1.4). The labels
+    // before the first pointer have to lie within the limit, while a pointer has to point
+    // before the labels that precede it, which rules out loops as well as forward and
+    // self pointers. The cursor ends up after the first pointer or the root label.
+    pub fn read_name(&mut self, what: &str) -> Result<DomainName> {
+        let invalid = |offset: usize, reason: &str| {
+            DNSResolverError::Decode(
+                String::from(what),
+                format!("{} in name at offset {}", reason, offset),
+            )
+        };
+
+        let mut labels = vec![];
+        // the length of the name in its wire format, including the root label.
+        let mut name_len = 1;
+        let mut hops = 0;
+        // the position being read, where the labels being read started and how far they
+        // can go; only the labels before the first pointer are bound by the limit.
+        let mut pos = self.offset;
+        let mut start = self.offset;
+        let mut limit = self.limit;
+        // the position after the name, which is known once the first pointer is found.
+        let mut end = None;
+        loop {
+            let len = *self
+                .message
+                .get(pos)
+                .filter(|_| pos < limit)
+                .ok_or_else(|| invalid(pos, "unexpected end of data"))?;
+            match len >> 6 {
+                0b00 if len == 0 => {
+                    pos += 1;
+                    break;
+                }
+                0b00 => {
+                    let len = usize::from(len);
+                    name_len += len + 1;
+                    if name_len > MAX_NAME_LEN {
+                        return Err(invalid(pos, "name longer than 255 octets"));
+                    }
+                    if pos + 1 + len > limit {
+                        return Err(invalid(pos, "unexpected end of data"));
+                    }
+                    labels.push(self.message[pos + 1..pos + 1 + len].to_vec());
+                    pos += 1 + len;
+                }
+                0b11 => {
+                    let next = *self
+                        .message
+                        .get(pos + 1)
+                        .filter(|_| pos + 1 < limit)
+                        .ok_or_else(|| invalid(pos, "unexpected end of data"))?;
+                    let target = usize::from(u16::from_be_bytes([len & 0b0011_1111, next]));
+                    if target >= start {
+                        return Err(invalid(pos, "pointer doesn't point backwards"));
+                    }
+                    hops += 1;
+                    if hops > MAX_POINTER_HOPS {
+                        return Err(invalid(pos, "too many compression pointers"));
+                    }
+                    end.get_or_insert(pos + 2);
+                    pos = target;
+                    start = target;
+                    limit = self.message.len();
+                }
+                _ => return Err(invalid(pos, "unsupported label type")),
+            }
+        }
+        self.offset = end.unwrap_or(pos);
+        Ok(DomainName::from_labels(labels))
+    }
+
+    fn truncated(&self, what: &str) -> DNSResolverError {
+        DNSResolverError::Decode(
+            String::from(what),
+            format!("unexpected end of data at offset {}", self.offset),
+        )
+    }
+}
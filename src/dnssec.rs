@@ -0,0 +1,471 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_encoding::{BASE32HEX_NOPAD, HEXUPPER};
+use itertools::Itertools;
+use ring::digest;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+
+use crate::domain_name::DomainName;
+use crate::error::Result;
+use crate::rdata::{DNSKEY, DS, NSEC, NSEC3, RRSIG};
+use crate::resource_record::DNSRecord;
+use crate::rr_types::RRType;
+
+// The DNSSEC algorithms that we can validate signatures of.
+const RSASHA256: u8 = 8;
+const ECDSAP256SHA256: u8 = 13;
+const ECDSAP384SHA384: u8 = 14;
+const ED25519: u8 = 15;
+
+// The digest types of DS records that we understand.
+const SHA1: u8 = 1;
+const SHA256: u8 = 2;
+const SHA384: u8 = 4;
+
+// The most iterations of NSEC3 hashing that we compute (RFC 9276 section 3.2). NSEC3
+// records with more than that are costly to check, so they prove nothing and the answers
+// that rely on them are treated as insecure.
+const MAX_NSEC3_ITERATIONS: u16 = 100;
+
+// ValidationStatus is the outcome of validating data using DNSSEC (RFC 4035 section 4.3).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationStatus {
+    // There's a chain of signed records from a trust anchor to the data.
+    Secure,
+    // There's a proof that the data belongs to a zone that isn't signed.
+    Insecure,
+    // The data should have been signed but it could not be validated; the
+    // reason is attached.
+    Bogus(String),
+}
+
+impl ValidationStatus {
+    // Combines the two statuses into the least secure of the two.
+    pub fn and(self, other: ValidationStatus) -> ValidationStatus {
+        match (self, other) {
+            (ValidationStatus::Bogus(reason), _) | (_, ValidationStatus::Bogus(reason)) => {
+                ValidationStatus::Bogus(reason)
+            }
+            (ValidationStatus::Insecure, _) | (_, ValidationStatus::Insecure) => {
+                ValidationStatus::Insecure
+            }
+            _ => ValidationStatus::Secure,
+        }
+    }
+}
+
+// Denial is what a set of NSEC or NSEC3 records proves about a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denial {
+    // The name does not exist.
+    NXDomain,
+    // The name exists but has no records of the type. delegation is true if the name is
+    // a delegation to a child zone, as seen from the parent.
+    NoData { delegation: bool },
+    // The name falls within an Opt-Out span (RFC 5155 section 6), i.e. it may be an
+    // unsigned delegation.
+    OptOut,
+    // The NSEC3 records use more iterations than MAX_NSEC3_ITERATIONS, so they weren't
+    // checked.
+    ExcessiveIterations,
+}
+
+// Returns the DS record of the root zone's key signing key (KSK-2017), which is used
+// as the trust anchor by default.
+pub fn root_trust_anchor() -> DS {
+    let digest = HEXUPPER
+        .decode(b"E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D")
+        .expect("the root trust anchor is valid hex");
+    DS::new(20326, RSASHA256, SHA256, digest)
+}
+
+pub fn is_supported_algorithm(algorithm: u8) -> bool {
+    matches!(
+        algorithm,
+        RSASHA256 | ECDSAP256SHA256 | ECDSAP384SHA384 | ED25519
+    )
+}
+
+pub fn is_supported_digest_type(digest_type: u8) -> bool {
+    matches!(digest_type, SHA1 | SHA256 | SHA384)
+}
+
+// Returns the current time as the number of seconds since the UNIX epoch, which is how
+// the validity period of a RRSIG is expressed.
+pub fn now() -> u32 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    u32::try_from(secs).unwrap_or(u32::MAX)
+}
+
+// Returns the number of labels in the name as used by RRSIGs, i.e. without the
+// root and a leading wildcard label.
+pub fn label_count(name: &DomainName) -> u8 {
+    let labels = name.labels();
+    let count = if labels.first().map(Vec::as_slice) == Some(b"*") {
+        labels.len() - 1
+    } else {
+        labels.len()
+    };
+    u8::try_from(count).unwrap_or(u8::MAX)
+}
+
+// Builds the data that is signed by the RRSIG, i.e. the fields of the RRSIG followed by
+// the covered records in their canonical form and order (RFC 4034 section 3.1.8.1).
+pub fn signed_data(rrsig: &RRSIG, records: &[&DNSRecord]) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    rrsig.encode_without_signature(&mut data, true)?;
+
+    let owner = match records.first() {
+        Some(record) => record.name(),
+        None => return Ok(data),
+    };
+    // if the records were synthesized from a wildcard, then the signature is over the
+    // wildcard itself.
+    let owner = if rrsig.labels() < label_count(owner) {
+        owner.suffix(usize::from(rrsig.labels())).child(b"*")
+    } else {
+        owner.clone()
+    };
+    let mut owner_bytes = vec![];
+    owner.encode_canonical(&mut owner_bytes)?;
+
+    let mut rdatas = vec![];
+    for record in records {
+        let mut rdata = vec![];
+        match record.rdata() {
+            Some(parsed) => parsed.encode_canonical(&mut rdata)?,
+            None => rdata.extend(record.raw_data()),
+        }
+        rdatas.push(rdata);
+    }
+    rdatas.sort();
+    rdatas.dedup();
+
+    for rdata in rdatas {
+        data.extend(&owner_bytes);
+        data.extend(rrsig.type_covered().code().to_be_bytes());
+        data.extend(records[0].class().code().to_be_bytes());
+        data.extend(rrsig.original_ttl().to_be_bytes());
+        data.extend((rdata.len() as u16).to_be_bytes());
+        data.extend(rdata);
+    }
+    Ok(data)
+}
+
+// Verifies that the RRSIG is a valid signature over the records, created using the key.
+// The records must make up a RRset, i.e. share the same owner, type and class.
+pub fn verify_rrsig(
+    rrsig: &RRSIG,
+    records: &[&DNSRecord],
+    key: &DNSKEY,
+    now: u32,
+) -> std::result::Result<(), String> {
+    let owner = match records.first() {
+        Some(record) => record.name(),
+        None => return Err(String::from("there are no records to verify")),
+    };
+    let rrset = format!("{} {}", owner, rrsig.type_covered());
+    if records.iter().any(|record| {
+        record.name() != owner
+            || record.r_type() != rrsig.type_covered()
+            || record.class() != records[0].class()
+    }) {
+        return Err(format!("the records of {} don't make up a RRset", rrset));
+    }
+    if !owner.is_subdomain_of(rrsig.signer_name()) {
+        return Err(format!(
+            "{} is signed by {}, which isn't its zone",
+            rrset,
+            rrsig.signer_name()
+        ));
+    }
+    if rrsig.labels() > label_count(owner) {
+        return Err(format!("the RRSIG over {} has too many labels", rrset));
+    }
+    if now < rrsig.inception() || now > rrsig.expiration() {
+        return Err(format!(
+            "the RRSIG over {} is not valid at this time",
+            rrset
+        ));
+    }
+    if key.protocol() != 3
+        || !key.is_zone_key()
+        || key.algorithm() != rrsig.algorithm()
+        || key.key_tag() != rrsig.key_tag()
+    {
+        return Err(format!("the RRSIG over {} is not made by the key", rrset));
+    }
+
+    let data = signed_data(rrsig, records).map_err(|e| e.to_string())?;
+    if verify_signature(key, &data, rrsig.signature()) {
+        Ok(())
+    } else {
+        Err(format!("the RRSIG over {} is invalid", rrset))
+    }
+}
+
+// Verifies the signature over the data using the public key.
+fn verify_signature(key: &DNSKEY, data: &[u8], sig: &[u8]) -> bool {
+    let public_key = key.public_key();
+    match key.algorithm() {
+        RSASHA256 => {
+            // the public key is made up of the length of the exponent (RFC 3110), either
+            // as one octet or as a zero octet followed by two octets, the exponent and
+            // the modulus.
+            let (exponent_len, rest) = match public_key {
+                [0, a, b, rest @ ..] => (usize::from(u16::from_be_bytes([*a, *b])), rest),
+                [len, rest @ ..] => (usize::from(*len), rest),
+                [] => return false,
+            };
+            if rest.len() <= exponent_len {
+                return false;
+            }
+            let (e, n) = rest.split_at(exponent_len);
+            let components = RsaPublicKeyComponents { n, e };
+            components
+                .verify(
+                    &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
+                    data,
+                    sig,
+                )
+                .is_ok()
+        }
+        ECDSAP256SHA256 | ECDSAP384SHA384 => {
+            let algorithm = if key.algorithm() == ECDSAP256SHA256 {
+                &signature::ECDSA_P256_SHA256_FIXED
+            } else {
+                &signature::ECDSA_P384_SHA384_FIXED
+            };
+            // the public key is the uncompressed point without its 0x04 prefix (RFC 6605).
+            let mut point = vec![4];
+            point.extend(public_key);
+            UnparsedPublicKey::new(algorithm, point)
+                .verify(data, sig)
+                .is_ok()
+        }
+        ED25519 => UnparsedPublicKey::new(&signature::ED25519, public_key)
+            .verify(data, sig)
+            .is_ok(),
+        _ => false,
+    }
+}
+
+// Returns true if the DS record is a digest of the key, which belongs to the provided zone.
+pub fn ds_matches(zone: &DomainName, key: &DNSKEY, ds: &DS) -> bool {
+    if ds.key_tag() != key.key_tag() || ds.algorithm() != key.algorithm() {
+        return false;
+    }
+    let algorithm = match ds.digest_type() {
+        SHA1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        SHA256 => &digest::SHA256,
+        SHA384 => &digest::SHA384,
+        _ => return false,
+    };
+
+    let mut data = vec![];
+    if zone.encode_canonical(&mut data).is_err() {
+        return false;
+    }
+    data.extend(key.flags().to_be_bytes());
+    data.push(key.protocol());
+    data.push(key.algorithm());
+    data.extend(key.public_key());
+    digest::digest(algorithm, &data).as_ref() == ds.digest()
+}
+
+// Returns the hash of the name as used by NSEC3 records (RFC 5155 section 5), i.e.
+// iterations of SHA-1 over the name in its canonical form and the salt.
+pub fn nsec3_hash(name: &DomainName, salt: &[u8], iterations: u16) -> Vec<u8> {
+    let mut wire = vec![];
+    // a name with a label that's too long can't be hashed, but it can't exist either.
+    let _ = name.encode_canonical(&mut wire);
+
+    let hash = |data: &[u8]| {
+        let mut ctx = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+        ctx.update(data);
+        ctx.update(salt);
+        ctx.finish().as_ref().to_vec()
+    };
+    let mut hashed = hash(&wire);
+    for _ in 0..iterations {
+        hashed = hash(&hashed);
+    }
+    hashed
+}
+
+// Returns true if the NSEC3 record uses more iterations than we compute.
+pub fn excessive_iterations(nsec3: &NSEC3) -> bool {
+    nsec3.iterations() > MAX_NSEC3_ITERATIONS
+}
+
+// Returns true if name falls strictly between owner and next in the canonical order,
+// where the last NSEC of a zone wraps around to the apex.
+fn nsec_covers(owner: &DomainName, next: &DomainName, name: &DomainName) -> bool {
+    let after_owner = owner < name;
+    let before_next = name < next;
+    if owner < next {
+        after_owner && before_next
+    } else {
+        after_owner || before_next
+    }
+}
+
+// Returns what the NSEC records prove about the query (RFC 4035 section 5.4). The
+// records must already have been validated.
+pub fn nsec_denial(
+    qname: &DomainName,
+    qtype: &RRType,
+    nxdomain: bool,
+    nsecs: &[(DomainName, NSEC)],
+) -> Option<Denial> {
+    if !nxdomain {
+        let (_, nsec) = nsecs.iter().find(|(owner, _)| owner == qname)?;
+        return no_data(qtype, nsec.types());
+    }
+
+    // the name must be covered by a NSEC and so must be the wildcard at its closest
+    // encloser, which is the longest ancestor shared with either side of the NSEC.
+    let (owner, nsec) = nsecs
+        .iter()
+        .find(|(owner, nsec)| nsec_covers(owner, nsec.next_domain_name(), qname))?;
+    let shared = |other: &DomainName| {
+        (0..=qname.labels().len())
+            .rev()
+            .find(|n| other.is_subdomain_of(&qname.suffix(*n)))
+            .unwrap_or(0)
+    };
+    let closest_encloser = qname.suffix(shared(owner).max(shared(nsec.next_domain_name())));
+    let wildcard = closest_encloser.child(b"*");
+    nsecs
+        .iter()
+        .any(|(owner, nsec)| nsec_covers(owner, nsec.next_domain_name(), &wildcard))
+        .then_some(Denial::NXDomain)
+}
+
+// Returns what the type bitmap of a record matching the query name proves about the
+// query type.
+fn no_data(qtype: &RRType, types: &[RRType]) -> Option<Denial> {
+    if types.contains(qtype) || types.contains(&RRType::CNAME) {
+        return None;
+    }
+    let delegation = types.contains(&RRType::NS) && !types.contains(&RRType::SOA);
+    // the absence of a DS must be proven by the parent, while the absence of anything
+    // else must be proven by the child.
+    match qtype {
+        RRType::DS if types.contains(&RRType::SOA) => None,
+        RRType::DS => Some(Denial::NoData { delegation }),
+        _ if delegation => None,
+        _ => Some(Denial::NoData { delegation }),
+    }
+}
+
+// Returns the hash in the owner name of a NSEC3 record.
+fn nsec3_owner_hash(owner: &DomainName) -> Option<Vec<u8>> {
+    let first = owner.labels().first()?;
+    BASE32HEX_NOPAD.decode(&first.to_ascii_uppercase()).ok()
+}
+
+// Returns true if the hash falls strictly between the owner's hash and the next hash,
+// where the last NSEC3 of a zone wraps around to the first one.
+fn nsec3_covers(owner_hash: &[u8], nsec3: &NSEC3, hash: &[u8]) -> bool {
+    let next = nsec3.next_hashed_owner_name();
+    if owner_hash < next {
+        owner_hash < hash && hash < next
+    } else {
+        owner_hash < hash || hash < next
+    }
+}
+
+// Returns what the NSEC3 records prove about the query (RFC 5155 section 8). The
+// records must already have been validated.
+pub fn nsec3_denial(
+    qname: &DomainName,
+    qtype: &RRType,
+    nxdomain: bool,
+    nsec3s: &[(DomainName, NSEC3)],
+) -> Option<Denial> {
+    // all the NSEC3 records of a zone use the same parameters.
+    let (_, first) = nsec3s
+        .iter()
+        .find(|(_, nsec3)| nsec3.hash_algorithm() == 1)?;
+    if excessive_iterations(first) {
+        return Some(Denial::ExcessiveIterations);
+    }
+    let hash = |name: &DomainName| nsec3_hash(name, first.salt(), first.iterations());
+    let hashed = nsec3s
+        .iter()
+        .filter_map(|(owner, nsec3)| Some((nsec3_owner_hash(owner)?, nsec3)))
+        .collect_vec();
+    let matching = |name: &DomainName| {
+        let name_hash = hash(name);
+        hashed
+            .iter()
+            .find(|(owner_hash, _)| *owner_hash == name_hash)
+            .map(|(_, nsec3)| *nsec3)
+    };
+    let covering = |name: &DomainName| {
+        let name_hash = hash(name);
+        hashed
+            .iter()
+            .find(|(owner_hash, nsec3)| nsec3_covers(owner_hash, nsec3, &name_hash))
+            .map(|(_, nsec3)| *nsec3)
+    };
+
+    if !nxdomain {
+        if let Some(nsec3) = matching(qname) {
+            return no_data(qtype, nsec3.types());
+        }
+    }
+
+    // the closest encloser proof: the closest encloser must exist and the next closer
+    // name, i.e. the name one label longer on the way to the query name, must not.
+    let qname_labels = qname.labels().len();
+    let (closest_encloser, next_closer) = (0..qname_labels)
+        .rev()
+        .map(|n| (qname.suffix(n), qname.suffix(n + 1)))
+        .find(|(encloser, _)| matching(encloser).is_some())?;
+    let next_closer = covering(&next_closer)?;
+
+    // an Opt-Out span covering the next closer name may hide an unsigned delegation,
+    // so the name may exist after all (RFC 5155 section 9.2).
+    if nxdomain && next_closer.opt_out() {
+        Some(Denial::OptOut)
+    } else if nxdomain {
+        covering(&closest_encloser.child(b"*")).map(|_| Denial::NXDomain)
+    } else if qtype == &RRType::DS && next_closer.opt_out() {
+        Some(Denial::OptOut)
+    } else {
+        None
+    }
+}
+
+// Returns true if the NSEC or NSEC3 records prove that the query name does not exist,
+// which is required when an answer was synthesized from a wildcard. labels is the
+// number of labels in the RRSIG over the answer.
+pub fn wildcard_proof(
+    qname: &DomainName,
+    labels: u8,
+    nsecs: &[(DomainName, NSEC)],
+    nsec3s: &[(DomainName, NSEC3)],
+) -> bool {
+    if nsecs
+        .iter()
+        .any(|(owner, nsec)| nsec_covers(owner, nsec.next_domain_name(), qname))
+    {
+        return true;
+    }
+
+    let next_closer = qname.suffix(usize::from(labels) + 1);
+    nsec3s.iter().any(|(owner, nsec3)| {
+        if excessive_iterations(nsec3) {
+            return false;
+        }
+        let hash = nsec3_hash(&next_closer, nsec3.salt(), nsec3.iterations());
+        nsec3_owner_hash(owner)
+            .map(|owner_hash| nsec3_covers(&owner_hash, nsec3, &hash))
+            .unwrap_or(false)
+    })
+}
@@ -1,218 +1,372 @@
-use crate::error::{map_decode_err, map_encode_err, DNSResolverError, Result};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::error::{map_encode_err, DNSResolverError, Result};
+use idna::uts46::{AsciiDenyList, DnsLength, Hyphens, Uts46};
 use itertools::Itertools;
 
-// DomainName represents a fully form domain name.
-#[derive(Debug, Clone, Eq, Hash, PartialEq)]
-pub struct DomainName(pub String);
-
-// LabelSequenceParser is a parser that knows how to construct
-// domain names where a domain name is represented as:
-// 1. a sequence of labels ending in a zero octet
-// 2. a pointer
-// 3. a sequence of labels ending with a pointer
-pub struct LabelSequenceParser(Option<Vec<String>>);
-
-impl LabelSequenceParser {
-    pub fn new() -> LabelSequenceParser {
-        LabelSequenceParser(None)
-    }
-
-    // Constructs the domain name from its wire format.
-    // response is supposed to be the entire DNS message required
-    // to go back to where a pointer is pointing.
-    pub fn construct_domain_name<'a, T>(
-        &mut self,
-        iter: &mut T,
-        response: Option<&mut T>,
-    ) -> Result<DomainName>
-    where
-        T: Iterator<Item = &'a u8> + Clone,
-    {
-        if self.0 == None {
-            self.0 = Some(vec![]);
-        }
-        let parts = self.0.as_mut().expect("");
+// DomainName represents a fully formed domain name as a sequence of labels, without the
+// empty root label. Names are compared, hashed and ordered case-insensitively, where the
+// order is the canonical DNS order (RFC 4034 section 6.1).
+#[derive(Debug, Clone)]
+pub struct DomainName {
+    labels: Vec<Vec<u8>>,
+}
 
-        loop {
-            if let Some(len) = iter.next() {
-                // If length is 0, then there are no more labels.
-                if *len == 0 {
-                    break;
-                }
-                // check if the length is actually a pointer
-                if len.leading_ones() == 2 {
-                    if let Some(val) = response {
-                        let domain_name = self.construct_compressed_domain_name(*len, iter, val)?;
-                        return Ok(domain_name);
-                    }
-                }
-                let len_usize = usize::try_from(*len).map_err(|e| map_decode_err("name", &e))?;
-
-                // to check if the data ends with a pointer
-                let mut pp_iter = iter.clone();
-                // if length is less than 2, then it can't be a pointer.
-                if len_usize >= 2 {
-                    // Advance the iterator till the (potential) pointer.
-                    for _ in 0..len_usize - 2 {
-                        pp_iter.next();
-                    }
-                    if let Some(pp) = pp_iter.next() {
-                        // check if it is indeed a pointer
-                        if pp.leading_ones() == 2 {
-                            if let Some(val) = response {
-                                let domain_name =
-                                    self.construct_compressed_domain_name(*pp, &mut pp_iter, val)?;
-                                return Ok(domain_name);
-                            }
-                        }
-                    }
-                }
+// NameCompressor keeps track of where the names in a message have been encoded, so that
+// names encoded later on can point to them instead of repeating their labels (RFC 1035
+// section 4.1.4). Suffixes are matched exactly, including their case, so that compressing
+// a name never changes it.
+#[derive(Debug, Default)]
+pub struct NameCompressor {
+    offsets: HashMap<Vec<Vec<u8>>, u16>,
+}
 
-                let data_bytes = iter.take(len_usize);
-                let labels = data_bytes.map(|x| *x).collect_vec();
-                let label_str = String::from_utf8_lossy(&labels).into_owned();
-                parts.push(label_str);
-            } else {
-                break;
-            }
+impl NameCompressor {
+    pub fn new() -> NameCompressor {
+        NameCompressor {
+            offsets: HashMap::new(),
         }
-
-        let name = parts.join(".");
-        Ok(DomainName(name))
-    }
-
-    fn construct_compressed_domain_name<'a, T>(
-        &mut self,
-        length: u8,
-        iter: &mut T,
-        response: &mut T,
-    ) -> Result<DomainName>
-    where
-        T: Iterator<Item = &'a u8> + Clone,
-    {
-        let next = iter.next().ok_or(DNSResolverError::Decode(
-            String::from("compressed_name"),
-            String::from("could not parse bytes"),
-        ))?;
-        // get offset; the place we need to go to
-        let offset_bytes = u16::from_be_bytes([length & 0b0011_1111, *next]);
-        let offset =
-            usize::try_from(offset_bytes).map_err(|e| map_decode_err("compressed_name", &e))?;
-
-        // sad but necessary
-        let mut og = response.clone();
-
-        // move iterator to the offset
-        response.nth(offset - 1);
-        let domain_name = self.construct_domain_name(response, Some(&mut og))?;
-        Ok(domain_name)
     }
 }
 
+// The maximum lengths of a label and of a name in its wire format (RFC 1035 section 2.3.4).
+const MAX_LABEL_LEN: usize = 63;
+pub(crate) const MAX_NAME_LEN: usize = 255;
+
 impl DomainName {
+    // Creates a domain name out of its presentation format without validating it, in
+    // which case invalid names are rejected when they're encoded. Use str::parse to
+    // reject them upfront.
     pub fn new(domain: String) -> DomainName {
-        DomainName(domain)
+        let labels = parse_labels(&domain).unwrap_or_else(|_| {
+            let domain = domain.strip_suffix('.').unwrap_or(&domain);
+            if domain.is_empty() {
+                return vec![];
+            }
+            domain
+                .split('.')
+                .map(|label| label.as_bytes().to_vec())
+                .collect_vec()
+        });
+        DomainName { labels }
+    }
+
+    // Returns the root domain, i.e. the name without any labels.
+    pub fn root() -> DomainName {
+        DomainName { labels: vec![] }
+    }
+
+    pub fn from_labels(labels: Vec<Vec<u8>>) -> DomainName {
+        DomainName { labels }
+    }
+
+    // Returns the labels of the name, starting with the leftmost one.
+    pub fn labels(&self) -> &[Vec<u8>] {
+        &self.labels
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    // Returns the name without its leftmost label. The root domain has no parent.
+    pub fn parent(&self) -> Option<DomainName> {
+        if self.is_root() {
+            return None;
+        }
+        Some(DomainName::from_labels(self.labels[1..].to_vec()))
+    }
+
+    // Returns the name made up of the last n labels of this name.
+    pub fn suffix(&self, n: usize) -> DomainName {
+        let skip = self.labels.len().saturating_sub(n);
+        DomainName::from_labels(self.labels[skip..].to_vec())
+    }
+
+    // Returns the name with the provided label prepended to this name.
+    pub fn child(&self, label: &[u8]) -> DomainName {
+        let mut labels = vec![label.to_vec()];
+        labels.extend(self.labels.iter().cloned());
+        DomainName::from_labels(labels)
+    }
+
+    // Returns true if this name is the same as or is below the provided name.
+    pub fn is_subdomain_of(&self, other: &DomainName) -> bool {
+        self.labels.len() >= other.labels.len() && self.suffix(other.labels.len()) == *other
+    }
+
+    // Checks that the labels of the name aren't empty and that neither the labels nor
+    // the name are too long.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| {
+            Err(DNSResolverError::InvalidName(
+                self.to_string(),
+                String::from(reason),
+            ))
+        };
+        if self.labels.iter().any(|label| label.is_empty()) {
+            return invalid("labels can't be empty");
+        }
+        if self.labels.iter().any(|label| label.len() > MAX_LABEL_LEN) {
+            return invalid("labels can't be longer than 63 octets");
+        }
+        // every label is preceded by its length and the name ends with the root label.
+        let len = self
+            .labels
+            .iter()
+            .map(|label| label.len() + 1)
+            .sum::<usize>()
+            + 1;
+        if len > MAX_NAME_LEN {
+            return invalid("names can't be longer than 255 octets");
+        }
+        Ok(())
+    }
+
+    // Renders the name in its presentation format like Display does, except that A-labels
+    // (xn--) are decoded into the Unicode labels they represent. A-labels that aren't
+    // valid punycode are left as they are.
+    pub fn to_unicode(&self) -> String {
+        if self.is_root() {
+            return String::from(".");
+        }
+        let mut rendered = String::new();
+        for (i, label) in self.labels.iter().enumerate() {
+            if i > 0 {
+                rendered.push('.');
+            }
+            if label.len() > 4 && label[..4].eq_ignore_ascii_case(b"xn--") {
+                let (unicode, result) =
+                    Uts46::new().to_unicode(label, AsciiDenyList::EMPTY, Hyphens::Allow);
+                if result.is_ok() {
+                    rendered.push_str(&unicode);
+                    continue;
+                }
+            }
+            // writing to a String can't fail.
+            let _ = write_label(&mut rendered, label);
+        }
+        rendered
     }
 
     // Encodes the domain name into a sequence of labels ending in a zero octect.
     pub fn encode(&self, encoded: &mut Vec<u8>) -> Result<()> {
-        let name = &self.0;
-        let parts: Vec<&str> = name.split('.').collect();
-        for part in parts {
-            let len = u8::try_from(part.len()).map_err(|e| map_encode_err("name", &e))?;
-            let content = part.as_bytes();
+        self.validate()?;
+        for label in &self.labels {
+            let len = u8::try_from(label.len()).map_err(|e| map_encode_err("name", &e))?;
             encoded.push(len);
-            encoded.extend(content);
+            encoded.extend(label);
         }
         encoded.extend(0_u8.to_be_bytes());
         Ok(())
     }
 
-    // Encodes the domain name into either a pointer or a sequence of labels ending in a pointer.
-    pub fn encode_with_compression(&self, encoded: &mut Vec<u8>) -> Result<()> {
-        let mut non_compressed = vec![];
-        self.encode(&mut non_compressed)?;
-        // Last element is a 0 that we don't care about.
-        non_compressed.pop();
-        let mut iter = non_compressed.iter();
-
-        // a vector where each element is a tuple consisting of the label size and the
-        // label itself.
-        let mut parts = vec![];
-        loop {
-            if let Some(len) = iter.next() {
-                let len_usize = usize::try_from(*len).map_err(|e| map_encode_err("name", &e))?;
-
-                let mut label = vec![];
-                for _ in 0..len_usize {
-                    let next = iter.next().ok_or(DNSResolverError::Encode(
-                        String::from("compressed_name"),
-                        String::from("could not parse bytes"),
-                    ))?;
-                    label.push(*next);
-                }
+    // Encodes the domain name in its canonical form (RFC 4034 section 6.2), i.e. as an
+    // uncompressed sequence of lowercased labels.
+    pub fn encode_canonical(&self, encoded: &mut Vec<u8>) -> Result<()> {
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| label.to_ascii_lowercase())
+            .collect_vec();
+        DomainName::from_labels(labels).encode(encoded)
+    }
 
-                parts.push((len_usize, label));
-            } else {
-                break;
+    // Encodes the domain name at the end of the message being built in encoded, pointing
+    // to the longest suffix of the name that has already been encoded in the message, if
+    // any. The suffixes of the name are remembered in the compressor for later names.
+    pub fn encode_with_compression(
+        &self,
+        encoded: &mut Vec<u8>,
+        compressor: &mut NameCompressor,
+    ) -> Result<()> {
+        self.validate()?;
+        for (i, label) in self.labels.iter().enumerate() {
+            let suffix = &self.labels[i..];
+            if let Some(offset) = compressor.offsets.get(suffix) {
+                // the first two bits mark the offset as a pointer.
+                encoded.extend((offset | 0b1100_0000_0000_0000).to_be_bytes());
+                return Ok(());
             }
-        }
-        // reverse the vector, since we want to search for occurences from the
-        // last label onwards.
-        parts.reverse();
-
-        // offset is the position where the pointer in our answer points to.
-        let mut offset = None;
-        let mut parts_iter = parts.iter();
-        loop {
-            if let Some(part) = parts_iter.next() {
-                // check if the label is already present somewhere in the encoded message.
-                if let Some(idx) = find_subset_index(&encoded, &part.1) {
-                    // subtract 1 here since we need to include the length octect that appears
-                    // before the start of label.
-                    offset = Some(idx - 1);
-                } else {
-                    break;
+            // pointers can only point within the first 16 KiB of a message.
+            if let Ok(offset) = u16::try_from(encoded.len()) {
+                if offset < 0b0100_0000_0000_0000 {
+                    compressor.offsets.insert(suffix.to_vec(), offset);
                 }
-            } else {
-                break;
             }
+            let len = u8::try_from(label.len()).map_err(|e| map_encode_err("name", &e))?;
+            encoded.push(len);
+            encoded.extend(label);
         }
+        encoded.push(0);
+        Ok(())
+    }
+}
 
-        if let Some(offset_idx) = offset {
-            // push the non-compressable labels first.
-            parts_iter.for_each(|part| {
-                encoded.push(part.0 as u8);
-                encoded.extend(part.1.clone());
-            });
-
-            let offset_u16 = offset_idx as u16;
-            let mut offset_bytes = offset_u16.to_be_bytes();
-            // this bitwise operation makes it clear that this is a pointer.
-            offset_bytes[0] |= 0b1100_0000;
-            encoded.extend(offset_bytes);
-        } else {
-            encoded.extend(non_compressed);
+impl PartialEq for DomainName {
+    fn eq(&self, other: &Self) -> bool {
+        self.labels.len() == other.labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(&other.labels)
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl Eq for DomainName {}
+
+impl Hash for DomainName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for label in &self.labels {
+            label.to_ascii_lowercase().hash(state);
         }
+    }
+}
 
-        Ok(())
+impl Ord for DomainName {
+    // Compares the names by their lowercased labels, starting from the rightmost one.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.labels
+            .iter()
+            .rev()
+            .map(|label| label.to_ascii_lowercase())
+            .cmp(
+                other
+                    .labels
+                    .iter()
+                    .rev()
+                    .map(|label| label.to_ascii_lowercase()),
+            )
     }
 }
 
-fn find_subset_index(superset: &Vec<u8>, subset: &Vec<u8>) -> Option<usize> {
-    if subset.is_empty() {
-        return None;
+impl PartialOrd for DomainName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
+}
 
-    if subset.len() > superset.len() {
-        return None;
+impl FromStr for DomainName {
+    type Err = DNSResolverError;
+
+    // Parses and validates the name from its presentation format (RFC 1035 section 5.1).
+    fn from_str(s: &str) -> Result<Self> {
+        let labels = parse_labels(s)
+            .map_err(|reason| DNSResolverError::InvalidName(s.to_owned(), reason))?;
+        let name = DomainName { labels };
+        name.validate()?;
+        Ok(name)
     }
+}
 
-    for i in 0..=(superset.len() - subset.len()) {
-        if superset[i..(i + subset.len())] == *subset {
-            return Some(i);
+impl Display for DomainName {
+    // Renders the name in its presentation format, where special characters within a
+    // label are escaped as \X and non-printable octets as \DDD.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_root() {
+            return write!(f, ".");
         }
+        for (i, label) in self.labels.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write_label(f, label)?;
+        }
+        Ok(())
     }
+}
+
+// Writes a label in its presentation format.
+fn write_label(f: &mut impl std::fmt::Write, label: &[u8]) -> std::fmt::Result {
+    for byte in label {
+        match byte {
+            b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                write!(f, "\\{}", char::from(*byte))?
+            }
+            0x21..=0x7e => write!(f, "{}", char::from(*byte))?,
+            _ => write!(f, "\\{:03}", byte)?,
+        }
+    }
+    Ok(())
+}
+
+// Parses the labels of a name in its presentation format, where the labels are separated
+// by dots and may contain escaped characters, i.e. \X for a character X, such as a dot,
+// and \DDD for the octet with the decimal value DDD. A trailing dot is optional.
+fn parse_labels(s: &str) -> std::result::Result<Vec<Vec<u8>>, String> {
+    if s == "." {
+        return Ok(vec![]);
+    }
+    // the labels along with whether they contain non-ASCII characters; escaped octets
+    // don't count, since they're taken as they are.
+    let mut labels = vec![];
+    let mut label = vec![];
+    let mut unicode = false;
+    let mut bytes = s.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'.' => labels.push((std::mem::take(&mut label), std::mem::take(&mut unicode))),
+            b'\\' => {
+                let escaped = bytes
+                    .next()
+                    .ok_or_else(|| String::from("incomplete escape"))?;
+                if !escaped.is_ascii_digit() {
+                    label.push(escaped);
+                    continue;
+                }
+                let mut value = u16::from(escaped - b'0');
+                for _ in 0..2 {
+                    match bytes.next() {
+                        Some(digit) if digit.is_ascii_digit() => {
+                            value = value * 10 + u16::from(digit - b'0')
+                        }
+                        _ => return Err(String::from("\\DDD escapes need three digits")),
+                    }
+                }
+                let value = u8::try_from(value)
+                    .map_err(|_| String::from("\\DDD escapes can't be larger than 255"))?;
+                label.push(value);
+            }
+            _ => {
+                unicode |= !byte.is_ascii();
+                label.push(byte);
+            }
+        }
+    }
+    // a trailing dot separates the last label from the root, which has no label of its own.
+    if !label.is_empty() {
+        labels.push((label, unicode));
+    }
+    let mut ascii_labels = vec![];
+    for (label, unicode) in labels {
+        if unicode {
+            ascii_labels.extend(to_ascii_labels(label)?);
+        } else {
+            ascii_labels.push(label);
+        }
+    }
+    Ok(ascii_labels)
+}
 
-    None
+// Converts a label containing non-ASCII characters into its A-label, i.e. its punycode
+// form prefixed with xn--, using the UTS #46 mapping. Since the mapping turns dots such
+// as the ideographic full stop into ASCII dots, the label may turn into several labels.
+fn to_ascii_labels(label: Vec<u8>) -> std::result::Result<Vec<Vec<u8>>, String> {
+    let ascii = Uts46::new()
+        .to_ascii(
+            &label,
+            AsciiDenyList::EMPTY,
+            Hyphens::Allow,
+            DnsLength::Ignore,
+        )
+        .map_err(|_| String::from("non-ASCII labels must be valid internationalised labels"))?;
+    Ok(ascii
+        .split('.')
+        .map(|label| label.as_bytes().to_vec())
+        .collect_vec())
 }
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use crate::domain_name::DomainName;
+use crate::error::{map_encode_err, Result};
+use crate::rdata::RData;
+use crate::resource_record::DNSRecord;
+use crate::rr_types::{DNSClass, RRType};
+
+// Edns represents the EDNS(0) information of a message (RFC 6891), which is carried
+// in the additional section as an OPT pseudo-record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edns {
+    // The largest UDP payload that the sender can reassemble.
+    udp_payload_size: u16,
+    // The upper 8 bits of the extended 12-bit RCODE.
+    extended_rcode: u8,
+    version: u8,
+    // Signifies that the sender is able to handle DNSSEC records (RFC 3225).
+    dnssec_ok: bool,
+    options: Vec<EdnsOption>,
+}
+
+// EdnsOption is an option carried in the OPT record as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+impl Edns {
+    pub fn new(udp_payload_size: u16) -> Edns {
+        Edns {
+            udp_payload_size,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: vec![],
+        }
+    }
+
+    pub fn udp_payload_size(&self) -> u16 {
+        self.udp_payload_size
+    }
+
+    pub fn extended_rcode(&self) -> u8 {
+        self.extended_rcode
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn dnssec_ok(&self) -> bool {
+        self.dnssec_ok
+    }
+
+    pub fn set_dnssec_ok(&mut self, dnssec_ok: bool) {
+        self.dnssec_ok = dnssec_ok;
+    }
+
+    pub fn options(&self) -> &Vec<EdnsOption> {
+        &self.options
+    }
+
+    pub fn add_option(&mut self, option: EdnsOption) {
+        self.options.push(option);
+    }
+
+    // Converts the provided OPT record into our representation. Returns None if the
+    // record isn't an OPT record or its options are malformed.
+    pub fn from_record(record: &DNSRecord) -> Option<Edns> {
+        if record.r_type() != &RRType::OPT {
+            return None;
+        }
+        // the TTL of an OPT record is made up of the extended RCODE, the version
+        // and the flags, of which only the DO bit is defined.
+        let ttl = u32::try_from(record.ttl().as_secs()).ok()?;
+        let [extended_rcode, version, flags, _] = ttl.to_be_bytes();
+
+        let mut options = vec![];
+        if let Some(RData::Unknown(raw)) = record.rdata() {
+            let mut rest = raw.as_slice();
+            while !rest.is_empty() {
+                if rest.len() < 4 {
+                    return None;
+                }
+                let code = u16::from_be_bytes([rest[0], rest[1]]);
+                let len = usize::from(u16::from_be_bytes([rest[2], rest[3]]));
+                let data = rest.get(4..4 + len)?.to_vec();
+                options.push(EdnsOption { code, data });
+                rest = &rest[4 + len..];
+            }
+        }
+
+        Some(Edns {
+            udp_payload_size: record.class().code(),
+            extended_rcode,
+            version,
+            dnssec_ok: flags & 0b1000_0000 != 0,
+            options,
+        })
+    }
+
+    // Converts this into an OPT record that can be added to the additional section.
+    pub fn to_record(&self) -> Result<DNSRecord> {
+        let flags: u8 = if self.dnssec_ok { 0b1000_0000 } else { 0 };
+        let ttl = u32::from_be_bytes([self.extended_rcode, self.version, flags, 0]);
+
+        let mut raw = vec![];
+        for option in &self.options {
+            let len = u16::try_from(option.data.len()).map_err(|e| map_encode_err("edns", &e))?;
+            raw.extend(option.code.to_be_bytes());
+            raw.extend(len.to_be_bytes());
+            raw.extend(&option.data);
+        }
+
+        DNSRecord::new(
+            DomainName::root(),
+            RRType::OPT,
+            DNSClass::from(self.udp_payload_size),
+            Duration::from_secs(u64::from(ttl)),
+            RData::Unknown(raw),
+        )
+    }
+}
@@ -12,12 +12,26 @@ pub enum DNSResolverError {
     Parse,
     #[error("invalid record type: `{0}`")]
     InvalidRecordType(String),
+    #[error("invalid class: `{0}`")]
+    InvalidClass(String),
+    #[error("invalid domain name `{0}`: `{1}`")]
+    InvalidName(String, String),
     #[error("failed while trying to connect to `{0}`: `{1}`")]
     ConnectionFailure(String, String),
     #[error("failed while trying to `{0}` data: `{1}`")]
     IOFailure(String, String),
     #[error("could not lookup `{0}` record of `{1}`")]
     LookupFailure(String, String),
+    #[error("invalid zone file `{0}` at line {1}: `{2}`")]
+    ZoneFile(String, usize, String),
+    #[error("invalid zone `{0}`: `{1}`")]
+    InvalidZone(String, String),
+    #[error("transfer of zone `{0}` failed: `{1}`")]
+    TransferFailure(String, String),
+    #[error("response from `{0}` does not match the query: `{1}`")]
+    MismatchedResponse(String, String),
+    #[error("no response from `{0}` in time")]
+    Timeout(String),
 }
 
 pub type Result<T> = std::result::Result<T, DNSResolverError>;
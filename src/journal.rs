@@ -0,0 +1,120 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::decoder::Decoder;
+use crate::domain_name::NameCompressor;
+use crate::error::{map_encode_err, DNSResolverError, Result};
+use crate::resource_record::DNSRecord;
+use crate::transfer::soa_serial;
+use crate::update::Change;
+use crate::zone::Zone;
+
+// Journal is a file that records the changes made to a zone by dynamic updates, so that
+// they survive a restart of the server that loads the zone from its master file. The
+// changes are stored one after the other as the records of IXFR in their wire format,
+// every one of them preceded by its length as two octets.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Journal {
+        Journal { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Returns the zone with the changes in the journal applied, which have to start at
+    // the serial of the zone. The zone is returned as it is if there's no journal yet.
+    pub fn replay(&self, zone: Zone) -> Result<Zone> {
+        let records = self.read()?;
+        let Some(first) = records.first() else {
+            return Ok(zone);
+        };
+        if soa_serial(first) != Some(zone.serial()) {
+            return Err(DNSResolverError::InvalidZone(
+                zone.origin().to_string(),
+                format!(
+                    "the journal {} doesn't start at serial {}",
+                    self.path.display(),
+                    zone.serial()
+                ),
+            ));
+        }
+        zone.apply(&records)
+    }
+
+    // Returns the changes in the journal since the version of the zone with the provided
+    // serial, in the order of IXFR, or None if no change starts at that version.
+    pub fn changes_since(&self, serial: u32) -> Result<Option<Vec<DNSRecord>>> {
+        let mut records = self.read()?;
+        // every change has two SOA records, the first one of which is the old version.
+        let start = records
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| soa_serial(record).is_some())
+            .step_by(2)
+            .find(|(_, record)| soa_serial(record) == Some(serial))
+            .map(|(i, _)| i);
+        Ok(start.map(|start| records.split_off(start)))
+    }
+
+    // Reads all the records in the journal, of which there are none if there's no
+    // journal yet.
+    fn read(&self) -> Result<Vec<DNSRecord>> {
+        let contents = match fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => {
+                return Err(DNSResolverError::IOFailure(
+                    String::from("read"),
+                    format!("{}: {}", self.path.display(), e),
+                ))
+            }
+        };
+        let mut decoder = Decoder::new(&contents);
+        let mut records = vec![];
+        while !decoder.is_empty() {
+            let len = decoder.read_u16("journal")?;
+            let record = decoder.read_bytes(usize::from(len), "journal")?;
+            records.push(DNSRecord::decode(&mut Decoder::new(record))?);
+        }
+        Ok(records)
+    }
+
+    // Appends the change to the journal. A change that fails to be written in full is
+    // cut off again, since a partial change would leave the journal unreadable.
+    pub fn append(&self, change: &Change) -> Result<()> {
+        // every record is encoded on its own, since the names in a record are compressed
+        // against the record.
+        let mut encoded = vec![];
+        for record in change.records() {
+            let mut framed = vec![];
+            record.encode(&mut framed, &mut NameCompressor::new())?;
+            let len = u16::try_from(framed.len()).map_err(|e| map_encode_err("journal", &e))?;
+            encoded.extend(len.to_be_bytes());
+            encoded.extend(framed);
+        }
+        let failed = |e: std::io::Error| {
+            DNSResolverError::IOFailure(
+                String::from("write"),
+                format!("{}: {}", self.path.display(), e),
+            )
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(failed)?;
+        let len = file.metadata().map_err(failed)?.len();
+        if let Err(e) = file.write_all(&encoded).and_then(|_| file.sync_data()) {
+            let _ = file.set_len(len);
+            return Err(failed(e));
+        }
+        Ok(())
+    }
+}
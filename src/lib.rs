@@ -1,9 +1,20 @@
 pub mod cache;
+pub mod decoder;
+pub mod dnssec;
 pub mod domain_name;
+pub mod edns;
 pub mod error;
+pub mod journal;
 pub mod message;
 pub mod query;
+pub mod rdata;
 pub mod resolver;
 pub mod resource_record;
 pub mod rr_types;
+pub mod secondary;
+pub mod tcp;
+pub mod transfer;
+pub mod update;
 pub mod utils;
+pub mod zone;
+pub mod zone_file;
@@ -1,6 +1,9 @@
-use crate::error::Result;
+use crate::decoder::Decoder;
+use crate::domain_name::{DomainName, NameCompressor};
+use crate::edns::Edns;
+use crate::error::{map_encode_err, Result};
 use crate::{
-    query::{DNSHeader, DNSQuestion},
+    query::{DNSHeader, DNSQuestion, Opcode, ResponseCode, QR},
     resource_record::DNSRecord,
     rr_types::RRType,
 };
@@ -32,29 +35,44 @@ impl DNSMessage {
         }
     }
 
+    // Returns a builder for a response to the provided query, which carries the ID,
+    // the questions, the opcode and the RD flag of the query.
+    pub fn response_to(query: &DNSMessage) -> MessageBuilder {
+        let mut builder = MessageBuilder::response()
+            .id(query.header.id())
+            .recursion_desired(query.header.recursion_desired());
+        if let Some(opcode) = query.header.opcode() {
+            builder = builder.opcode(opcode);
+        }
+        for question in &query.questions {
+            builder = builder.question(question.clone());
+        }
+        builder
+    }
+
     // Decode the message from its wire format into our representation.
-    pub fn decode(message: &Vec<u8>) -> Result<DNSMessage> {
-        let mut message_iter = message.iter();
+    pub fn decode(message: &[u8]) -> Result<DNSMessage> {
+        let mut decoder = Decoder::new(message);
         let mut questions = vec![];
         let mut answers = vec![];
         let mut authorities = vec![];
         let mut additionals = vec![];
 
-        let header = DNSHeader::decode(&mut message_iter)?;
+        let header = DNSHeader::decode(&mut decoder)?;
         for _ in 0..header.num_questions() {
-            let question = DNSQuestion::decode(&mut message_iter)?;
+            let question = DNSQuestion::decode(&mut decoder)?;
             questions.push(question);
         }
         for _ in 0..header.num_answers() {
-            let answer = DNSRecord::decode(&mut message_iter, &mut message.iter())?;
+            let answer = DNSRecord::decode(&mut decoder)?;
             answers.push(answer);
         }
         for _ in 0..header.num_authorities() {
-            let rr = DNSRecord::decode(&mut message_iter, &mut message.iter())?;
+            let rr = DNSRecord::decode(&mut decoder)?;
             authorities.push(rr);
         }
         for _ in 0..header.num_additionals() {
-            let rr = DNSRecord::decode(&mut message_iter, &mut message.iter())?;
+            let rr = DNSRecord::decode(&mut decoder)?;
             additionals.push(rr);
         }
 
@@ -67,21 +85,32 @@ impl DNSMessage {
         })
     }
 
-    // Encode the message into the wire format.
+    // Encode the message into the wire format. The counts in the header are derived from
+    // the sections, since they may have been changed after the header was created.
     pub fn encode(&self) -> Result<Vec<u8>> {
         let mut encoded = vec![];
-        self.header.encode(&mut encoded);
+        let mut compressor = NameCompressor::new();
+        let count = |len: usize| u16::try_from(len).map_err(|e| map_encode_err("header", &e));
+        let header = DNSHeader::new(
+            self.header.id(),
+            self.header.flags(),
+            count(self.questions.len())?,
+            count(self.answers.len())?,
+            count(self.authorities.len())?,
+            count(self.additionals.len())?,
+        );
+        header.encode(&mut encoded);
         for question in &self.questions {
-            question.encode(&mut encoded)?;
+            question.encode(&mut encoded, &mut compressor)?;
         }
         for answer in &self.answers {
-            answer.encode(&mut encoded)?;
+            answer.encode(&mut encoded, &mut compressor)?;
         }
         for authority in &self.authorities {
-            authority.encode(&mut encoded)?;
+            authority.encode(&mut encoded, &mut compressor)?;
         }
         for additional in &self.additionals {
-            additional.encode(&mut encoded)?;
+            additional.encode(&mut encoded, &mut compressor)?;
         }
         Ok(encoded)
     }
@@ -90,41 +119,301 @@ impl DNSMessage {
         self.header.set_id(id);
     }
 
+    pub fn set_authentic_data(&mut self, ad: bool) {
+        self.header.set_authentic_data(ad);
+    }
+
+    pub fn header(&self) -> &DNSHeader {
+        &self.header
+    }
+
+    pub fn header_mut(&mut self) -> &mut DNSHeader {
+        &mut self.header
+    }
+
+    // Returns the entire questions section.
+    pub fn questions(&self) -> &Vec<DNSQuestion> {
+        &self.questions
+    }
+
+    pub fn questions_mut(&mut self) -> &mut Vec<DNSQuestion> {
+        &mut self.questions
+    }
+
     // Returns the entire answers section.
-    pub fn answers(&mut self) -> &Vec<DNSRecord> {
+    pub fn answers(&self) -> &Vec<DNSRecord> {
         &self.answers
     }
 
+    pub fn answers_mut(&mut self) -> &mut Vec<DNSRecord> {
+        &mut self.answers
+    }
+
+    // Returns the entire authorities section.
+    pub fn authorities(&self) -> &Vec<DNSRecord> {
+        &self.authorities
+    }
+
+    pub fn authorities_mut(&mut self) -> &mut Vec<DNSRecord> {
+        &mut self.authorities
+    }
+
+    // Returns the entire additionals section.
+    pub fn additionals(&self) -> &Vec<DNSRecord> {
+        &self.additionals
+    }
+
+    pub fn additionals_mut(&mut self) -> &mut Vec<DNSRecord> {
+        &mut self.additionals
+    }
+
+    // Returns the records of all the sections in the order that they appear in.
+    pub fn records(&self) -> impl Iterator<Item = &DNSRecord> {
+        self.answers
+            .iter()
+            .chain(&self.authorities)
+            .chain(&self.additionals)
+    }
+
+    // Returns the records of the provided type from all the sections.
+    pub fn records_of_type<'a>(
+        &'a self,
+        r_type: &'a RRType,
+    ) -> impl Iterator<Item = &'a DNSRecord> {
+        self.records()
+            .filter(move |record| record.r_type() == r_type)
+    }
+
+    // Returns the records with the provided owner name from all the sections.
+    pub fn records_named<'a>(
+        &'a self,
+        name: &'a DomainName,
+    ) -> impl Iterator<Item = &'a DNSRecord> {
+        self.records().filter(move |record| record.name() == name)
+    }
+
+    // Returns the records of the provided type from the answers section.
+    pub fn answers_of_type<'a>(
+        &'a self,
+        r_type: &'a RRType,
+    ) -> impl Iterator<Item = &'a DNSRecord> {
+        of_type(&self.answers, r_type)
+    }
+
+    // Returns the records of the provided type from the authorities section.
+    pub fn authorities_of_type<'a>(
+        &'a self,
+        r_type: &'a RRType,
+    ) -> impl Iterator<Item = &'a DNSRecord> {
+        of_type(&self.authorities, r_type)
+    }
+
+    // Returns the records of the provided type from the additionals section.
+    pub fn additionals_of_type<'a>(
+        &'a self,
+        r_type: &'a RRType,
+    ) -> impl Iterator<Item = &'a DNSRecord> {
+        of_type(&self.additionals, r_type)
+    }
+
+    // Splits the message into its header, questions, answers, authorities and additionals.
+    pub fn into_parts(
+        self,
+    ) -> (
+        DNSHeader,
+        Vec<DNSQuestion>,
+        Vec<DNSRecord>,
+        Vec<DNSRecord>,
+        Vec<DNSRecord>,
+    ) {
+        (
+            self.header,
+            self.questions,
+            self.answers,
+            self.authorities,
+            self.additionals,
+        )
+    }
+
+    // Returns the EDNS information carried in the additionals section, if any.
+    pub fn edns(&self) -> Option<Edns> {
+        self.additionals.iter().find_map(Edns::from_record)
+    }
+
     // Returns the data of a particular record type from the answers section.
     pub fn answers_data(&self, record_type: &RRType) -> Vec<String> {
-        let mut answers = vec![];
-        for ans in &self.answers {
-            if ans.r_type() == record_type {
-                if let Some(data) = ans.parsed_data() {
-                    answers.push(data.to_owned());
-                }
-            }
-        }
-        answers
+        self.answers_of_type(record_type)
+            .filter_map(|ans| ans.parsed_data().to_owned())
+            .collect()
     }
 
     // Returns the data of the first A record in the additionals section.
     pub fn ns_ip(&self) -> &Option<String> {
-        for additional in &self.additionals {
-            if additional.r_type() == &RRType::A {
-                return additional.parsed_data();
-            }
+        match self.additionals_of_type(&RRType::A).next() {
+            Some(additional) => additional.parsed_data(),
+            None => &None,
         }
-        &None
     }
 
     // Returns the data of the first NS record in the authorities section.
     pub fn nameserver(&self) -> &Option<String> {
-        for ns in &self.authorities {
-            if ns.r_type() == &RRType::NS {
-                return ns.parsed_data();
-            }
+        match self.authorities_of_type(&RRType::NS).next() {
+            Some(ns) => ns.parsed_data(),
+            None => &None,
         }
-        &None
+    }
+}
+
+// Returns the records of the provided type from the section.
+fn of_type<'a>(
+    records: &'a [DNSRecord],
+    r_type: &'a RRType,
+) -> impl Iterator<Item = &'a DNSRecord> {
+    records
+        .iter()
+        .filter(move |record| record.r_type() == r_type)
+}
+
+// MessageBuilder builds a DNS message section by section, deriving the counts in the
+// header from the contents of the sections.
+#[derive(Debug)]
+pub struct MessageBuilder {
+    header: DNSHeader,
+    questions: Vec<DNSQuestion>,
+    answers: Vec<DNSRecord>,
+    authorities: Vec<DNSRecord>,
+    additionals: Vec<DNSRecord>,
+    edns: Option<Edns>,
+}
+
+impl MessageBuilder {
+    // Returns a builder for a query.
+    pub fn query() -> MessageBuilder {
+        MessageBuilder {
+            header: DNSHeader::new(0, 0, 0, 0, 0, 0),
+            questions: vec![],
+            answers: vec![],
+            authorities: vec![],
+            additionals: vec![],
+            edns: None,
+        }
+    }
+
+    // Returns a builder for a response.
+    pub fn response() -> MessageBuilder {
+        let mut builder = MessageBuilder::query();
+        builder.header.set_qr(QR::Response);
+        builder
+    }
+
+    pub fn id(mut self, id: u16) -> MessageBuilder {
+        self.header.set_id(id);
+        self
+    }
+
+    pub fn opcode(mut self, opcode: Opcode) -> MessageBuilder {
+        self.header.set_opcode(opcode);
+        self
+    }
+
+    pub fn response_code(mut self, rcode: ResponseCode) -> MessageBuilder {
+        self.header.set_response_code(rcode);
+        self
+    }
+
+    pub fn authoritative(mut self, aa: bool) -> MessageBuilder {
+        self.header.set_authoritative(aa);
+        self
+    }
+
+    pub fn truncated(mut self, tc: bool) -> MessageBuilder {
+        self.header.set_truncated(tc);
+        self
+    }
+
+    pub fn recursion_desired(mut self, rd: bool) -> MessageBuilder {
+        self.header.set_recursion_desired(rd);
+        self
+    }
+
+    pub fn recursion_available(mut self, ra: bool) -> MessageBuilder {
+        self.header.set_recursion_available(ra);
+        self
+    }
+
+    pub fn authentic_data(mut self, ad: bool) -> MessageBuilder {
+        self.header.set_authentic_data(ad);
+        self
+    }
+
+    pub fn checking_disabled(mut self, cd: bool) -> MessageBuilder {
+        self.header.set_checking_disabled(cd);
+        self
+    }
+
+    pub fn question(mut self, question: DNSQuestion) -> MessageBuilder {
+        self.questions.push(question);
+        self
+    }
+
+    pub fn answer(mut self, record: DNSRecord) -> MessageBuilder {
+        self.answers.push(record);
+        self
+    }
+
+    pub fn answers(mut self, records: impl IntoIterator<Item = DNSRecord>) -> MessageBuilder {
+        self.answers.extend(records);
+        self
+    }
+
+    pub fn authority(mut self, record: DNSRecord) -> MessageBuilder {
+        self.authorities.push(record);
+        self
+    }
+
+    pub fn authorities(mut self, records: impl IntoIterator<Item = DNSRecord>) -> MessageBuilder {
+        self.authorities.extend(records);
+        self
+    }
+
+    pub fn additional(mut self, record: DNSRecord) -> MessageBuilder {
+        self.additionals.push(record);
+        self
+    }
+
+    pub fn additionals(mut self, records: impl IntoIterator<Item = DNSRecord>) -> MessageBuilder {
+        self.additionals.extend(records);
+        self
+    }
+
+    // Sets the EDNS information of the message, which is added to the end of the
+    // additionals section as an OPT record.
+    pub fn edns(mut self, edns: Edns) -> MessageBuilder {
+        self.edns = Some(edns);
+        self
+    }
+
+    // Builds the message. Fails if a section has more records than the header can count.
+    pub fn build(self) -> Result<DNSMessage> {
+        let mut additionals = self.additionals;
+        if let Some(edns) = &self.edns {
+            additionals.push(edns.to_record()?);
+        }
+        let count = |len: usize| u16::try_from(len).map_err(|e| map_encode_err("header", &e));
+        let header = DNSHeader::new(
+            self.header.id(),
+            self.header.flags(),
+            count(self.questions.len())?,
+            count(self.answers.len())?,
+            count(self.authorities.len())?,
+            count(additionals.len())?,
+        );
+        Ok(DNSMessage::new(
+            header,
+            self.questions,
+            self.answers,
+            self.authorities,
+            additionals,
+        ))
     }
 }
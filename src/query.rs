@@ -1,10 +1,15 @@
-use crate::domain_name::{DomainName, LabelSequenceParser};
-use crate::error::{map_decode_err, map_encode_err, DNSResolverError, Result};
-use crate::rr_types::RRType;
-use crate::utils::{self, get_bit, set_bit};
+use std::fmt::Display;
+
+use crate::decoder::Decoder;
+use crate::domain_name::{DomainName, NameCompressor};
+use crate::edns::Edns;
+use crate::error::{map_encode_err, Result};
+use crate::message::MessageBuilder;
+use crate::rr_types::{DNSClass, RRType};
+use crate::utils::{get_bit, set_bit};
 
 // DNSHeader represents a DNS header.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DNSHeader {
     id: u16,
     flags: u16,
@@ -15,11 +20,11 @@ pub struct DNSHeader {
 }
 
 // DNSQuestion represents a DNS question.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DNSQuestion {
     name: DomainName,
     q_type: RRType,
-    class: u16,
+    class: DNSClass,
 }
 
 pub enum QR {
@@ -27,8 +32,97 @@ pub enum QR {
     Response,
 }
 
+// Opcode is the kind of query that a message carries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Opcode {
+    Query = 0,
+    IQuery,
+    Status,
+    Notify = 4,
+    Update,
+}
+
+// ResponseCode is the RCODE of a response, i.e. the outcome of a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseCode {
+    NoError,
+    FormErr,
+    ServFail,
+    NXDomain,
+    NotImp,
+    Refused,
+    // The RCODEs of dynamic updates (RFC 2136 section 2.2).
+    YXDomain,
+    YXRRSet,
+    NXRRSet,
+    NotAuth,
+    NotZone,
+    // Any other RCODE, which is carried as it is.
+    Unknown(u8),
+}
+
+impl ResponseCode {
+    // Returns the value of the RCODE.
+    pub fn code(&self) -> u8 {
+        match self {
+            ResponseCode::NoError => 0,
+            ResponseCode::FormErr => 1,
+            ResponseCode::ServFail => 2,
+            ResponseCode::NXDomain => 3,
+            ResponseCode::NotImp => 4,
+            ResponseCode::Refused => 5,
+            ResponseCode::YXDomain => 6,
+            ResponseCode::YXRRSet => 7,
+            ResponseCode::NXRRSet => 8,
+            ResponseCode::NotAuth => 9,
+            ResponseCode::NotZone => 10,
+            ResponseCode::Unknown(code) => *code,
+        }
+    }
+}
+
+impl From<u8> for ResponseCode {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => ResponseCode::NoError,
+            1 => ResponseCode::FormErr,
+            2 => ResponseCode::ServFail,
+            3 => ResponseCode::NXDomain,
+            4 => ResponseCode::NotImp,
+            5 => ResponseCode::Refused,
+            6 => ResponseCode::YXDomain,
+            7 => ResponseCode::YXRRSet,
+            8 => ResponseCode::NXRRSet,
+            9 => ResponseCode::NotAuth,
+            10 => ResponseCode::NotZone,
+            _ => ResponseCode::Unknown(code),
+        }
+    }
+}
+
+impl Display for ResponseCode {
+    // Renders the RCODE by its mnemonic, e.g. NXDOMAIN, or as RCODE<value> if it has none.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mnemonic = match self {
+            ResponseCode::NoError => "NOERROR",
+            ResponseCode::FormErr => "FORMERR",
+            ResponseCode::ServFail => "SERVFAIL",
+            ResponseCode::NXDomain => "NXDOMAIN",
+            ResponseCode::NotImp => "NOTIMP",
+            ResponseCode::Refused => "REFUSED",
+            ResponseCode::YXDomain => "YXDOMAIN",
+            ResponseCode::YXRRSet => "YXRRSET",
+            ResponseCode::NXRRSet => "NXRRSET",
+            ResponseCode::NotAuth => "NOTAUTH",
+            ResponseCode::NotZone => "NOTZONE",
+            ResponseCode::Unknown(code) => return write!(f, "RCODE{}", code),
+        };
+        f.write_str(mnemonic)
+    }
+}
+
 impl DNSQuestion {
-    pub fn new(name: DomainName, q_type: RRType, class: u16) -> DNSQuestion {
+    pub fn new(name: DomainName, q_type: RRType, class: DNSClass) -> DNSQuestion {
         DNSQuestion {
             name,
             q_type,
@@ -44,38 +138,32 @@ impl DNSQuestion {
         &self.q_type
     }
 
-    // Encode the question into the provided vector in its wire format.
-    pub fn encode(&self, encoded: &mut Vec<u8>) -> Result<()> {
+    pub fn class(&self) -> &DNSClass {
+        &self.class
+    }
+
+    // Encode the question into the provided vector in its wire format, compressing the
+    // name if possible.
+    pub fn encode(&self, encoded: &mut Vec<u8>, compressor: &mut NameCompressor) -> Result<()> {
         self.name
-            .encode(encoded)
+            .encode_with_compression(encoded, compressor)
             .map_err(|e| map_encode_err("question", &e))?;
 
-        let rr_type = self.q_type.clone() as u16;
+        let rr_type = self.q_type.code();
         encoded.extend(rr_type.to_be_bytes());
-        encoded.extend(self.class.to_be_bytes());
+        encoded.extend(self.class.code().to_be_bytes());
         Ok(())
     }
 
     // Decode the question from its wire format into our representation.
-    pub fn decode<'a, T>(iter: &mut T) -> Result<DNSQuestion>
-    where
-        T: Iterator<Item = &'a u8> + Clone,
-    {
-        let mut label_parser = LabelSequenceParser::new();
-        let name = label_parser.construct_domain_name(iter, None)?;
-
-        let parts = utils::u8_bytes_to_u16_vec(iter, 2)?;
-        if parts.len() < 2 {
-            return Err(DNSResolverError::Decode(
-                String::from("question"),
-                String::from("failed to convert bytes"),
-            ));
-        }
-        let q_type: RRType = parts[0].try_into()?;
+    pub fn decode(decoder: &mut Decoder) -> Result<DNSQuestion> {
+        let name = decoder.read_name("question")?;
+        let q_type = RRType::from(decoder.read_u16("question")?);
+        let class = DNSClass::from(decoder.read_u16("question")?);
         Ok(DNSQuestion {
             name,
             q_type,
-            class: parts[1],
+            class,
         })
     }
 }
@@ -105,6 +193,23 @@ impl DNSHeader {
         self.flags = set_bit(self.flags, qr as u8, 0);
     }
 
+    // Set the OPCODE (1st to 4th bits) of the header's flags.
+    pub fn set_opcode(&mut self, opcode: Opcode) {
+        self.flags = (self.flags & !(0b1111 << 11)) | ((opcode as u16) << 11);
+    }
+
+    // Set the AA (5th) bit of the header's flags.
+    // Signifies that the response is from a nameserver that's authoritative for the name.
+    pub fn set_authoritative(&mut self, aa: bool) {
+        self.flags = set_bit(self.flags, aa as u8, 5);
+    }
+
+    // Set the TC (6th) bit of the header's flags.
+    // Signifies that the message was truncated to fit the transport.
+    pub fn set_truncated(&mut self, tc: bool) {
+        self.flags = set_bit(self.flags, tc as u8, 6);
+    }
+
     // Set the RD (7th) bit of the header's flags.
     // Signifies whether recursion is desired.
     pub fn set_recursion_desired(&mut self, rd: bool) {
@@ -117,6 +222,24 @@ impl DNSHeader {
         self.flags = set_bit(self.flags, ra as u8, 8);
     }
 
+    // Set the RCODE (last 4 bits) of the header's flags. Only the lower 4 bits of the
+    // RCODE fit; the upper ones are carried by EDNS.
+    pub fn set_response_code(&mut self, rcode: ResponseCode) {
+        self.flags = (self.flags & !0b1111) | u16::from(rcode.code() & 0b1111);
+    }
+
+    // Set the AD (10th) bit of the header's flags.
+    // Signifies that all the data in the response has been validated using DNSSEC.
+    pub fn set_authentic_data(&mut self, ad: bool) {
+        self.flags = set_bit(self.flags, ad as u8, 10);
+    }
+
+    // Set the CD (11th) bit of the header's flags.
+    // Signifies that the resolver shouldn't validate the response using DNSSEC.
+    pub fn set_checking_disabled(&mut self, cd: bool) {
+        self.flags = set_bit(self.flags, cd as u8, 11);
+    }
+
     // Set the header ID.
     pub fn set_id(&mut self, id: u16) {
         self.id = id
@@ -126,28 +249,68 @@ impl DNSHeader {
         self.id
     }
 
-    pub fn recursion_desired(&self) -> bool {
-        let rd = get_bit(self.flags, 7);
-        if rd == 1 {
-            true
-        } else {
-            false
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    pub fn is_response(&self) -> bool {
+        get_bit(self.flags, 0) == 1
+    }
+
+    // Returns the OPCODE of the header's flags; None if the value isn't assigned.
+    pub fn opcode(&self) -> Option<Opcode> {
+        match (self.flags >> 11) & 0b1111 {
+            0 => Some(Opcode::Query),
+            1 => Some(Opcode::IQuery),
+            2 => Some(Opcode::Status),
+            4 => Some(Opcode::Notify),
+            5 => Some(Opcode::Update),
+            _ => None,
         }
     }
 
+    pub fn authoritative(&self) -> bool {
+        get_bit(self.flags, 5) == 1
+    }
+
+    pub fn truncated(&self) -> bool {
+        get_bit(self.flags, 6) == 1
+    }
+
+    pub fn recursion_desired(&self) -> bool {
+        get_bit(self.flags, 7) == 1
+    }
+
+    pub fn recursion_available(&self) -> bool {
+        get_bit(self.flags, 8) == 1
+    }
+
+    pub fn authentic_data(&self) -> bool {
+        get_bit(self.flags, 10) == 1
+    }
+
+    pub fn checking_disabled(&self) -> bool {
+        get_bit(self.flags, 11) == 1
+    }
+
+    // Returns the RCODE of the header's flags.
+    pub fn response_code(&self) -> ResponseCode {
+        ResponseCode::from((self.flags & 0b1111) as u8)
+    }
+
     pub fn num_questions(&self) -> u16 {
-        return self.qd_count;
+        self.qd_count
     }
 
     pub fn num_answers(&self) -> u16 {
-        return self.an_count;
+        self.an_count
     }
 
     pub fn num_authorities(&self) -> u16 {
-        return self.ns_count;
+        self.ns_count
     }
     pub fn num_additionals(&self) -> u16 {
-        return self.ar_count;
+        self.ar_count
     }
 
     // Encode the header into the provided vector in its wire format.
@@ -161,37 +324,32 @@ impl DNSHeader {
     }
 
     // Decode the header from its wire format into our representation.
-    pub fn decode<'a, T>(header_bytes: &mut T) -> Result<DNSHeader>
-    where
-        T: Iterator<Item = &'a u8>,
-    {
-        let parts = utils::u8_bytes_to_u16_vec(header_bytes, 6)
-            .map_err(|e| map_decode_err("header", &e))?;
-        if parts.len() < 6 {
-            return Err(DNSResolverError::Decode(
-                String::from("header"),
-                String::from("failed to convert bytes"),
-            ));
-        }
-
+    pub fn decode(decoder: &mut Decoder) -> Result<DNSHeader> {
         Ok(DNSHeader {
-            id: parts[0],
-            flags: parts[1],
-            qd_count: parts[2],
-            an_count: parts[3],
-            ns_count: parts[4],
-            ar_count: parts[5],
+            id: decoder.read_u16("header")?,
+            flags: decoder.read_u16("header")?,
+            qd_count: decoder.read_u16("header")?,
+            an_count: decoder.read_u16("header")?,
+            ns_count: decoder.read_u16("header")?,
+            ar_count: decoder.read_u16("header")?,
         })
     }
 }
 
-// Build a DNS query (in the wire format) using the provided domain
-// name and the record type.
-pub fn build_query(domain_name: String, record_type: RRType) -> Result<Vec<u8>> {
-    let header = DNSHeader::new(45232, 0, 1, 0, 0, 0);
-    let question = DNSQuestion::new(DomainName::new(domain_name), record_type, 1);
-    let mut query: Vec<u8> = vec![];
-    header.encode(&mut query);
-    question.encode(&mut query)?;
-    Ok(query)
+// Build a DNS query (in the wire format) with the provided ID using the provided
+// domain name, record type and class. If EDNS information is provided, then it's
+// added to the additionals section as an OPT record.
+pub fn build_query(
+    id: u16,
+    domain_name: String,
+    record_type: RRType,
+    class: DNSClass,
+    edns: Option<&Edns>,
+) -> Result<Vec<u8>> {
+    let question = DNSQuestion::new(DomainName::new(domain_name), record_type, class);
+    let mut builder = MessageBuilder::query().id(id).question(question);
+    if let Some(edns) = edns {
+        builder = builder.edns(edns.clone());
+    }
+    builder.build()?.encode()
 }
@@ -0,0 +1,1673 @@
+use std::fmt::Display;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use data_encoding::{BASE32HEX_NOPAD, BASE64, HEXUPPER};
+use itertools::Itertools;
+
+use crate::decoder::Decoder;
+use crate::domain_name::{DomainName, NameCompressor};
+use crate::error::{map_encode_err, DNSResolverError, Result};
+use crate::rr_types::RRType;
+
+// RData is our typed representation of the data in a resource record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RData {
+    A(Ipv4Addr),
+    AAAA(Ipv6Addr),
+    NS(DomainName),
+    MD(DomainName),
+    MF(DomainName),
+    CNAME(DomainName),
+    SOA(SOA),
+    MB(DomainName),
+    MG(DomainName),
+    MR(DomainName),
+    PTR(DomainName),
+    HINFO(HINFO),
+    MINFO(MINFO),
+    MX(MX),
+    TXT(Vec<Vec<u8>>),
+    SRV(SRV),
+    NAPTR(NAPTR),
+    DS(DS),
+    SSHFP(SSHFP),
+    RRSIG(RRSIG),
+    NSEC(NSEC),
+    DNSKEY(DNSKEY),
+    NSEC3(NSEC3),
+    NSEC3PARAM(NSEC3PARAM),
+    TLSA(TLSA),
+    SVCB(SVCB),
+    HTTPS(SVCB),
+    URI(URI),
+    CAA(CAA),
+    // The data of a record type that we don't understand, kept as is (RFC 3597).
+    Unknown(Vec<u8>),
+}
+
+// SOA represents the data of a SOA record; the start of a zone of authority.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SOA {
+    // The nameserver that was the original or primary source of data for this zone.
+    mname: DomainName,
+    // The mailbox of the person responsible for this zone.
+    rname: DomainName,
+    serial: u32,
+    refresh: u32,
+    retry: u32,
+    expire: u32,
+    minimum: u32,
+}
+
+impl SOA {
+    pub fn new(
+        mname: DomainName,
+        rname: DomainName,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    ) -> SOA {
+        SOA {
+            mname,
+            rname,
+            serial,
+            refresh,
+            retry,
+            expire,
+            minimum,
+        }
+    }
+
+    pub fn mname(&self) -> &DomainName {
+        &self.mname
+    }
+
+    pub fn rname(&self) -> &DomainName {
+        &self.rname
+    }
+
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    pub fn set_serial(&mut self, serial: u32) {
+        self.serial = serial;
+    }
+
+    pub fn refresh(&self) -> u32 {
+        self.refresh
+    }
+
+    pub fn retry(&self) -> u32 {
+        self.retry
+    }
+
+    pub fn expire(&self) -> u32 {
+        self.expire
+    }
+
+    pub fn minimum(&self) -> u32 {
+        self.minimum
+    }
+}
+
+impl Display for SOA {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} {}",
+            self.mname,
+            self.rname,
+            self.serial,
+            self.refresh,
+            self.retry,
+            self.expire,
+            self.minimum
+        )
+    }
+}
+
+// HINFO represents the data of a HINFO record; the CPU and OS of a host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HINFO {
+    cpu: Vec<u8>,
+    os: Vec<u8>,
+}
+
+impl HINFO {
+    pub fn new(cpu: Vec<u8>, os: Vec<u8>) -> HINFO {
+        HINFO { cpu, os }
+    }
+
+    pub fn cpu(&self) -> &[u8] {
+        &self.cpu
+    }
+
+    pub fn os(&self) -> &[u8] {
+        &self.os
+    }
+}
+
+impl Display for HINFO {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            quote_character_string(&self.cpu),
+            quote_character_string(&self.os)
+        )
+    }
+}
+
+// MINFO represents the data of a MINFO record; the mailboxes responsible for a mailing
+// list or mailbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MINFO {
+    // The mailbox responsible for the mailing list or mailbox.
+    rmailbx: DomainName,
+    // The mailbox that receives errors related to the mailing list or mailbox.
+    emailbx: DomainName,
+}
+
+impl MINFO {
+    pub fn new(rmailbx: DomainName, emailbx: DomainName) -> MINFO {
+        MINFO { rmailbx, emailbx }
+    }
+
+    pub fn rmailbx(&self) -> &DomainName {
+        &self.rmailbx
+    }
+
+    pub fn emailbx(&self) -> &DomainName {
+        &self.emailbx
+    }
+}
+
+impl Display for MINFO {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.rmailbx, self.emailbx)
+    }
+}
+
+// MX represents the data of a MX record; a mail exchanger for the owner name
+// along with its preference. Lower values are preferred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MX {
+    preference: u16,
+    exchange: DomainName,
+}
+
+impl MX {
+    pub fn new(preference: u16, exchange: DomainName) -> MX {
+        MX {
+            preference,
+            exchange,
+        }
+    }
+
+    pub fn preference(&self) -> u16 {
+        self.preference
+    }
+
+    pub fn exchange(&self) -> &DomainName {
+        &self.exchange
+    }
+
+    // Returns true if this is a null MX record (RFC 7505), i.e. the exchange is the
+    // root domain, signifying that the domain does not accept any mail.
+    pub fn is_null(&self) -> bool {
+        self.exchange.is_root()
+    }
+}
+
+impl Display for MX {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.preference, self.exchange)
+    }
+}
+
+// SRV represents the data of a SRV record (RFC 2782); the location of a service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SRV {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: DomainName,
+}
+
+impl SRV {
+    pub fn new(priority: u16, weight: u16, port: u16, target: DomainName) -> SRV {
+        SRV {
+            priority,
+            weight,
+            port,
+            target,
+        }
+    }
+
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn target(&self) -> &DomainName {
+        &self.target
+    }
+
+    // Returns true if the target is the root domain, signifying that the
+    // service is decidedly not available at the domain.
+    pub fn is_unavailable(&self) -> bool {
+        self.target.is_root()
+    }
+}
+
+impl Display for SRV {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.priority, self.weight, self.port, self.target
+        )
+    }
+}
+
+// NAPTR represents the data of a NAPTR record (RFC 3403); a rule that rewrites a
+// string into a new domain name or URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NAPTR {
+    order: u16,
+    preference: u16,
+    flags: Vec<u8>,
+    services: Vec<u8>,
+    regexp: Vec<u8>,
+    replacement: DomainName,
+}
+
+impl NAPTR {
+    pub fn new(
+        order: u16,
+        preference: u16,
+        flags: Vec<u8>,
+        services: Vec<u8>,
+        regexp: Vec<u8>,
+        replacement: DomainName,
+    ) -> NAPTR {
+        NAPTR {
+            order,
+            preference,
+            flags,
+            services,
+            regexp,
+            replacement,
+        }
+    }
+
+    pub fn order(&self) -> u16 {
+        self.order
+    }
+
+    pub fn preference(&self) -> u16 {
+        self.preference
+    }
+
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+
+    pub fn services(&self) -> &[u8] {
+        &self.services
+    }
+
+    pub fn regexp(&self) -> &[u8] {
+        &self.regexp
+    }
+
+    pub fn replacement(&self) -> &DomainName {
+        &self.replacement
+    }
+}
+
+impl Display for NAPTR {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {}",
+            self.order,
+            self.preference,
+            quote_character_string(&self.flags),
+            quote_character_string(&self.services),
+            quote_character_string(&self.regexp),
+            self.replacement
+        )
+    }
+}
+
+// SSHFP represents the data of a SSHFP record (RFC 4255); the fingerprint of a
+// SSH public key of the owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SSHFP {
+    algorithm: u8,
+    fingerprint_type: u8,
+    fingerprint: Vec<u8>,
+}
+
+impl SSHFP {
+    pub fn new(algorithm: u8, fingerprint_type: u8, fingerprint: Vec<u8>) -> SSHFP {
+        SSHFP {
+            algorithm,
+            fingerprint_type,
+            fingerprint,
+        }
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    pub fn fingerprint_type(&self) -> u8 {
+        self.fingerprint_type
+    }
+
+    pub fn fingerprint(&self) -> &[u8] {
+        &self.fingerprint
+    }
+}
+
+impl Display for SSHFP {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.algorithm,
+            self.fingerprint_type,
+            HEXUPPER.encode(&self.fingerprint)
+        )
+    }
+}
+
+// DNSKEY represents the data of a DNSKEY record (RFC 4034); a public key of the zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DNSKEY {
+    flags: u16,
+    protocol: u8,
+    algorithm: u8,
+    public_key: Vec<u8>,
+}
+
+impl DNSKEY {
+    pub fn new(flags: u16, protocol: u8, algorithm: u8, public_key: Vec<u8>) -> DNSKEY {
+        DNSKEY {
+            flags,
+            protocol,
+            algorithm,
+            public_key,
+        }
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    // Returns true if the Zone Key flag is set, i.e. the key can be used to verify
+    // signatures over the zone's records.
+    pub fn is_zone_key(&self) -> bool {
+        self.flags & 0b0000_0001_0000_0000 != 0
+    }
+
+    // Returns true if the Secure Entry Point flag is set, i.e. the key is a key
+    // signing key.
+    pub fn is_secure_entry_point(&self) -> bool {
+        self.flags & 0b0000_0000_0000_0001 != 0
+    }
+
+    // Returns the key tag of the key, which is used to efficiently select the key that
+    // might have created a signature (RFC 4034 appendix B).
+    pub fn key_tag(&self) -> u16 {
+        let mut rdata = vec![];
+        rdata.extend(self.flags.to_be_bytes());
+        rdata.push(self.protocol);
+        rdata.push(self.algorithm);
+        rdata.extend(&self.public_key);
+
+        let mut acc: u32 = 0;
+        for (i, byte) in rdata.iter().enumerate() {
+            if i & 1 == 1 {
+                acc += u32::from(*byte);
+            } else {
+                acc += u32::from(*byte) << 8;
+            }
+        }
+        acc += (acc >> 16) & 0xFFFF;
+        (acc & 0xFFFF) as u16
+    }
+}
+
+impl Display for DNSKEY {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.flags,
+            self.protocol,
+            self.algorithm,
+            BASE64.encode(&self.public_key)
+        )
+    }
+}
+
+// DS represents the data of a DS record (RFC 4034); a digest of a DNSKEY of the child
+// zone, stored in the parent zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DS {
+    key_tag: u16,
+    algorithm: u8,
+    digest_type: u8,
+    digest: Vec<u8>,
+}
+
+impl DS {
+    pub fn new(key_tag: u16, algorithm: u8, digest_type: u8, digest: Vec<u8>) -> DS {
+        DS {
+            key_tag,
+            algorithm,
+            digest_type,
+            digest,
+        }
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    pub fn digest_type(&self) -> u8 {
+        self.digest_type
+    }
+
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+}
+
+impl Display for DS {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.key_tag,
+            self.algorithm,
+            self.digest_type,
+            HEXUPPER.encode(&self.digest)
+        )
+    }
+}
+
+// RRSIG represents the data of a RRSIG record (RFC 4034); a signature over the records
+// of a particular type of the owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RRSIG {
+    type_covered: RRType,
+    algorithm: u8,
+    labels: u8,
+    original_ttl: u32,
+    // The signature is valid until expiration and from inception; both are in seconds
+    // since the UNIX epoch.
+    expiration: u32,
+    inception: u32,
+    key_tag: u16,
+    signer_name: DomainName,
+    signature: Vec<u8>,
+}
+
+impl RRSIG {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        type_covered: RRType,
+        algorithm: u8,
+        labels: u8,
+        original_ttl: u32,
+        expiration: u32,
+        inception: u32,
+        key_tag: u16,
+        signer_name: DomainName,
+        signature: Vec<u8>,
+    ) -> RRSIG {
+        RRSIG {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+            signature,
+        }
+    }
+
+    pub fn type_covered(&self) -> &RRType {
+        &self.type_covered
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    pub fn labels(&self) -> u8 {
+        self.labels
+    }
+
+    pub fn original_ttl(&self) -> u32 {
+        self.original_ttl
+    }
+
+    pub fn expiration(&self) -> u32 {
+        self.expiration
+    }
+
+    pub fn inception(&self) -> u32 {
+        self.inception
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    pub fn signer_name(&self) -> &DomainName {
+        &self.signer_name
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    // Encode all the fields but the signature into the provided vector in their wire format.
+    // In its canonical form, the signer's name is lowercased.
+    pub fn encode_without_signature(&self, encoded: &mut Vec<u8>, canonical: bool) -> Result<()> {
+        encoded.extend(self.type_covered.code().to_be_bytes());
+        encoded.push(self.algorithm);
+        encoded.push(self.labels);
+        encoded.extend(self.original_ttl.to_be_bytes());
+        encoded.extend(self.expiration.to_be_bytes());
+        encoded.extend(self.inception.to_be_bytes());
+        encoded.extend(self.key_tag.to_be_bytes());
+        if canonical {
+            self.signer_name.encode_canonical(encoded)
+        } else {
+            self.signer_name.encode(encoded)
+        }
+    }
+}
+
+impl Display for RRSIG {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {} {}",
+            self.type_covered,
+            self.algorithm,
+            self.labels,
+            self.original_ttl,
+            format_timestamp(self.expiration),
+            format_timestamp(self.inception),
+            self.key_tag,
+            self.signer_name,
+            BASE64.encode(&self.signature)
+        )
+    }
+}
+
+// NSEC represents the data of a NSEC record (RFC 4034); the next owner name in the
+// canonical ordering of the zone and the record types present at the owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NSEC {
+    next_domain_name: DomainName,
+    types: Vec<RRType>,
+}
+
+impl NSEC {
+    pub fn new(next_domain_name: DomainName, types: Vec<RRType>) -> NSEC {
+        NSEC {
+            next_domain_name,
+            types,
+        }
+    }
+
+    pub fn next_domain_name(&self) -> &DomainName {
+        &self.next_domain_name
+    }
+
+    pub fn types(&self) -> &Vec<RRType> {
+        &self.types
+    }
+}
+
+impl Display for NSEC {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.next_domain_name)?;
+        for r_type in &self.types {
+            write!(f, " {}", r_type)?;
+        }
+        Ok(())
+    }
+}
+
+// NSEC3 represents the data of a NSEC3 record (RFC 5155); the next hashed owner name in
+// the zone and the record types present at the owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NSEC3 {
+    hash_algorithm: u8,
+    flags: u8,
+    iterations: u16,
+    salt: Vec<u8>,
+    next_hashed_owner_name: Vec<u8>,
+    types: Vec<RRType>,
+}
+
+impl NSEC3 {
+    pub fn new(
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+        next_hashed_owner_name: Vec<u8>,
+        types: Vec<RRType>,
+    ) -> NSEC3 {
+        NSEC3 {
+            hash_algorithm,
+            flags,
+            iterations,
+            salt,
+            next_hashed_owner_name,
+            types,
+        }
+    }
+
+    pub fn hash_algorithm(&self) -> u8 {
+        self.hash_algorithm
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    // Returns true if the Opt-Out flag is set, i.e. the NSEC3 record may cover
+    // unsigned delegations.
+    pub fn opt_out(&self) -> bool {
+        self.flags & 1 == 1
+    }
+
+    pub fn iterations(&self) -> u16 {
+        self.iterations
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub fn next_hashed_owner_name(&self) -> &[u8] {
+        &self.next_hashed_owner_name
+    }
+
+    pub fn types(&self) -> &Vec<RRType> {
+        &self.types
+    }
+}
+
+impl Display for NSEC3 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.hash_algorithm,
+            self.flags,
+            self.iterations,
+            format_salt(&self.salt),
+            BASE32HEX_NOPAD.encode(&self.next_hashed_owner_name)
+        )?;
+        for r_type in &self.types {
+            write!(f, " {}", r_type)?;
+        }
+        Ok(())
+    }
+}
+
+// NSEC3PARAM represents the data of a NSEC3PARAM record (RFC 5155); the parameters
+// used to calculate the hashed owner names of the zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NSEC3PARAM {
+    hash_algorithm: u8,
+    flags: u8,
+    iterations: u16,
+    salt: Vec<u8>,
+}
+
+impl NSEC3PARAM {
+    pub fn new(hash_algorithm: u8, flags: u8, iterations: u16, salt: Vec<u8>) -> NSEC3PARAM {
+        NSEC3PARAM {
+            hash_algorithm,
+            flags,
+            iterations,
+            salt,
+        }
+    }
+
+    pub fn hash_algorithm(&self) -> u8 {
+        self.hash_algorithm
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn iterations(&self) -> u16 {
+        self.iterations
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+}
+
+impl Display for NSEC3PARAM {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.hash_algorithm,
+            self.flags,
+            self.iterations,
+            format_salt(&self.salt)
+        )
+    }
+}
+
+// TLSA represents the data of a TLSA record (RFC 6698); the certificate association
+// of a TLS server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TLSA {
+    usage: u8,
+    selector: u8,
+    matching_type: u8,
+    certificate_data: Vec<u8>,
+}
+
+impl TLSA {
+    pub fn new(usage: u8, selector: u8, matching_type: u8, certificate_data: Vec<u8>) -> TLSA {
+        TLSA {
+            usage,
+            selector,
+            matching_type,
+            certificate_data,
+        }
+    }
+
+    pub fn usage(&self) -> u8 {
+        self.usage
+    }
+
+    pub fn selector(&self) -> u8 {
+        self.selector
+    }
+
+    pub fn matching_type(&self) -> u8 {
+        self.matching_type
+    }
+
+    pub fn certificate_data(&self) -> &[u8] {
+        &self.certificate_data
+    }
+}
+
+impl Display for TLSA {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.usage,
+            self.selector,
+            self.matching_type,
+            HEXUPPER.encode(&self.certificate_data)
+        )
+    }
+}
+
+// SVCB represents the data of a SVCB or HTTPS record (RFC 9460); the alternative
+// endpoints of a service along with the parameters needed to connect to them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SVCB {
+    priority: u16,
+    target: DomainName,
+    params: Vec<SvcParam>,
+}
+
+// SvcParam is a key=value pair describing a service endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvcParam {
+    // The keys that a client must understand to be able to use the record.
+    Mandatory(Vec<u16>),
+    // The protocol ids supported by the endpoint.
+    Alpn(Vec<Vec<u8>>),
+    // The endpoint does not support the default protocol of the scheme.
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    // The encrypted ClientHello configuration.
+    Ech(Vec<u8>),
+    Ipv6Hint(Vec<Ipv6Addr>),
+    // A key that we don't understand along with its raw value.
+    Unknown(u16, Vec<u8>),
+}
+
+impl SVCB {
+    pub fn new(priority: u16, target: DomainName, params: Vec<SvcParam>) -> SVCB {
+        SVCB {
+            priority,
+            target,
+            params,
+        }
+    }
+
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    pub fn target(&self) -> &DomainName {
+        &self.target
+    }
+
+    pub fn params(&self) -> &Vec<SvcParam> {
+        &self.params
+    }
+
+    // Returns true if this record is in AliasMode, i.e. the target is an alias for
+    // the owner, much like a CNAME.
+    pub fn is_alias(&self) -> bool {
+        self.priority == 0
+    }
+}
+
+impl Display for SVCB {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.priority, self.target)?;
+        for param in &self.params {
+            write!(f, " {}", param)?;
+        }
+        Ok(())
+    }
+}
+
+impl SvcParam {
+    pub fn key(&self) -> u16 {
+        match self {
+            SvcParam::Mandatory(_) => 0,
+            SvcParam::Alpn(_) => 1,
+            SvcParam::NoDefaultAlpn => 2,
+            SvcParam::Port(_) => 3,
+            SvcParam::Ipv4Hint(_) => 4,
+            SvcParam::Ech(_) => 5,
+            SvcParam::Ipv6Hint(_) => 6,
+            SvcParam::Unknown(key, _) => *key,
+        }
+    }
+
+    // Decode the param with the provided key from its wire format value.
+    fn decode(key: u16, value: &[u8]) -> Result<SvcParam> {
+        let err = || rdata_err("SVCB");
+        let param = match key {
+            0 => SvcParam::Mandatory(
+                value
+                    .chunks(2)
+                    .map(|key| {
+                        <[u8; 2]>::try_from(key)
+                            .map(u16::from_be_bytes)
+                            .map_err(|_| err())
+                    })
+                    .collect::<Result<Vec<u16>>>()?,
+            ),
+            1 => {
+                let mut decoder = Decoder::new(value);
+                let mut ids = vec![];
+                while !decoder.is_empty() {
+                    ids.push(decoder.read_character_string("SVCB rdata")?);
+                }
+                SvcParam::Alpn(ids)
+            }
+            2 if value.is_empty() => SvcParam::NoDefaultAlpn,
+            3 => SvcParam::Port(u16::from_be_bytes(value.try_into().map_err(|_| err())?)),
+            4 => SvcParam::Ipv4Hint(
+                value
+                    .chunks(4)
+                    .map(|ip| {
+                        <[u8; 4]>::try_from(ip)
+                            .map(Ipv4Addr::from)
+                            .map_err(|_| err())
+                    })
+                    .collect::<Result<Vec<Ipv4Addr>>>()?,
+            ),
+            5 => SvcParam::Ech(value.to_vec()),
+            6 => SvcParam::Ipv6Hint(
+                value
+                    .chunks(16)
+                    .map(|ip| {
+                        <[u8; 16]>::try_from(ip)
+                            .map(Ipv6Addr::from)
+                            .map_err(|_| err())
+                    })
+                    .collect::<Result<Vec<Ipv6Addr>>>()?,
+            ),
+            2 => return Err(err()),
+            _ => SvcParam::Unknown(key, value.to_vec()),
+        };
+        Ok(param)
+    }
+
+    // Encode the param (key, length and value) into the provided vector in its wire format.
+    fn encode(&self, encoded: &mut Vec<u8>) -> Result<()> {
+        let mut value = vec![];
+        match self {
+            SvcParam::Mandatory(keys) => keys.iter().for_each(|k| value.extend(k.to_be_bytes())),
+            SvcParam::Alpn(ids) => {
+                for id in ids {
+                    encode_character_string(id, &mut value)?;
+                }
+            }
+            SvcParam::NoDefaultAlpn => {}
+            SvcParam::Port(port) => value.extend(port.to_be_bytes()),
+            SvcParam::Ipv4Hint(ips) => ips.iter().for_each(|ip| value.extend(ip.octets())),
+            SvcParam::Ech(config) => value.extend(config),
+            SvcParam::Ipv6Hint(ips) => ips.iter().for_each(|ip| value.extend(ip.octets())),
+            SvcParam::Unknown(_, raw) => value.extend(raw),
+        }
+        let len = u16::try_from(value.len()).map_err(|e| map_encode_err("SVCB rdata", &e))?;
+        encoded.extend(self.key().to_be_bytes());
+        encoded.extend(len.to_be_bytes());
+        encoded.extend(value);
+        Ok(())
+    }
+}
+
+// Returns the presentation format name of a SvcParam key.
+fn svc_param_key_name(key: u16) -> String {
+    match key {
+        0 => String::from("mandatory"),
+        1 => String::from("alpn"),
+        2 => String::from("no-default-alpn"),
+        3 => String::from("port"),
+        4 => String::from("ipv4hint"),
+        5 => String::from("ech"),
+        6 => String::from("ipv6hint"),
+        _ => format!("key{}", key),
+    }
+}
+
+impl Display for SvcParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let key = svc_param_key_name(self.key());
+        match self {
+            SvcParam::Mandatory(keys) => {
+                let keys = keys.iter().map(|k| svc_param_key_name(*k)).join(",");
+                write!(f, "{}={}", key, keys)
+            }
+            SvcParam::Alpn(ids) => {
+                // commas and backslashes within an id need to be escaped, since the
+                // ids themselves are separated by commas.
+                let ids = ids
+                    .iter()
+                    .map(|id| {
+                        String::from_utf8_lossy(id)
+                            .replace('\\', "\\\\")
+                            .replace(',', "\\,")
+                    })
+                    .join(",");
+                write!(f, "{}={}", key, ids)
+            }
+            SvcParam::NoDefaultAlpn => write!(f, "{}", key),
+            SvcParam::Port(port) => write!(f, "{}={}", key, port),
+            SvcParam::Ipv4Hint(ips) => write!(f, "{}={}", key, ips.iter().join(",")),
+            SvcParam::Ech(config) => write!(f, "{}={}", key, BASE64.encode(config)),
+            SvcParam::Ipv6Hint(ips) => write!(f, "{}={}", key, ips.iter().join(",")),
+            SvcParam::Unknown(_, raw) => write!(f, "{}={}", key, quote_character_string(raw)),
+        }
+    }
+}
+
+// URI represents the data of a URI record (RFC 7553); a mapping from the owner
+// name to a URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct URI {
+    priority: u16,
+    weight: u16,
+    target: Vec<u8>,
+}
+
+impl URI {
+    pub fn new(priority: u16, weight: u16, target: Vec<u8>) -> URI {
+        URI {
+            priority,
+            weight,
+            target,
+        }
+    }
+
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    pub fn target(&self) -> &[u8] {
+        &self.target
+    }
+}
+
+impl Display for URI {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.priority,
+            self.weight,
+            quote_character_string(&self.target)
+        )
+    }
+}
+
+// CAA represents the data of a CAA record (RFC 8659); a property that restricts
+// which certification authorities may issue certificates for the owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CAA {
+    flags: u8,
+    tag: Vec<u8>,
+    value: Vec<u8>,
+}
+
+impl CAA {
+    pub fn new(flags: u8, tag: Vec<u8>, value: Vec<u8>) -> CAA {
+        CAA { flags, tag, value }
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    // Returns true if the issuer critical flag is set, i.e. a certification authority
+    // must not issue a certificate if it does not understand this property.
+    pub fn critical(&self) -> bool {
+        self.flags & 0b1000_0000 != 0
+    }
+
+    pub fn tag(&self) -> &[u8] {
+        &self.tag
+    }
+
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+impl Display for CAA {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.flags,
+            String::from_utf8_lossy(&self.tag),
+            quote_character_string(&self.value)
+        )
+    }
+}
+
+impl RData {
+    // Decode the data of a resource record of the provided type. The decoder has to be
+    // restricted to the data of the record, all of which has to be consumed; compressed
+    // domain names are followed within the rest of the message.
+    // The data of record types that we don't understand is decoded opaquely.
+    pub fn decode(r_type: &RRType, decoder: &mut Decoder) -> Result<RData> {
+        let what = format!("{} rdata", r_type);
+        let rdata = match r_type {
+            RRType::A => {
+                let octets: [u8; 4] = decoder.read_rest().try_into().map_err(|_| rdata_err("A"))?;
+                RData::A(Ipv4Addr::from(octets))
+            }
+            RRType::AAAA => {
+                let octets: [u8; 16] = decoder
+                    .read_rest()
+                    .try_into()
+                    .map_err(|_| rdata_err("AAAA"))?;
+                RData::AAAA(Ipv6Addr::from(octets))
+            }
+            RRType::NS => RData::NS(decoder.read_name(&what)?),
+            RRType::MD => RData::MD(decoder.read_name(&what)?),
+            RRType::MF => RData::MF(decoder.read_name(&what)?),
+            RRType::CNAME => RData::CNAME(decoder.read_name(&what)?),
+            RRType::MB => RData::MB(decoder.read_name(&what)?),
+            RRType::MG => RData::MG(decoder.read_name(&what)?),
+            RRType::MR => RData::MR(decoder.read_name(&what)?),
+            RRType::PTR => RData::PTR(decoder.read_name(&what)?),
+            RRType::SOA => {
+                let mname = decoder.read_name(&what)?;
+                let rname = decoder.read_name(&what)?;
+                RData::SOA(SOA::new(
+                    mname,
+                    rname,
+                    decoder.read_u32(&what)?,
+                    decoder.read_u32(&what)?,
+                    decoder.read_u32(&what)?,
+                    decoder.read_u32(&what)?,
+                    decoder.read_u32(&what)?,
+                ))
+            }
+            RRType::MINFO => {
+                let rmailbx = decoder.read_name(&what)?;
+                let emailbx = decoder.read_name(&what)?;
+                RData::MINFO(MINFO::new(rmailbx, emailbx))
+            }
+            RRType::HINFO => {
+                let cpu = decoder.read_character_string(&what)?;
+                let os = decoder.read_character_string(&what)?;
+                RData::HINFO(HINFO::new(cpu, os))
+            }
+            RRType::MX => {
+                let preference = decoder.read_u16(&what)?;
+                let exchange = decoder.read_name(&what)?;
+                RData::MX(MX::new(preference, exchange))
+            }
+            RRType::TXT => RData::TXT(decode_character_strings(decoder, &what)?),
+            RRType::SRV => {
+                let priority = decoder.read_u16(&what)?;
+                let weight = decoder.read_u16(&what)?;
+                let port = decoder.read_u16(&what)?;
+                let target = decoder.read_name(&what)?;
+                RData::SRV(SRV::new(priority, weight, port, target))
+            }
+            RRType::NAPTR => {
+                let order = decoder.read_u16(&what)?;
+                let preference = decoder.read_u16(&what)?;
+                let flags = decoder.read_character_string(&what)?;
+                let services = decoder.read_character_string(&what)?;
+                let regexp = decoder.read_character_string(&what)?;
+                let replacement = decoder.read_name(&what)?;
+                RData::NAPTR(NAPTR::new(
+                    order,
+                    preference,
+                    flags,
+                    services,
+                    regexp,
+                    replacement,
+                ))
+            }
+            RRType::DS => {
+                let key_tag = decoder.read_u16(&what)?;
+                let algorithm = decoder.read_u8(&what)?;
+                let digest_type = decoder.read_u8(&what)?;
+                let digest = decoder.read_rest().to_vec();
+                RData::DS(DS::new(key_tag, algorithm, digest_type, digest))
+            }
+            RRType::SSHFP => {
+                let algorithm = decoder.read_u8(&what)?;
+                let fingerprint_type = decoder.read_u8(&what)?;
+                let fingerprint = decoder.read_rest().to_vec();
+                RData::SSHFP(SSHFP::new(algorithm, fingerprint_type, fingerprint))
+            }
+            RRType::RRSIG => {
+                let type_covered = RRType::from(decoder.read_u16(&what)?);
+                let algorithm = decoder.read_u8(&what)?;
+                let labels = decoder.read_u8(&what)?;
+                let original_ttl = decoder.read_u32(&what)?;
+                let expiration = decoder.read_u32(&what)?;
+                let inception = decoder.read_u32(&what)?;
+                let key_tag = decoder.read_u16(&what)?;
+                let signer_name = decoder.read_name(&what)?;
+                let signature = decoder.read_rest().to_vec();
+                RData::RRSIG(RRSIG::new(
+                    type_covered,
+                    algorithm,
+                    labels,
+                    original_ttl,
+                    expiration,
+                    inception,
+                    key_tag,
+                    signer_name,
+                    signature,
+                ))
+            }
+            RRType::NSEC => {
+                let next_domain_name = decoder.read_name(&what)?;
+                let types = decode_type_bitmaps(decoder, &what)?;
+                RData::NSEC(NSEC::new(next_domain_name, types))
+            }
+            RRType::DNSKEY => {
+                let flags = decoder.read_u16(&what)?;
+                let protocol = decoder.read_u8(&what)?;
+                let algorithm = decoder.read_u8(&what)?;
+                let public_key = decoder.read_rest().to_vec();
+                RData::DNSKEY(DNSKEY::new(flags, protocol, algorithm, public_key))
+            }
+            RRType::NSEC3 => {
+                let hash_algorithm = decoder.read_u8(&what)?;
+                let flags = decoder.read_u8(&what)?;
+                let iterations = decoder.read_u16(&what)?;
+                let salt = decoder.read_character_string(&what)?;
+                let next_hashed_owner_name = decoder.read_character_string(&what)?;
+                let types = decode_type_bitmaps(decoder, &what)?;
+                RData::NSEC3(NSEC3::new(
+                    hash_algorithm,
+                    flags,
+                    iterations,
+                    salt,
+                    next_hashed_owner_name,
+                    types,
+                ))
+            }
+            RRType::NSEC3PARAM => {
+                let hash_algorithm = decoder.read_u8(&what)?;
+                let flags = decoder.read_u8(&what)?;
+                let iterations = decoder.read_u16(&what)?;
+                let salt = decoder.read_character_string(&what)?;
+                RData::NSEC3PARAM(NSEC3PARAM::new(hash_algorithm, flags, iterations, salt))
+            }
+            RRType::TLSA => {
+                let usage = decoder.read_u8(&what)?;
+                let selector = decoder.read_u8(&what)?;
+                let matching_type = decoder.read_u8(&what)?;
+                let certificate_data = decoder.read_rest().to_vec();
+                RData::TLSA(TLSA::new(usage, selector, matching_type, certificate_data))
+            }
+            RRType::SVCB | RRType::HTTPS => {
+                let priority = decoder.read_u16(&what)?;
+                let target = decoder.read_name(&what)?;
+                let mut params: Vec<SvcParam> = vec![];
+                while !decoder.is_empty() {
+                    let key = decoder.read_u16(&what)?;
+                    // the keys have to be in strictly increasing order, which also rules
+                    // out repeated keys (RFC 9460 section 2.2).
+                    if params.last().is_some_and(|last| last.key() >= key) {
+                        return Err(DNSResolverError::Decode(
+                            what,
+                            format!("SvcParam key {} is out of order", key),
+                        ));
+                    }
+                    let len = usize::from(decoder.read_u16(&what)?);
+                    let value = decoder.read_bytes(len, &what)?;
+                    params.push(SvcParam::decode(key, value)?);
+                }
+                let svcb = SVCB::new(priority, target, params);
+                if r_type == &RRType::HTTPS {
+                    RData::HTTPS(svcb)
+                } else {
+                    RData::SVCB(svcb)
+                }
+            }
+            RRType::URI => {
+                let priority = decoder.read_u16(&what)?;
+                let weight = decoder.read_u16(&what)?;
+                let target = decoder.read_rest().to_vec();
+                RData::URI(URI::new(priority, weight, target))
+            }
+            RRType::CAA => {
+                let flags = decoder.read_u8(&what)?;
+                let tag = decoder.read_character_string(&what)?;
+                let value = decoder.read_rest().to_vec();
+                RData::CAA(CAA::new(flags, tag, value))
+            }
+            _ => RData::Unknown(decoder.read_rest().to_vec()),
+        };
+        decoder.finish(&what)?;
+        Ok(rdata)
+    }
+
+    // Encode the data into the provided vector in its wire format. Domain names
+    // are never compressed.
+    pub fn encode(&self, encoded: &mut Vec<u8>) -> Result<()> {
+        self.encode_rdata(encoded, false, None)
+    }
+
+    // Encode the data into the provided vector in its canonical wire format (RFC 4034
+    // section 6.2), i.e. the domain names of the types listed there are lowercased.
+    pub fn encode_canonical(&self, encoded: &mut Vec<u8>) -> Result<()> {
+        self.encode_rdata(encoded, true, None)
+    }
+
+    // Encode the data at the end of the message being built in encoded, compressing the
+    // domain names of the types defined in RFC 1035. The names of other types are never
+    // compressed, since resolvers that don't know those types couldn't decompress them
+    // (RFC 3597 section 4).
+    pub fn encode_with_compression(
+        &self,
+        encoded: &mut Vec<u8>,
+        compressor: &mut NameCompressor,
+    ) -> Result<()> {
+        self.encode_rdata(encoded, false, Some(compressor))
+    }
+
+    fn encode_rdata(
+        &self,
+        encoded: &mut Vec<u8>,
+        canonical: bool,
+        mut compressor: Option<&mut NameCompressor>,
+    ) -> Result<()> {
+        let encode_name = |name: &DomainName, encoded: &mut Vec<u8>| {
+            if canonical {
+                name.encode_canonical(encoded)
+            } else {
+                name.encode(encoded)
+            }
+        };
+        let mut encode_compressible_name =
+            |name: &DomainName, encoded: &mut Vec<u8>| match compressor.as_deref_mut() {
+                Some(compressor) => name.encode_with_compression(encoded, compressor),
+                None => encode_name(name, encoded),
+            };
+        match self {
+            RData::A(ip) => encoded.extend(ip.octets()),
+            RData::AAAA(ip) => encoded.extend(ip.octets()),
+            RData::NS(name)
+            | RData::MD(name)
+            | RData::MF(name)
+            | RData::CNAME(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name)
+            | RData::PTR(name) => encode_compressible_name(name, encoded)?,
+            RData::SOA(soa) => {
+                encode_compressible_name(&soa.mname, encoded)?;
+                encode_compressible_name(&soa.rname, encoded)?;
+                encoded.extend(soa.serial.to_be_bytes());
+                encoded.extend(soa.refresh.to_be_bytes());
+                encoded.extend(soa.retry.to_be_bytes());
+                encoded.extend(soa.expire.to_be_bytes());
+                encoded.extend(soa.minimum.to_be_bytes());
+            }
+            RData::HINFO(hinfo) => {
+                encode_character_string(&hinfo.cpu, encoded)?;
+                encode_character_string(&hinfo.os, encoded)?;
+            }
+            RData::MINFO(minfo) => {
+                encode_compressible_name(&minfo.rmailbx, encoded)?;
+                encode_compressible_name(&minfo.emailbx, encoded)?;
+            }
+            RData::MX(mx) => {
+                encoded.extend(mx.preference.to_be_bytes());
+                encode_compressible_name(&mx.exchange, encoded)?;
+            }
+            RData::TXT(strings) => {
+                for string in strings {
+                    encode_character_string(string, encoded)?;
+                }
+            }
+            RData::SRV(srv) => {
+                encoded.extend(srv.priority.to_be_bytes());
+                encoded.extend(srv.weight.to_be_bytes());
+                encoded.extend(srv.port.to_be_bytes());
+                encode_name(&srv.target, encoded)?;
+            }
+            RData::NAPTR(naptr) => {
+                encoded.extend(naptr.order.to_be_bytes());
+                encoded.extend(naptr.preference.to_be_bytes());
+                encode_character_string(&naptr.flags, encoded)?;
+                encode_character_string(&naptr.services, encoded)?;
+                encode_character_string(&naptr.regexp, encoded)?;
+                encode_name(&naptr.replacement, encoded)?;
+            }
+            RData::DS(ds) => {
+                encoded.extend(ds.key_tag.to_be_bytes());
+                encoded.push(ds.algorithm);
+                encoded.push(ds.digest_type);
+                encoded.extend(&ds.digest);
+            }
+            RData::SSHFP(sshfp) => {
+                encoded.push(sshfp.algorithm);
+                encoded.push(sshfp.fingerprint_type);
+                encoded.extend(&sshfp.fingerprint);
+            }
+            RData::RRSIG(rrsig) => {
+                rrsig.encode_without_signature(encoded, canonical)?;
+                encoded.extend(&rrsig.signature);
+            }
+            RData::NSEC(nsec) => {
+                // the next domain name is never lowercased (RFC 6840 section 5.1).
+                nsec.next_domain_name.encode(encoded)?;
+                encode_type_bitmaps(&nsec.types, encoded);
+            }
+            RData::DNSKEY(dnskey) => {
+                encoded.extend(dnskey.flags.to_be_bytes());
+                encoded.push(dnskey.protocol);
+                encoded.push(dnskey.algorithm);
+                encoded.extend(&dnskey.public_key);
+            }
+            RData::NSEC3(nsec3) => {
+                encoded.push(nsec3.hash_algorithm);
+                encoded.push(nsec3.flags);
+                encoded.extend(nsec3.iterations.to_be_bytes());
+                encode_character_string(&nsec3.salt, encoded)?;
+                encode_character_string(&nsec3.next_hashed_owner_name, encoded)?;
+                encode_type_bitmaps(&nsec3.types, encoded);
+            }
+            RData::NSEC3PARAM(param) => {
+                encoded.push(param.hash_algorithm);
+                encoded.push(param.flags);
+                encoded.extend(param.iterations.to_be_bytes());
+                encode_character_string(&param.salt, encoded)?;
+            }
+            RData::TLSA(tlsa) => {
+                encoded.push(tlsa.usage);
+                encoded.push(tlsa.selector);
+                encoded.push(tlsa.matching_type);
+                encoded.extend(&tlsa.certificate_data);
+            }
+            RData::SVCB(svcb) | RData::HTTPS(svcb) => {
+                encoded.extend(svcb.priority.to_be_bytes());
+                svcb.target.encode(encoded)?;
+                for param in &svcb.params {
+                    param.encode(encoded)?;
+                }
+            }
+            RData::URI(uri) => {
+                encoded.extend(uri.priority.to_be_bytes());
+                encoded.extend(uri.weight.to_be_bytes());
+                encoded.extend(&uri.target);
+            }
+            RData::CAA(caa) => {
+                encoded.push(caa.flags);
+                encode_character_string(&caa.tag, encoded)?;
+                encoded.extend(&caa.value);
+            }
+            RData::Unknown(raw) => encoded.extend(raw),
+        }
+        Ok(())
+    }
+}
+
+impl Display for RData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RData::A(ip) => write!(f, "{}", ip),
+            RData::AAAA(ip) => write!(f, "{}", ip),
+            RData::NS(name)
+            | RData::MD(name)
+            | RData::MF(name)
+            | RData::CNAME(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name)
+            | RData::PTR(name) => write!(f, "{}", name),
+            RData::SOA(soa) => write!(f, "{}", soa),
+            RData::HINFO(hinfo) => write!(f, "{}", hinfo),
+            RData::MINFO(minfo) => write!(f, "{}", minfo),
+            RData::MX(mx) => write!(f, "{}", mx),
+            RData::TXT(strings) => {
+                let strings = strings.iter().map(|s| quote_character_string(s)).join(" ");
+                write!(f, "{}", strings)
+            }
+            RData::SRV(srv) => write!(f, "{}", srv),
+            RData::NAPTR(naptr) => write!(f, "{}", naptr),
+            RData::DS(ds) => write!(f, "{}", ds),
+            RData::SSHFP(sshfp) => write!(f, "{}", sshfp),
+            RData::RRSIG(rrsig) => write!(f, "{}", rrsig),
+            RData::NSEC(nsec) => write!(f, "{}", nsec),
+            RData::DNSKEY(dnskey) => write!(f, "{}", dnskey),
+            RData::NSEC3(nsec3) => write!(f, "{}", nsec3),
+            RData::NSEC3PARAM(param) => write!(f, "{}", param),
+            RData::TLSA(tlsa) => write!(f, "{}", tlsa),
+            RData::SVCB(svcb) | RData::HTTPS(svcb) => write!(f, "{}", svcb),
+            RData::URI(uri) => write!(f, "{}", uri),
+            RData::CAA(caa) => write!(f, "{}", caa),
+            // the generic presentation format, i.e. \# <length> <hex data>
+            RData::Unknown(raw) if raw.is_empty() => write!(f, "\\# 0"),
+            RData::Unknown(raw) => write!(f, "\\# {} {}", raw.len(), HEXUPPER.encode(raw)),
+        }
+    }
+}
+
+fn rdata_err(r_type: &str) -> DNSResolverError {
+    DNSResolverError::Decode(
+        format!("{} rdata", r_type),
+        String::from("could not parse bytes"),
+    )
+}
+
+// Decodes all the remaining data as a sequence of <character-string>s.
+fn decode_character_strings(decoder: &mut Decoder, what: &str) -> Result<Vec<Vec<u8>>> {
+    let mut strings = vec![];
+    while !decoder.is_empty() {
+        strings.push(decoder.read_character_string(what)?);
+    }
+    Ok(strings)
+}
+
+// Decodes all the remaining data as type bitmaps (RFC 4034 section 4.1.2), where each
+// window block is a window number, the length of the bitmap and the bitmap itself.
+// Each set bit in a window's bitmap represents a record type.
+fn decode_type_bitmaps(decoder: &mut Decoder, what: &str) -> Result<Vec<RRType>> {
+    let mut types = vec![];
+    let mut previous = None;
+    while !decoder.is_empty() {
+        let window = u16::from(decoder.read_u8(what)?);
+        // the windows have to be in increasing order and can't be repeated, so that
+        // every set of types has a single encoding.
+        if previous.is_some_and(|previous| previous >= window) {
+            return Err(DNSResolverError::Decode(
+                String::from(what),
+                format!("type bitmap window {} is out of order", window),
+            ));
+        }
+        previous = Some(window);
+        let len = usize::from(decoder.read_u8(what)?);
+        if len == 0 || len > 32 {
+            return Err(rdata_err("type bitmaps"));
+        }
+        for (i, byte) in decoder.read_bytes(len, what)?.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0b1000_0000 >> bit) != 0 {
+                    let code = (window << 8) | (i as u16 * 8 + bit);
+                    types.push(RRType::from(code));
+                }
+            }
+        }
+    }
+    Ok(types)
+}
+
+fn encode_type_bitmaps(types: &[RRType], encoded: &mut Vec<u8>) {
+    let codes = types.iter().map(|r_type| r_type.code()).sorted().dedup();
+    for (window, codes) in &codes.group_by(|code| code >> 8) {
+        let mut bitmap = [0_u8; 32];
+        let mut len = 0;
+        for code in codes {
+            let low = usize::from((code & 0xFF) as u8);
+            bitmap[low / 8] |= 0b1000_0000 >> (low % 8);
+            len = low / 8 + 1;
+        }
+        encoded.push(window as u8);
+        encoded.push(len as u8);
+        encoded.extend(&bitmap[..len]);
+    }
+}
+
+// Renders a salt in its presentation format; hex or "-" if there's no salt.
+fn format_salt(salt: &[u8]) -> String {
+    if salt.is_empty() {
+        String::from("-")
+    } else {
+        HEXUPPER.encode(salt)
+    }
+}
+
+// Renders a timestamp (seconds since the UNIX epoch) in the YYYYMMDDHHmmSS format.
+fn format_timestamp(timestamp: u32) -> String {
+    let secs = i64::from(timestamp);
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // convert the days since the epoch into a civil date; see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+fn encode_character_string(string: &[u8], encoded: &mut Vec<u8>) -> Result<()> {
+    let len = u8::try_from(string.len()).map_err(|e| map_encode_err("character-string", &e))?;
+    encoded.push(len);
+    encoded.extend(string);
+    Ok(())
+}
+
+// Renders a <character-string> in its quoted presentation format, escaping
+// quotes, backslashes and non-printable bytes.
+fn quote_character_string(string: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for byte in string {
+        match byte {
+            b'"' | b'\\' => {
+                quoted.push('\\');
+                quoted.push(char::from(*byte));
+            }
+            0x20..=0x7e => quoted.push(char::from(*byte)),
+            _ => quoted.push_str(&format!("\\{:03}", byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
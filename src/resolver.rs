@@ -1,14 +1,124 @@
+use crate::decoder::Decoder;
+use crate::dnssec::{self, Denial, ValidationStatus};
+use crate::domain_name::DomainName;
+use crate::edns::Edns;
 use crate::error::{DNSResolverError, Result};
 use crate::message::DNSMessage;
-use crate::query::{self};
-use crate::rr_types::RRType;
+use crate::query::{self, DNSHeader, DNSQuestion, ResponseCode};
+use crate::rdata::{RData, DNSKEY, DS, MX, NSEC, NSEC3, RRSIG, SOA, SRV};
+use crate::resource_record::DNSRecord;
+use crate::rr_types::{DNSClass, RRType};
+use itertools::Itertools;
+use rand::Rng;
+use std::collections::HashMap;
 use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::pin::Pin;
+use std::time::Duration;
 use tokio::net::UdpSocket;
+use tokio::time::timeout;
 
 // Resolver is a DNS resolver.
 pub struct Resolver {
     pub socket: UdpSocket,
+    // The DS records of the root zone's keys, which answers are validated against
+    // using DNSSEC. Answers can't be validated if there are none.
+    trust_anchors: Vec<DS>,
+    // Whether the names that we ask nameservers about are minimised (RFC 9156).
+    qname_minimisation: bool,
+    // Whether the case of the letters in the names that we ask nameservers about is
+    // randomised and must be echoed in their responses.
+    case_randomisation: bool,
+    // The nameserver that resolution starts at, whose port every nameserver is asked on.
+    root_server: SocketAddr,
+    // The time that a nameserver is given to respond to a query, after which it's
+    // considered to have failed.
+    query_timeout: Duration,
+}
+
+// The maximum number of AliasMode SVCB/HTTPS records that are followed while resolving.
+const MAX_ALIAS_CHAIN: usize = 8;
+
+// The maximum number of CNAME records that are followed while resolving a validated answer.
+const MAX_CNAME_CHAIN: usize = 8;
+
+// The maximum number of minimised queries made while resolving a domain, after which the
+// full domain is revealed, so that names with many labels don't need too many queries.
+const MAX_MINIMISED_QUERIES: usize = 10;
+
+// The root nameserver that resolution starts at by default, a.root-servers.net.
+const ROOT_SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(198, 41, 0, 4)), 53);
+
+// The UDP payload size that we advertise using EDNS, which is large enough for
+// most responses carrying DNSSEC records.
+const UDP_PAYLOAD_SIZE: u16 = 4096;
+
+// The time that a nameserver is given to respond to a query by default.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// MailExchangers are the mail exchangers of a domain as found by a MX lookup.
+#[derive(Debug)]
+pub enum MailExchangers {
+    // The MX records published by the domain, most preferred first.
+    Explicit(Vec<MX>),
+    // The domain publishes no MX records but has an address record, so it
+    // acts as its own mail exchanger with a preference of 0.
+    Implicit(MX),
+    // The domain has a null MX record (RFC 7505), i.e. it does not accept mail.
+    NullMX,
+}
+
+// ZoneApex is the apex of a zone, i.e. the name at which the zone is cut from its
+// parent, along with the SOA record of the zone.
+#[derive(Debug)]
+pub struct ZoneApex {
+    pub name: DomainName,
+    pub soa: SOA,
+}
+
+// ServiceTarget is a target of a service as found by a SRV lookup, along with
+// the addresses of the target, if they were asked for.
+#[derive(Debug)]
+pub struct ServiceTarget {
+    pub srv: SRV,
+    pub addresses: Vec<IpAddr>,
+}
+
+// ValidatedAnswer is the answer to a query along with the outcome of validating it
+// using DNSSEC.
+#[derive(Debug)]
+pub struct ValidatedAnswer {
+    pub message: DNSMessage,
+    pub status: ValidationStatus,
+}
+
+// ZoneKeys are the keys of a zone, if they could be validated.
+#[derive(Debug, Clone)]
+enum ZoneKeys {
+    Secure(Vec<DNSKEY>),
+    Insecure,
+    Bogus(String),
+}
+
+// Delegation is what the DS records (or the proof of their absence) at a name tell
+// us about the name.
+#[derive(Debug, Clone)]
+enum Delegation {
+    // The name is a signed zone whose keys are identified by the DS records.
+    Secure(Vec<DS>),
+    // The name is an unsigned zone or belongs to one.
+    Insecure,
+    // The name isn't the apex of a zone.
+    NotZoneCut,
+    Bogus(String),
+}
+
+// ValidationCache holds the keys and delegations that have been looked up while
+// validating an answer, so that each of them is fetched and validated only once.
+#[derive(Default)]
+struct ValidationCache {
+    keys: HashMap<DomainName, ZoneKeys>,
+    delegations: HashMap<DomainName, Delegation>,
 }
 
 impl Resolver {
@@ -16,152 +126,432 @@ impl Resolver {
         let socket = UdpSocket::bind(addr)
             .await
             .map_err(|e| DNSResolverError::ConnectionFailure(addr.to_string(), e.to_string()))?;
-        Ok(Resolver { socket })
+        Ok(Resolver {
+            socket,
+            trust_anchors: vec![],
+            qname_minimisation: false,
+            case_randomisation: false,
+            root_server: ROOT_SERVER,
+            query_timeout: QUERY_TIMEOUT,
+        })
+    }
+
+    // Sets the trust anchors that answers are validated against, which enables the
+    // DO bit in our queries so that nameservers include DNSSEC records.
+    pub fn set_trust_anchors(&mut self, trust_anchors: Vec<DS>) {
+        self.trust_anchors = trust_anchors;
+    }
+
+    // Sets whether QNAME minimisation is used, in which case a nameserver is only told
+    // as much of the domain as it needs to refer us to the next zone.
+    pub fn set_qname_minimisation(&mut self, qname_minimisation: bool) {
+        self.qname_minimisation = qname_minimisation;
+    }
+
+    // Sets whether the case of the letters in our queries is randomised (also known as
+    // DNS 0x20). Nameservers echo the question as is, so responses that don't match it
+    // exactly are rejected, which makes spoofing a response harder than guessing the ID.
+    pub fn set_case_randomisation(&mut self, case_randomisation: bool) {
+        self.case_randomisation = case_randomisation;
+    }
+
+    // Sets the nameserver that resolution starts at instead of a root nameserver. The
+    // nameservers that it refers us to are asked on its port as well, so that a single
+    // local nameserver can stand in for all of them.
+    pub fn set_root_server(&mut self, root_server: SocketAddr) {
+        self.root_server = root_server;
+    }
+
+    // Sets the time that a nameserver is given to respond to a query.
+    pub fn set_query_timeout(&mut self, query_timeout: Duration) {
+        self.query_timeout = query_timeout;
     }
 
     // Constructs a DNS query out of the provided domain and record type, resolves
     // the same and returns the resolved DNS message.
     pub async fn resolve(&self, domain: String, record_type: &RRType) -> Result<DNSMessage> {
         match record_type {
-            &RRType::A => self.resolve_a_record(domain).await,
-            &RRType::CNAME => self.resolve_cname_record(domain).await,
-            &RRType::TXT => self.resolve_txt_record(domain).await,
-            &RRType::NS => self.resolve_ns_record(domain).await,
-            _ => {
-                return Err(DNSResolverError::InvalidRecordType(
-                    record_type.as_ref().to_string(),
-                ))
+            RRType::SVCB | RRType::HTTPS => {
+                self.resolve_service_binding(domain, record_type.clone())
+                    .await
             }
+            _ => self.resolve_record(domain, record_type.clone()).await,
         }
     }
 
-    // Constructs a DNS query of type NS using the provided domain, resolves
-    // the same and returns the resolved DNS message.
-    async fn resolve_ns_record(&self, domain: String) -> Result<DNSMessage> {
-        let mut nameserver = String::from("198.41.0.4");
-        let record_type = RRType::NS;
+    // Looks up the mail exchangers of the provided domain. Explicit exchangers are sorted
+    // by their preference. If the domain exists and has no MX records but has an address
+    // record, then the domain itself is its implicit mail exchanger (RFC 5321 section 5.1),
+    // where the domain is the canonical name if the domain is an alias.
+    pub async fn lookup_mx(&self, domain: String) -> Result<MailExchangers> {
+        let (canonical, message) = self.resolve_canonical(domain.clone(), RRType::MX).await?;
+        let mut exchangers = message
+            .answers()
+            .iter()
+            .filter_map(|answer| match answer.rdata() {
+                Some(RData::MX(mx)) => Some(mx.clone()),
+                _ => None,
+            })
+            .collect_vec();
+        if exchangers.len() == 1 && exchangers[0].is_null() {
+            return Ok(MailExchangers::NullMX);
+        }
+        if !exchangers.is_empty() {
+            exchangers.retain(|mx| !mx.is_null());
+            exchangers.sort_by_key(|mx| mx.preference());
+            return Ok(MailExchangers::Explicit(exchangers));
+        }
+
+        // a domain that doesn't exist has no mail exchangers, implicit or not.
+        if message.header().response_code() == ResponseCode::NoError {
+            for record_type in [RRType::A, RRType::AAAA] {
+                let message = self
+                    .resolve_answer(canonical.clone(), record_type.clone())
+                    .await?;
+                if !message.answers_data(&record_type).is_empty() {
+                    let mx = MX::new(0, DomainName::new(canonical));
+                    return Ok(MailExchangers::Implicit(mx));
+                }
+            }
+        }
+        Err(DNSResolverError::LookupFailure(
+            RRType::MX.to_string(),
+            domain,
+        ))
+    }
+
+    // Looks up the targets of the provided service, where service is of the form
+    // _service._proto.name. The targets are ordered by their priority and, within a priority,
+    // by a weighted random selection (RFC 2782). If resolve_targets is true, then the addresses
+    // of each target are resolved as well. If the service is decidedly not available at the
+    // domain, then no targets are returned.
+    pub async fn lookup_srv(
+        &self,
+        service: String,
+        resolve_targets: bool,
+    ) -> Result<Vec<ServiceTarget>> {
+        let message = self.resolve_record(service, RRType::SRV).await?;
+        let records = message
+            .answers()
+            .iter()
+            .filter_map(|answer| match answer.rdata() {
+                Some(RData::SRV(srv)) => Some(srv.clone()),
+                _ => None,
+            })
+            .collect_vec();
+        if records.len() == 1 && records[0].is_unavailable() {
+            return Ok(vec![]);
+        }
+
+        let mut targets = vec![];
+        for srv in order_srv_records(records) {
+            let mut addresses = vec![];
+            if resolve_targets {
+                for record_type in [RRType::A, RRType::AAAA] {
+                    let target = srv.target().to_string();
+                    if let Ok(resolved) = self.resolve_record(target, record_type).await {
+                        addresses.extend(resolved.answers().iter().filter_map(|answer| {
+                            match answer.rdata() {
+                                Some(RData::A(ip)) => Some(IpAddr::V4(*ip)),
+                                Some(RData::AAAA(ip)) => Some(IpAddr::V6(*ip)),
+                                _ => None,
+                            }
+                        }));
+                    }
+                }
+            }
+            targets.push(ServiceTarget { srv, addresses });
+        }
+        Ok(targets)
+    }
 
+    // Finds the apex of the zone that the provided name belongs to, and returns the same
+    // along with the SOA. The SOA is asked for at the name itself, without following
+    // CNAMEs. If the name isn't the apex, the negative answer carries the SOA of the
+    // enclosing zone in its authorities section (RFC 2308 section 3), whose owner is the
+    // apex. Only negative answers without a SOA move the search up to the parent, while
+    // any other failure is returned.
+    pub async fn find_zone_apex(&self, name: String) -> Result<ZoneApex> {
+        let lookup_failure = || DNSResolverError::LookupFailure(String::from("SOA"), name.clone());
+        let soa_of = |record: &DNSRecord| match record.rdata() {
+            Some(RData::SOA(soa)) => Some(ZoneApex {
+                name: record.name().clone(),
+                soa: soa.clone(),
+            }),
+            _ => None,
+        };
+        let mut candidate = DomainName::new(name.clone());
         loop {
-            println!(
-                "Querying {} for {} about record type {:?}",
-                nameserver, domain, record_type
-            );
-            let response = self
-                .send_query(nameserver, domain.clone(), record_type.clone())
+            let message = self
+                .resolve_authoritative(candidate.to_string(), RRType::SOA)
                 .await?;
-            let message = DNSMessage::decode(&response)?;
-
-            if message.answers_data(&record_type).len() > 0 {
-                return Ok(message);
-            } else if message.answers_data(&RRType::CNAME).len() > 0 {
-                let cname = message.answers_data(&RRType::CNAME)[0].clone();
-                let resolved_cname = self.resolve_a_record(cname).await?;
-                return Ok(resolved_cname);
-            } else if let Some(ns_ip) = message.ns_ip() {
-                nameserver = ns_ip.to_owned();
-            } else if let Some(ns) = message.nameserver() {
-                let resolved_ns = self.resolve_a_record(ns.to_owned()).await?;
-                nameserver = resolved_ns.answers_data(&RRType::A)[0].clone();
-            } else {
-                return Err(DNSResolverError::LookupFailure(String::from("NS"), domain));
+            let apex = message
+                .answers()
+                .iter()
+                .filter(|answer| answer.name() == &candidate)
+                .find_map(soa_of);
+            if let Some(apex) = apex {
+                return Ok(apex);
+            }
+
+            // an alias can't be the apex of a zone, so it's treated like NODATA, while
+            // any other answer doesn't make sense for a SOA question.
+            let alias = message
+                .answers()
+                .iter()
+                .any(|answer| answer.r_type() == &RRType::CNAME);
+            if !alias {
+                if !message.answers().is_empty() {
+                    return Err(lookup_failure());
+                }
+                let apex = message
+                    .authorities()
+                    .iter()
+                    .filter(|authority| candidate.is_subdomain_of(authority.name()))
+                    .find_map(soa_of);
+                if let Some(apex) = apex {
+                    return Ok(apex);
+                }
             }
+
+            candidate = candidate.parent().ok_or_else(lookup_failure)?;
         }
     }
 
-    // Constructs a DNS query of type CNAME using the provided domain, resolves
-    // the same and returns the resolved DNS message.
-    async fn resolve_cname_record(&self, domain: String) -> Result<DNSMessage> {
-        let mut nameserver = String::from("198.41.0.4");
-        let record_type = RRType::CNAME;
+    // Resolves the SVCB or HTTPS records of the provided domain. If the answer is an
+    // AliasMode record, then its target is resolved instead, much like a CNAME (RFC 9460).
+    // If the target of an alias can't be resolved, then the alias itself is returned.
+    async fn resolve_service_binding(
+        &self,
+        domain: String,
+        record_type: RRType,
+    ) -> Result<DNSMessage> {
+        let mut message = self
+            .resolve_record(domain.clone(), record_type.clone())
+            .await?;
+        for _ in 0..MAX_ALIAS_CHAIN {
+            let alias = message
+                .answers()
+                .iter()
+                .find_map(|answer| match answer.rdata() {
+                    Some(RData::SVCB(svcb)) | Some(RData::HTTPS(svcb)) if svcb.is_alias() => {
+                        Some(svcb.target().clone())
+                    }
+                    _ => None,
+                });
+            // an alias to the root domain signifies that the service is not available.
+            match alias {
+                Some(target) if !target.is_root() => {
+                    match self
+                        .resolve_record(target.to_string(), record_type.clone())
+                        .await
+                    {
+                        Ok(resolved) => message = resolved,
+                        Err(_) => return Ok(message),
+                    }
+                }
+                _ => return Ok(message),
+            }
+        }
+        // the target of the last alias that was followed may be the service itself.
+        let service = message.answers().iter().any(|answer| match answer.rdata() {
+            Some(RData::SVCB(svcb)) | Some(RData::HTTPS(svcb)) => !svcb.is_alias(),
+            _ => false,
+        });
+        if service {
+            return Ok(message);
+        }
+        Err(DNSResolverError::LookupFailure(
+            record_type.to_string(),
+            domain,
+        ))
+    }
 
-        loop {
-            println!(
-                "Querying {} for {} about record type {:?}",
-                nameserver, domain, record_type
-            );
-            let response = self
-                .send_query(nameserver, domain.clone(), record_type.clone())
+    // Resolves the provided domain for the provided record type and validates the answer
+    // using DNSSEC, starting from the trust anchors. If the answer is a CNAME, its target
+    // is resolved and validated instead, unless a CNAME was asked for. Unlike resolve, a
+    // response proving that the domain or the record type doesn't exist is returned as is,
+    // since the proof is validated as well.
+    pub async fn resolve_validated(
+        &self,
+        domain: String,
+        record_type: &RRType,
+    ) -> Result<ValidatedAnswer> {
+        let mut cache = ValidationCache::default();
+        let mut status = ValidationStatus::Secure;
+        let mut name = domain.clone();
+        for _ in 0..MAX_CNAME_CHAIN {
+            let message = self
+                .resolve_authoritative(name.clone(), record_type.clone())
                 .await?;
-            let message = DNSMessage::decode(&response)?;
-
-            if message.answers_data(&record_type).len() > 0 {
-                return Ok(message);
-            } else if let Some(ns_ip) = message.ns_ip() {
-                nameserver = ns_ip.to_owned();
-            } else if let Some(ns) = message.nameserver() {
-                let resolved_ns = self.resolve_a_record(ns.to_owned()).await?;
-                nameserver = resolved_ns.answers_data(&RRType::A)[0].clone();
+            status = if self.trust_anchors.is_empty() {
+                ValidationStatus::Insecure
             } else {
-                return Err(DNSResolverError::LookupFailure(
-                    String::from("CNAME"),
-                    domain,
-                ));
+                let qname = DomainName::new(name.clone());
+                let validated = self
+                    .validate_message(&message, &qname, record_type, &mut cache)
+                    .await;
+                status.and(validated)
+            };
+
+            let cname = message.answers_data(&RRType::CNAME);
+            if record_type == &RRType::CNAME
+                || !message.answers_data(record_type).is_empty()
+                || cname.is_empty()
+            {
+                return Ok(ValidatedAnswer { message, status });
             }
+            name = cname[0].clone();
         }
+        Err(DNSResolverError::LookupFailure(
+            record_type.to_string(),
+            domain,
+        ))
     }
 
-    // Constructs a DNS query of type TXT using the provided domain, resolves
-    // the same and returns the resolved DNS message.
-    async fn resolve_txt_record(&self, domain: String) -> Result<DNSMessage> {
-        let mut nameserver = String::from("198.41.0.4");
-        let record_type = RRType::TXT;
+    // Resolves the provided domain for the provided record type. If the answer is a
+    // CNAME, its target is resolved instead, unless a CNAME was asked for.
+    async fn resolve_record(&self, domain: String, record_type: RRType) -> Result<DNSMessage> {
+        let message = self
+            .resolve_answer(domain.clone(), record_type.clone())
+            .await?;
+        if message.answers_data(&record_type).is_empty() {
+            return Err(DNSResolverError::LookupFailure(
+                record_type.to_string(),
+                domain,
+            ));
+        }
+        Ok(message)
+    }
+
+    // Resolves the provided domain for the provided record type like resolve_record, but
+    // returns a negative response as is, so that NXDOMAIN can be told apart from NODATA.
+    async fn resolve_answer(&self, domain: String, record_type: RRType) -> Result<DNSMessage> {
+        let (_, message) = self.resolve_canonical(domain, record_type).await?;
+        Ok(message)
+    }
 
+    // Resolves the provided domain for the provided record type like resolve_answer, and
+    // returns the canonical name of the domain along with the answer, i.e. the name at
+    // the end of the CNAME records that were followed.
+    async fn resolve_canonical(
+        &self,
+        domain: String,
+        record_type: RRType,
+    ) -> Result<(String, DNSMessage)> {
+        let mut name = domain;
         loop {
-            println!(
-                "Querying {} for {} about record type {:?}",
-                nameserver, domain, record_type
-            );
-            let response = self
-                .send_query(nameserver, domain.clone(), record_type.clone())
+            let message = self
+                .resolve_authoritative(name.clone(), record_type.clone())
                 .await?;
-            let message = DNSMessage::decode(&response)?;
-
-            if message.answers_data(&record_type).len() > 0 {
-                return Ok(message);
-            } else if let Some(ns_ip) = message.ns_ip() {
-                nameserver = ns_ip.to_owned();
-            } else if let Some(ns) = message.nameserver() {
-                let resolved_ns = self.resolve_a_record(ns.to_owned()).await?;
-                nameserver = resolved_ns.answers_data(&RRType::A)[0].clone();
-            } else {
-                return Err(DNSResolverError::LookupFailure(String::from("TXT"), domain));
+            if record_type != RRType::CNAME
+                && message.answers_data(&record_type).is_empty()
+                && !message.answers_data(&RRType::CNAME).is_empty()
+            {
+                name = message.answers_data(&RRType::CNAME)[0].clone();
+                continue;
             }
+            return Ok((name, message));
         }
     }
 
-    // Constructs a DNS query of type A using the provided domain, resolves
-    // the same and returns the resolved DNS message.
-    fn resolve_a_record(
+    // Resolves the provided domain for the provided record type, starting at a root
+    // nameserver and following referrals until a nameserver answers authoritatively,
+    // and returns the answer. The answer may be a CNAME or a negative response.
+    // If QNAME minimisation is enabled, then each nameserver is only asked about the
+    // name one label below its zone, until the nameserver of the domain's zone is found.
+    fn resolve_authoritative(
         &self,
         domain: String,
-    ) -> Pin<Box<dyn Future<Output = Result<DNSMessage>> + '_>> {
+        record_type: RRType,
+    ) -> Pin<Box<dyn Future<Output = Result<DNSMessage>> + Send + '_>> {
         Box::pin(async move {
-            let mut nameserver = String::from("198.41.0.4");
-            let record_type = RRType::A;
+            let mut nameserver = self.root_server.ip().to_string();
+            let name = DomainName::new(domain.clone());
+            let labels = name.labels().len();
+            // the number of labels of the domain that make up the zone of the nameserver.
+            let mut revealed = 0;
+            let mut minimise = self.qname_minimisation;
+            let mut minimised_queries = 0;
 
             loop {
+                let minimised =
+                    minimise && revealed + 1 < labels && minimised_queries < MAX_MINIMISED_QUERIES;
+                // a minimised query asks for the A records of the name, since some
+                // nameservers don't respond properly to other types for names that
+                // they aren't authoritative for (RFC 9156 section 3).
+                let (query_name, query_type) = if minimised {
+                    minimised_queries += 1;
+                    (name.suffix(revealed + 1).to_string(), RRType::A)
+                } else {
+                    (domain.clone(), record_type.clone())
+                };
+
                 println!(
-                    "Querying {} for {} about record type {:?}",
-                    nameserver, domain, record_type
+                    "Querying {} for {} about record type {}",
+                    nameserver, query_name, query_type
                 );
                 let response = self
-                    .send_query(nameserver, domain.clone(), record_type.clone())
+                    .send_query(nameserver.clone(), query_name, query_type)
                     .await?;
                 let message = DNSMessage::decode(&response)?;
+                let rcode = message.header().response_code();
+
+                // a SOA in the authorities section means that the response is negative,
+                // rather than a referral.
+                let negative = rcode == ResponseCode::NXDomain
+                    || message
+                        .authorities()
+                        .iter()
+                        .any(|rr| rr.r_type() == &RRType::SOA);
+                let zone_cut = message
+                    .authorities()
+                    .iter()
+                    .find(|rr| rr.r_type() == &RRType::NS)
+                    .map(|rr| rr.name().clone());
 
-                if message.answers_data(&record_type).len() > 0 {
+                if minimised {
+                    match zone_cut {
+                        // referrals are followed as usual.
+                        Some(_) if !negative && message.header().num_answers() == 0 => {}
+                        // the name exists but isn't a zone cut, so the same nameserver
+                        // is asked about the name one label below.
+                        _ if rcode == ResponseCode::NoError => {
+                            revealed += 1;
+                            continue;
+                        }
+                        // some nameservers respond with errors or NXDOMAIN for names that
+                        // have no records but exist, so we fall back to the full name.
+                        _ => {
+                            minimise = false;
+                            continue;
+                        }
+                    }
+                }
+
+                if rcode != ResponseCode::NoError && rcode != ResponseCode::NXDomain {
+                    return Err(DNSResolverError::LookupFailure(
+                        record_type.to_string(),
+                        domain,
+                    ));
+                }
+                if !message.answers_data(&record_type).is_empty()
+                    || !message.answers_data(&RRType::CNAME).is_empty()
+                    || negative
+                {
                     return Ok(message);
-                } else if message.answers_data(&RRType::CNAME).len() > 0 {
-                    let cname = message.answers_data(&RRType::CNAME)[0].clone();
-                    let resolved_cname = self.resolve_a_record(cname).await?;
-                    return Ok(resolved_cname);
-                } else if let Some(ns_ip) = message.ns_ip() {
+                }
+
+                if let Some(zone) = zone_cut {
+                    revealed = dnssec::label_count(&zone).into();
+                }
+                if let Some(ns_ip) = message.ns_ip() {
                     nameserver = ns_ip.to_owned();
                 } else if let Some(ns) = message.nameserver() {
-                    let resolved_ns = self.resolve_a_record(ns.to_owned()).await?;
+                    let resolved_ns = self.resolve_record(ns.to_owned(), RRType::A).await?;
                     nameserver = resolved_ns.answers_data(&RRType::A)[0].clone();
                 } else {
-                    return Err(DNSResolverError::LookupFailure(String::from("A"), domain));
+                    return Ok(message);
                 }
             }
         })
@@ -169,31 +559,547 @@ impl Resolver {
 
     async fn send_query(
         &self,
-        mut nameserver: String,
+        nameserver: String,
         domain: String,
         record_type: RRType,
     ) -> Result<Vec<u8>> {
-        nameserver.push_str(":53");
+        let nameserver = format!("{}:{}", nameserver, self.root_server.port());
         self.socket
             .connect(&nameserver)
             .await
-            .map_err(|e| DNSResolverError::ConnectionFailure(nameserver, e.to_string()))?;
+            .map_err(|e| DNSResolverError::ConnectionFailure(nameserver.clone(), e.to_string()))?;
 
-        let q = query::build_query(domain, record_type)?;
+        let query_name = if self.case_randomisation {
+            randomise_case(&domain)
+        } else {
+            domain.clone()
+        };
+        // ask for DNSSEC records only if we're able to validate them.
+        let mut edns = Edns::new(UDP_PAYLOAD_SIZE);
+        edns.set_dnssec_ok(!self.trust_anchors.is_empty());
+        // every query gets an ID of its own, which makes forging a reply harder.
+        let id = rand::random();
+        let q = query::build_query(
+            id,
+            query_name.clone(),
+            record_type.clone(),
+            DNSClass::IN,
+            Some(&edns),
+        )?;
         let query = q.as_slice();
         self.socket
             .send(query)
             .await
             .map_err(|e| DNSResolverError::IOFailure(String::from("send"), e.to_string()))?;
 
-        let mut buf = [0; 1024];
-        let no = self
-            .socket
-            .recv(&mut buf)
+        // replies with another ID aren't replies to this query, like late replies to an
+        // earlier query or forged ones, so they're dropped while we wait for ours. A
+        // nameserver that doesn't reply in time has failed, just like one that can't be
+        // reached.
+        let mut buf = [0; UDP_PAYLOAD_SIZE as usize];
+        let receive = async {
+            loop {
+                let no = self.socket.recv(&mut buf).await.map_err(|e| {
+                    DNSResolverError::IOFailure(String::from("receive"), e.to_string())
+                })?;
+                if buf[..no].starts_with(&id.to_be_bytes()) {
+                    return Ok(buf[..no].to_vec());
+                }
+            }
+        };
+        let mut reply = timeout(self.query_timeout, receive)
             .await
-            .map_err(|e| DNSResolverError::IOFailure(String::from("receive"), e.to_string()))?;
-
-        let reply = buf[..no].to_vec();
+            .map_err(|_| DNSResolverError::Timeout(nameserver.clone()))??;
+        self.check_question(&reply, &query_name, &record_type)
+            .map_err(|reason| DNSResolverError::MismatchedResponse(nameserver, reason))?;
+        if self.case_randomisation {
+            // restore the case of the question, which also restores the names in the
+            // response that are compressed using a pointer to it.
+            let mut encoded = vec![];
+            DomainName::new(domain).encode(&mut encoded)?;
+            if let Some(echoed) = reply.get_mut(12..12 + encoded.len()) {
+                if echoed.eq_ignore_ascii_case(&encoded) {
+                    echoed.copy_from_slice(&encoded);
+                }
+            }
+        }
         Ok(reply)
     }
+
+    // Checks that the question in the reply is the one that we asked. The case of the
+    // name only has to match if it was randomised.
+    fn check_question(
+        &self,
+        reply: &[u8],
+        query_name: &str,
+        record_type: &RRType,
+    ) -> std::result::Result<(), String> {
+        let mut decoder = Decoder::new(reply);
+        let header = DNSHeader::decode(&mut decoder).map_err(|e| e.to_string())?;
+        if header.num_questions() != 1 {
+            return Err(format!(
+                "expected one question, got {}",
+                header.num_questions()
+            ));
+        }
+        let question = DNSQuestion::decode(&mut decoder).map_err(|e| e.to_string())?;
+
+        let echoed = question.name();
+        let asked = DomainName::new(query_name.to_string());
+        let same_name = if self.case_randomisation {
+            echoed.labels() == asked.labels()
+        } else {
+            echoed == &asked
+        };
+        if !same_name || question.q_type() != record_type {
+            return Err(format!(
+                "asked about {} {}, got {} {}",
+                asked,
+                record_type,
+                echoed,
+                question.q_type()
+            ));
+        }
+        Ok(())
+    }
+
+    // Validates all the RRsets in the answers section of the message. If the message
+    // doesn't answer the query, then the proof of that in the authorities section is
+    // validated instead (RFC 4035 section 5).
+    async fn validate_message<'a>(
+        &'a self,
+        message: &'a DNSMessage,
+        qname: &'a DomainName,
+        qtype: &'a RRType,
+        cache: &'a mut ValidationCache,
+    ) -> ValidationStatus {
+        let answers = message.answers();
+        let mut status = ValidationStatus::Secure;
+        let mut wildcards = vec![];
+        for (owner, r_type) in rrset_keys(answers) {
+            let records = rrset(answers, &owner, &r_type);
+            let rrsigs = rrsigs(answers, &owner, &r_type);
+            let (validated, labels) = self.validate_rrset(&records, &rrsigs, &owner, cache).await;
+            if let Some(labels) = labels.filter(|labels| *labels < dnssec::label_count(&owner)) {
+                wildcards.push((owner, labels));
+            }
+            status = status.and(validated);
+        }
+
+        let answered = answers.iter().any(|answer| {
+            answer.name() == qname
+                && (answer.r_type() == qtype || answer.r_type() == &RRType::CNAME)
+        });
+        if !answered {
+            // the absence of DS records is proven by the parent zone.
+            let name = match qtype {
+                RRType::DS => qname.parent().unwrap_or_else(DomainName::root),
+                _ => qname.clone(),
+            };
+            let (denied, _) = self
+                .validate_denial(message, qname, qtype, &name, cache)
+                .await;
+            return status.and(denied);
+        }
+
+        // an answer that was synthesized from a wildcard is only valid if the query name
+        // doesn't exist (RFC 4035 section 5.3.4).
+        if !wildcards.is_empty() {
+            let (denied, nsecs, nsec3s) = self.denial_records(message, qname, cache).await;
+            status = status.and(denied);
+            for (owner, labels) in wildcards {
+                if dnssec::wildcard_proof(&owner, labels, &nsecs, &nsec3s) {
+                    continue;
+                }
+                // the NSEC3 records that would prove it may use too many iterations to
+                // be checked.
+                if nsec3s
+                    .iter()
+                    .any(|(_, nsec3)| dnssec::excessive_iterations(nsec3))
+                {
+                    status = status.and(ValidationStatus::Insecure);
+                } else {
+                    status = status.and(ValidationStatus::Bogus(format!(
+                        "there's no proof that {} does not exist",
+                        owner
+                    )));
+                }
+            }
+        }
+        status
+    }
+
+    // Validates the RRset using its RRSIGs and the keys of the signer. An unsigned RRset
+    // is only acceptable if the provided name belongs to an unsigned zone. Returns the
+    // number of labels of the RRSIG that was used, if any.
+    fn validate_rrset<'a>(
+        &'a self,
+        records: &'a [&'a DNSRecord],
+        rrsigs: &'a [RRSIG],
+        name: &'a DomainName,
+        cache: &'a mut ValidationCache,
+    ) -> Pin<Box<dyn Future<Output = (ValidationStatus, Option<u8>)> + Send + 'a>> {
+        Box::pin(async move {
+            let rrset = match records.first() {
+                Some(record) => format!("{} {}", record.name(), record.r_type()),
+                None => return (ValidationStatus::Secure, None),
+            };
+            if rrsigs.is_empty() {
+                let status = match self.name_status(name.clone(), cache).await {
+                    ValidationStatus::Secure => {
+                        ValidationStatus::Bogus(format!("{} is not signed", rrset))
+                    }
+                    status => status,
+                };
+                return (status, None);
+            }
+
+            let now = dnssec::now();
+            let mut reason = format!("{} has no valid signatures", rrset);
+            for rrsig in rrsigs {
+                match self.zone_keys(rrsig.signer_name().clone(), cache).await {
+                    ZoneKeys::Secure(keys) => {
+                        for key in &keys {
+                            match dnssec::verify_rrsig(rrsig, records, key, now) {
+                                Ok(()) => return (ValidationStatus::Secure, Some(rrsig.labels())),
+                                Err(e) => reason = e,
+                            }
+                        }
+                    }
+                    ZoneKeys::Insecure => return (ValidationStatus::Insecure, None),
+                    ZoneKeys::Bogus(e) => reason = e,
+                }
+            }
+            (ValidationStatus::Bogus(reason), None)
+        })
+    }
+
+    // Validates the NSEC and NSEC3 records in the authorities section of the message and
+    // returns what they prove about the query. If there is no proof, then the query name
+    // must belong to an unsigned zone, which is checked using the provided name.
+    async fn validate_denial<'a>(
+        &'a self,
+        message: &'a DNSMessage,
+        qname: &'a DomainName,
+        qtype: &'a RRType,
+        name: &'a DomainName,
+        cache: &'a mut ValidationCache,
+    ) -> (ValidationStatus, Option<Denial>) {
+        let (status, nsecs, nsec3s) = self.denial_records(message, name, cache).await;
+        if let ValidationStatus::Bogus(reason) = status {
+            return (ValidationStatus::Bogus(reason), None);
+        }
+
+        let nxdomain = message.header().response_code() == ResponseCode::NXDomain;
+        let denial = dnssec::nsec_denial(qname, qtype, nxdomain, &nsecs)
+            .or_else(|| dnssec::nsec3_denial(qname, qtype, nxdomain, &nsec3s));
+        match denial {
+            Some(Denial::OptOut) | Some(Denial::ExcessiveIterations) => {
+                (ValidationStatus::Insecure, denial)
+            }
+            Some(_) => (status, denial),
+            None => match self.name_status(name.clone(), cache).await {
+                ValidationStatus::Secure => (
+                    ValidationStatus::Bogus(format!(
+                        "there's no proof that {} {} does not exist",
+                        qname, qtype
+                    )),
+                    None,
+                ),
+                status => (status, None),
+            },
+        }
+    }
+
+    // Validates the NSEC and NSEC3 RRsets in the authorities section of the message and
+    // returns the ones that are secure.
+    async fn denial_records<'a>(
+        &'a self,
+        message: &'a DNSMessage,
+        name: &'a DomainName,
+        cache: &'a mut ValidationCache,
+    ) -> (
+        ValidationStatus,
+        Vec<(DomainName, NSEC)>,
+        Vec<(DomainName, NSEC3)>,
+    ) {
+        let authorities = message.authorities();
+        let mut status = ValidationStatus::Secure;
+        let mut nsecs = vec![];
+        let mut nsec3s = vec![];
+        for (owner, r_type) in rrset_keys(authorities) {
+            if r_type != RRType::NSEC && r_type != RRType::NSEC3 {
+                continue;
+            }
+            let records = rrset(authorities, &owner, &r_type);
+            let rrsigs = rrsigs(authorities, &owner, &r_type);
+            let (validated, _) = self.validate_rrset(&records, &rrsigs, name, cache).await;
+            if validated != ValidationStatus::Secure {
+                status = status.and(validated);
+                continue;
+            }
+            for record in records {
+                match record.rdata() {
+                    Some(RData::NSEC(nsec)) => nsecs.push((owner.clone(), nsec.clone())),
+                    Some(RData::NSEC3(nsec3)) => nsec3s.push((owner.clone(), nsec3.clone())),
+                    _ => {}
+                }
+            }
+        }
+        (status, nsecs, nsec3s)
+    }
+
+    // Returns the status of the provided name, i.e. whether it belongs to a signed zone
+    // that has a chain of trust from the trust anchors.
+    fn name_status<'a>(
+        &'a self,
+        name: DomainName,
+        cache: &'a mut ValidationCache,
+    ) -> Pin<Box<dyn Future<Output = ValidationStatus> + Send + 'a>> {
+        Box::pin(async move {
+            if let Some(parent) = name.parent() {
+                match self.delegation(name.clone(), cache).await {
+                    Delegation::Secure(_) => {}
+                    Delegation::Insecure => return ValidationStatus::Insecure,
+                    Delegation::NotZoneCut => return self.name_status(parent, cache).await,
+                    Delegation::Bogus(reason) => return ValidationStatus::Bogus(reason),
+                }
+            }
+            match self.zone_keys(name, cache).await {
+                ZoneKeys::Secure(_) => ValidationStatus::Secure,
+                ZoneKeys::Insecure => ValidationStatus::Insecure,
+                ZoneKeys::Bogus(reason) => ValidationStatus::Bogus(reason),
+            }
+        })
+    }
+
+    // Returns the keys of the provided zone, which are validated using the DS records of
+    // the zone, or the trust anchors if it's the root zone.
+    fn zone_keys<'a>(
+        &'a self,
+        zone: DomainName,
+        cache: &'a mut ValidationCache,
+    ) -> Pin<Box<dyn Future<Output = ZoneKeys> + Send + 'a>> {
+        Box::pin(async move {
+            let key = zone.clone();
+            if let Some(keys) = cache.keys.get(&key) {
+                return keys.clone();
+            }
+            // the keys of a zone can't be used to validate themselves, so lookups that
+            // loop back to the zone fail.
+            let looped = format!("the keys of {} depend on themselves", zone);
+            cache.keys.insert(key.clone(), ZoneKeys::Bogus(looped));
+
+            let keys = self.fetch_zone_keys(zone, cache).await;
+            cache.keys.insert(key, keys.clone());
+            keys
+        })
+    }
+
+    async fn fetch_zone_keys(&self, zone: DomainName, cache: &mut ValidationCache) -> ZoneKeys {
+        let anchors = if zone.is_root() {
+            self.trust_anchors.clone()
+        } else {
+            match self.delegation(zone.clone(), cache).await {
+                Delegation::Secure(anchors) => anchors,
+                Delegation::Insecure => return ZoneKeys::Insecure,
+                Delegation::NotZoneCut => {
+                    return ZoneKeys::Bogus(format!("{} is not a zone", zone))
+                }
+                Delegation::Bogus(reason) => return ZoneKeys::Bogus(reason),
+            }
+        };
+        // a zone whose keys only use algorithms that we don't support is treated as
+        // unsigned (RFC 4035 section 5.2).
+        let anchors = anchors
+            .into_iter()
+            .filter(|ds| {
+                dnssec::is_supported_algorithm(ds.algorithm())
+                    && dnssec::is_supported_digest_type(ds.digest_type())
+            })
+            .collect_vec();
+        if anchors.is_empty() {
+            return ZoneKeys::Insecure;
+        }
+
+        let message = match self
+            .resolve_authoritative(zone.to_string(), RRType::DNSKEY)
+            .await
+        {
+            Ok(message) => message,
+            Err(e) => return ZoneKeys::Bogus(format!("the keys of {} are missing: {}", zone, e)),
+        };
+        let answers = message.answers();
+        let records = rrset(answers, &zone, &RRType::DNSKEY);
+        let keys = records
+            .iter()
+            .filter_map(|record| match record.rdata() {
+                Some(RData::DNSKEY(key)) => Some(key.clone()),
+                _ => None,
+            })
+            .collect_vec();
+
+        // the keys must be signed by a key that's identified by the anchors.
+        let rrsigs = rrsigs(answers, &zone, &RRType::DNSKEY);
+        let now = dnssec::now();
+        let signed = keys
+            .iter()
+            .filter(|key| anchors.iter().any(|ds| dnssec::ds_matches(&zone, key, ds)))
+            .any(|key| {
+                rrsigs
+                    .iter()
+                    .any(|rrsig| dnssec::verify_rrsig(rrsig, &records, key, now).is_ok())
+            });
+        if signed {
+            ZoneKeys::Secure(keys)
+        } else {
+            ZoneKeys::Bogus(format!("the keys of {} don't match its DS records", zone))
+        }
+    }
+
+    // Returns what the DS records at the provided name tell us about it. The DS records
+    // belong to the parent zone, so they're validated using the keys of the parent.
+    fn delegation<'a>(
+        &'a self,
+        name: DomainName,
+        cache: &'a mut ValidationCache,
+    ) -> Pin<Box<dyn Future<Output = Delegation> + Send + 'a>> {
+        Box::pin(async move {
+            let key = name.clone();
+            if let Some(delegation) = cache.delegations.get(&key) {
+                return delegation.clone();
+            }
+            let looped = format!("the DS records of {} depend on themselves", name);
+            cache
+                .delegations
+                .insert(key.clone(), Delegation::Bogus(looped));
+
+            let delegation = self.fetch_delegation(name, cache).await;
+            cache.delegations.insert(key, delegation.clone());
+            delegation
+        })
+    }
+
+    async fn fetch_delegation(&self, name: DomainName, cache: &mut ValidationCache) -> Delegation {
+        let message = match self
+            .resolve_authoritative(name.to_string(), RRType::DS)
+            .await
+        {
+            Ok(message) => message,
+            Err(e) => {
+                return Delegation::Bogus(format!("the DS records of {} are missing: {}", name, e))
+            }
+        };
+        let answers = message.answers();
+        let parent = name.parent().unwrap_or_else(DomainName::root);
+
+        let records = rrset(answers, &name, &RRType::DS);
+        if !records.is_empty() {
+            let rrsigs = rrsigs(answers, &name, &RRType::DS);
+            return match self.validate_rrset(&records, &rrsigs, &parent, cache).await {
+                (ValidationStatus::Secure, _) => Delegation::Secure(
+                    records
+                        .iter()
+                        .filter_map(|record| match record.rdata() {
+                            Some(RData::DS(ds)) => Some(ds.clone()),
+                            _ => None,
+                        })
+                        .collect_vec(),
+                ),
+                (ValidationStatus::Insecure, _) => Delegation::Insecure,
+                (ValidationStatus::Bogus(reason), _) => Delegation::Bogus(reason),
+            };
+        }
+        // a name that is an alias can't be a zone.
+        if !rrset(answers, &name, &RRType::CNAME).is_empty() {
+            return Delegation::NotZoneCut;
+        }
+
+        match self
+            .validate_denial(&message, &name, &RRType::DS, &parent, cache)
+            .await
+        {
+            (ValidationStatus::Bogus(reason), _) => Delegation::Bogus(reason),
+            (_, Some(Denial::NXDomain)) | (_, Some(Denial::NoData { delegation: false })) => {
+                Delegation::NotZoneCut
+            }
+            // either an unsigned delegation or a name in an unsigned zone.
+            _ => Delegation::Insecure,
+        }
+    }
+}
+
+// Returns the keys of the RRsets among the records, i.e. their owners and types. RRSIGs
+// aren't RRsets of their own, but belong to the RRsets that they cover.
+fn rrset_keys(records: &[DNSRecord]) -> Vec<(DomainName, RRType)> {
+    records
+        .iter()
+        .filter(|record| record.r_type() != &RRType::RRSIG && record.r_type() != &RRType::OPT)
+        .map(|record| (record.name().clone(), record.r_type().clone()))
+        .unique()
+        .collect_vec()
+}
+
+// Returns the records that make up the RRset with the provided owner and type.
+fn rrset<'a>(records: &'a [DNSRecord], owner: &DomainName, r_type: &RRType) -> Vec<&'a DNSRecord> {
+    records
+        .iter()
+        .filter(|record| record.r_type() == r_type && record.name() == owner)
+        .collect_vec()
+}
+
+// Returns the RRSIGs that cover the RRset with the provided owner and type.
+fn rrsigs(records: &[DNSRecord], owner: &DomainName, r_type: &RRType) -> Vec<RRSIG> {
+    records
+        .iter()
+        .filter(|record| record.name() == owner)
+        .filter_map(|record| match record.rdata() {
+            Some(RData::RRSIG(rrsig)) if rrsig.type_covered() == r_type => Some(rrsig.clone()),
+            _ => None,
+        })
+        .collect_vec()
+}
+
+// Randomises the case of each letter in the domain.
+fn randomise_case(domain: &str) -> String {
+    let mut rng = rand::thread_rng();
+    domain
+        .chars()
+        .map(|c| {
+            if rng.gen() {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            }
+        })
+        .collect()
+}
+
+// Orders the SRV records by their priority. Records with the same priority are ordered
+// using the weighted random selection described in RFC 2782; each record is picked with
+// a probability proportional to its weight, with records of weight 0 having a small
+// chance of being picked.
+fn order_srv_records(mut records: Vec<SRV>) -> Vec<SRV> {
+    let mut rng = rand::thread_rng();
+    let mut ordered = vec![];
+
+    records.sort_by_key(|srv| (srv.priority(), srv.weight() != 0));
+    let groups = records.into_iter().group_by(|srv| srv.priority());
+    for (_, group) in &groups {
+        let mut group = group.collect_vec();
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|srv| u32::from(srv.weight())).sum();
+            let chosen = rng.gen_range(0..=total);
+            let mut running_sum = 0;
+            let idx = group
+                .iter()
+                .position(|srv| {
+                    running_sum += u32::from(srv.weight());
+                    running_sum >= chosen
+                })
+                .unwrap_or(0);
+            ordered.push(group.remove(idx));
+        }
+    }
+    ordered
 }
@@ -1,21 +1,21 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
 use std::time::Duration;
 
 use itertools::Itertools;
 
-use crate::domain_name::{DomainName, LabelSequenceParser};
-use crate::error::{map_decode_err, DNSResolverError, Result};
-use crate::rr_types::RRType;
-use crate::utils;
+use crate::decoder::Decoder;
+use crate::domain_name::{DomainName, NameCompressor};
+use crate::error::{map_encode_err, Result};
+use crate::rdata::RData;
+use crate::rr_types::{DNSClass, RRType};
 
 // DNSRecord represents a DNS resource record.
 #[derive(Debug, Clone)]
 pub struct DNSRecord {
     name: DomainName,
     r_type: RRType,
-    class: u16,
+    class: DNSClass,
     ttl: Duration,
-    rd_len: u16,
     data: Data,
 }
 
@@ -24,123 +24,122 @@ pub struct DNSRecord {
 struct Data {
     raw: Vec<u8>,
     parsed: Option<String>,
+    rdata: Option<RData>,
 }
 
 impl DNSRecord {
+    pub fn new(
+        name: DomainName,
+        r_type: RRType,
+        class: DNSClass,
+        ttl: Duration,
+        rdata: RData,
+    ) -> Result<DNSRecord> {
+        let mut raw = vec![];
+        rdata.encode(&mut raw)?;
+        // the data has to fit within the length of the record.
+        u16::try_from(raw.len()).map_err(|e| map_encode_err("rr", &e))?;
+        Ok(DNSRecord {
+            name,
+            r_type,
+            class,
+            ttl,
+            data: Data {
+                raw,
+                parsed: Some(rdata.to_string()),
+                rdata: Some(rdata),
+            },
+        })
+    }
+
     pub fn r_type(&self) -> &RRType {
         &self.r_type
     }
 
+    pub fn class(&self) -> &DNSClass {
+        &self.class
+    }
+
     pub fn ttl(&self) -> Duration {
         self.ttl
     }
 
+    pub fn name(&self) -> &DomainName {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: DomainName) {
+        self.name = name;
+    }
+
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
     pub fn parsed_data(&self) -> &Option<String> {
         &self.data.parsed
     }
 
-    pub fn decode<'a, T>(iter: &mut T, response: &mut T) -> Result<DNSRecord>
-    where
-        T: Iterator<Item = &'a u8> + Clone,
-    {
-        // construct domain name
-        let mut label_parser = LabelSequenceParser::new();
-        let name = label_parser.construct_domain_name(iter, Some(&mut response.clone()))?;
-
-        // get r_type and class
-        let parts = utils::u8_bytes_to_u16_vec(iter, 2)?;
-        if parts.len() < 2 {
-            return Err(DNSResolverError::Decode(
-                String::from("rr"),
-                String::from("failed to convert bytes"),
-            ));
-        }
-        let r_type: RRType = parts[0].try_into()?;
-        let class = parts[1];
-
-        // get ttl
-        let mut ttl_bytes: [u8; 4] = [0, 0, 0, 0];
-        for i in 0..ttl_bytes.len() {
-            ttl_bytes[i] = *iter.next().ok_or(DNSResolverError::Decode(
-                String::from("rr"),
-                String::from("could not parse bytes"),
-            ))?;
-        }
-        let ttl_u32 = u32::from_be_bytes(ttl_bytes);
-        let ttl = Duration::from_secs(u64::from(ttl_u32));
-
-        // get rd_len
-        let rd_len_bytes = utils::u8_bytes_to_u16_vec(iter, 1)?;
-        if rd_len_bytes.len() < 1 {
-            return Err(DNSResolverError::Decode(
-                String::from("rr"),
-                String::from("failed to convert bytes"),
-            ));
-        }
-        let rd_len = rd_len_bytes[0];
-        let rd_size = usize::try_from(rd_len).map_err(|e| map_decode_err("rr", &e))?;
+    pub fn rdata(&self) -> &Option<RData> {
+        &self.data.rdata
+    }
 
-        // get data in its raw format
-        let data = iter.take(rd_size).map(|x| *x).collect_vec();
+    // Returns the data of the record in its uncompressed wire format, which is empty for
+    // records that carry no data.
+    pub fn raw_data(&self) -> &[u8] {
+        &self.data.raw
+    }
 
-        let mut record = DNSRecord {
+    // Decode the resource record from its wire format into our representation. The data
+    // is parsed within the bounds of its length, and records whose data doesn't parse
+    // as their type are rejected. The data of types that we don't understand is carried
+    // as it was received (RFC 3597), and so is empty data, which updates use for any
+    // type to match or delete whole RRsets (RFC 2136 section 2.4).
+    pub fn decode(decoder: &mut Decoder) -> Result<DNSRecord> {
+        let name = decoder.read_name("rr")?;
+        let r_type = RRType::from(decoder.read_u16("rr")?);
+        let class = DNSClass::from(decoder.read_u16("rr")?);
+        let ttl = Duration::from_secs(u64::from(decoder.read_u32("rr")?));
+        let rd_len = decoder.read_u16("rr")?;
+        let mut rdata_decoder = decoder.split(usize::from(rd_len), "rr")?;
+
+        // parse data into our typed representation and a human readable format. The raw
+        // data of parsed records is their uncompressed wire format, since the pointers in
+        // compressed names are only meaningful within the message they were received in.
+        let data = match RData::decode(&r_type, &mut rdata_decoder) {
+            Ok(rdata) => {
+                let mut raw = vec![];
+                rdata.encode(&mut raw)?;
+                Data {
+                    raw,
+                    parsed: Some(rdata.to_string()),
+                    rdata: Some(rdata),
+                }
+            }
+            Err(_) if rd_len == 0 => Data {
+                raw: vec![],
+                parsed: None,
+                rdata: None,
+            },
+            Err(e) => return Err(e),
+        };
+        Ok(DNSRecord {
             name,
             r_type,
             class,
             ttl,
-            rd_len,
-            data: Data {
-                raw: data,
-                parsed: None,
-            },
-        };
-
-        // parse data into a human readable format
-        record.data.parsed = record.parse_raw_data(response).ok();
-        Ok(record)
+            data,
+        })
     }
 
-    fn parse_raw_data<'a, T>(&self, response: &mut T) -> Result<String>
-    where
-        T: Iterator<Item = &'a u8> + Clone,
-    {
-        // if it's an A record, then each byte represents an octect, so just join them
-        // with a '.' as a separator.
-        if self.r_type == RRType::A {
-            let data = self.data.raw.iter().map(|x| x.to_string()).join(".");
-            return Ok(data);
-        // if it's a TXT record, then the bytes are utf8 bytes so convert them into a string.
-        } else if self.r_type == RRType::TXT {
-            let data = String::from_utf8_lossy(&self.data.raw[1..]).to_string();
-            return Ok(data);
-        // if it's a NS or CNAME record, then the bytes are label sequences which may or may
-        // not be compressed.
-        } else if self.r_type == RRType::NS || self.r_type == RRType::CNAME {
-            let raw = &self.data.raw;
-            let pp = raw[raw.len() - 2];
-            let name: DomainName;
-            let mut label_parser = LabelSequenceParser::new();
-            if pp.leading_ones() == 2 {
-                let val = response.map(|x| *x).collect_vec();
-                name =
-                    label_parser.construct_domain_name(&mut raw.iter(), Some(&mut val.iter()))?;
-            } else {
-                name = label_parser.construct_domain_name(&mut raw.iter(), None)?;
-            }
-            return Ok(name.0);
-        } else {
-            return Err(DNSResolverError::Parse);
-        }
-    }
+    // Encode the resource record into the provided vector in its wire format, which has
+    // to be the message being built, since names are compressed against it.
+    pub fn encode(&self, encoded: &mut Vec<u8>, compressor: &mut NameCompressor) -> Result<()> {
+        self.name.encode_with_compression(encoded, compressor)?;
 
-    // Encode the resource record into the provided vector in its wire format.
-    pub fn encode(&self, encoded: &mut Vec<u8>) -> Result<()> {
-        // encode the domain name and use compression if possible.
-        self.name.encode_with_compression(encoded)?;
-
-        let rr_type = self.r_type.clone() as u16;
+        let rr_type = self.r_type.code();
         encoded.extend(rr_type.to_be_bytes());
-        encoded.extend(self.class.to_be_bytes());
+        encoded.extend(self.class.code().to_be_bytes());
 
         // encode ttl. the drain gets us the last 4 bytes converting
         // u64 to u32.
@@ -153,8 +152,38 @@ impl DNSRecord {
             .collect_vec();
         encoded.extend(ttl);
 
-        encoded.extend(self.rd_len.to_be_bytes());
-        encoded.extend(&self.data.raw);
+        // the length is only known once the data has been encoded. The data is encoded
+        // again rather than copied as received, so that its names can be compressed
+        // against this message.
+        let rd_len_offset = encoded.len();
+        encoded.extend([0, 0]);
+        match &self.data.rdata {
+            Some(rdata) => rdata.encode_with_compression(encoded, compressor)?,
+            None => encoded.extend(&self.data.raw),
+        }
+        let rd_len = u16::try_from(encoded.len() - rd_len_offset - 2)
+            .map_err(|e| map_encode_err("rr", &e))?;
+        encoded[rd_len_offset..rd_len_offset + 2].copy_from_slice(&rd_len.to_be_bytes());
         Ok(())
     }
 }
+
+impl Display for DNSRecord {
+    // Renders the record in its presentation format, i.e. <name> <ttl> <class> <type> <data>.
+    // Records without data are rendered with the empty generic format (RFC 3597).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let data = match &self.data.parsed {
+            Some(parsed) => parsed.clone(),
+            None => "\\# 0".to_string(),
+        };
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.name,
+            self.ttl.as_secs(),
+            self.class,
+            self.r_type,
+            data
+        )
+    }
+}
@@ -1,26 +1,61 @@
+use std::fmt::Display;
 use std::str::FromStr;
 
 use crate::error::{DNSResolverError, Result};
-use strum_macros::{AsRefStr, EnumString};
 
-// Creates the provided enum and generates a TryFrom trait implementation
-// to convert a u16 to its equivalent variant.
+// Creates the provided enum along with an Unknown(u16) variant for all the values
+// that aren't listed. It also generates the conversions from and to a u16 and
+// the conversions from and to the presentation format, where unlisted values
+// are represented by the provided prefix followed by the value, e.g. TYPE<value>
+// (RFC 3597). Strings that can't be converted are reported using the provided error.
 macro_rules! rr_type {
-    ($(#[$meta:meta])* $vis:vis enum $name:ident {
-        $($variant:ident$( = $val:literal)?),*
+    ($prefix:literal, $err:path, $(#[$meta:meta])* $vis:vis enum $name:ident {
+        $($variant:ident = $val:literal),*
     ,}) => {
         $(#[$meta])*
         $vis enum $name {
-            $($variant$( = $val)?),*
+            $($variant,)*
+            Unknown(u16),
         }
 
-        impl TryFrom<u16> for $name {
-            type Error = DNSResolverError;
+        impl From<u16> for $name {
+            fn from(v: u16) -> Self {
+                match v {
+                    $($val => $name::$variant,)*
+                    _ => $name::Unknown(v),
+                }
+            }
+        }
 
-            fn try_from(v: u16) -> Result<Self> {
+        impl From<&$name> for u16 {
+            fn from(v: &$name) -> u16 {
                 match v {
-                    $(x if x == $name::$variant as u16 => Ok($name::$variant),)*
-                    _ => Err(DNSResolverError::InvalidRecordType(v.to_string())),
+                    $($name::$variant => $val,)*
+                    $name::Unknown(v) => *v,
+                }
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $($name::$variant => f.write_str(stringify!($variant)),)*
+                    $name::Unknown(v) => write!(f, concat!($prefix, "{}"), v),
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = DNSResolverError;
+
+            fn from_str(s: &str) -> Result<Self> {
+                $(if s.eq_ignore_ascii_case(stringify!($variant)) {
+                    return Ok($name::$variant);
+                })*
+                let upper = s.to_ascii_uppercase();
+                match upper.strip_prefix($prefix).map(u16::from_str) {
+                    Some(Ok(v)) => Ok($name::from(v)),
+                    _ => Err($err(s.to_owned())),
                 }
             }
         }
@@ -28,29 +63,75 @@ macro_rules! rr_type {
 }
 
 rr_type!(
-    #[derive(Debug, EnumString, Clone, PartialEq, AsRefStr, Eq, Hash)]
+    "TYPE",
+    DNSResolverError::InvalidRecordType,
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
     pub enum RRType {
         A = 1,
-        NS,
-        MD,
-        MF,
-        CNAME,
-        SOA,
-        MB,
-        MG,
-        MR,
-        NULl,
-        WKS,
-        PTR,
-        HINFO,
-        MINFO,
-        MX,
-        TXT,
+        NS = 2,
+        MD = 3,
+        MF = 4,
+        CNAME = 5,
+        SOA = 6,
+        MB = 7,
+        MG = 8,
+        MR = 9,
+        NULL = 10,
+        WKS = 11,
+        PTR = 12,
+        HINFO = 13,
+        MINFO = 14,
+        MX = 15,
+        TXT = 16,
         AAAA = 28,
+        SRV = 33,
+        NAPTR = 35,
+        OPT = 41,
+        DS = 43,
+        SSHFP = 44,
+        RRSIG = 46,
+        NSEC = 47,
+        DNSKEY = 48,
+        NSEC3 = 50,
+        NSEC3PARAM = 51,
+        TLSA = 52,
+        SVCB = 64,
+        HTTPS = 65,
+        IXFR = 251,
+        AXFR = 252,
+        URI = 256,
+        CAA = 257,
+    }
+);
+
+rr_type!(
+    "CLASS",
+    DNSResolverError::InvalidClass,
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub enum DNSClass {
+        IN = 1,
+        CH = 3,
+        HS = 4,
+        NONE = 254,
+        ANY = 255,
     }
 );
 
+impl RRType {
+    // Returns the value of the record type in its wire format.
+    pub fn code(&self) -> u16 {
+        u16::from(self)
+    }
+}
+
+impl DNSClass {
+    // Returns the value of the class in its wire format.
+    pub fn code(&self) -> u16 {
+        u16::from(self)
+    }
+}
+
 // Converts the provided str to a record type.
 pub fn str_to_record_type(val: &str) -> Result<RRType> {
-    RRType::from_str(val).map_err(|_| DNSResolverError::InvalidRecordType(val.to_owned()))
+    RRType::from_str(val)
 }
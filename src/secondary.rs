@@ -0,0 +1,156 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::time::{timeout, Instant};
+
+use crate::domain_name::DomainName;
+use crate::error::{DNSResolverError, Result};
+use crate::transfer;
+use crate::zone::{serial_gt, Zone};
+
+// The time that a transfer from the primary is given before it's abandoned.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(30);
+
+// The time to wait before retrying a zone that hasn't been transferred yet, which has no
+// SOA record to take the retry interval from.
+const DEFAULT_RETRY: Duration = Duration::from_secs(60);
+
+// The shortest refresh and retry intervals that are honoured, which stops a zone with
+// tiny intervals in its SOA record from having its primary checked all the time.
+const MIN_REFRESH: Duration = Duration::from_secs(300);
+const MIN_RETRY: Duration = Duration::from_secs(60);
+
+// Secondary keeps a copy of a zone up to date with its primary (RFC 1034 section 4.3.5).
+// The zone is checked for changes every refresh interval of its SOA record, or every
+// retry interval after a failed check, and stops being served once it hasn't been checked
+// successfully for the expire interval. A NOTIFY from the primary (RFC 1996) triggers a
+// check right away.
+#[derive(Debug, Clone)]
+pub struct Secondary {
+    origin: DomainName,
+    primary: SocketAddr,
+    // When the zone is next checked for changes.
+    refresh_at: Instant,
+    // When the zone expires, if it's being served.
+    expires_at: Option<Instant>,
+    // The time to wait before retrying a failed check.
+    retry: Duration,
+    // Whether a check is under way, in which case no other check is started.
+    refreshing: bool,
+    // Whether a NOTIFY arrived during the check that's under way, which calls for
+    // another check once it's done.
+    notified: bool,
+}
+
+impl Secondary {
+    // Creates a secondary for the zone with the provided origin, which is transferred
+    // from the primary right away.
+    pub fn new(origin: DomainName, primary: SocketAddr) -> Secondary {
+        Secondary {
+            origin,
+            primary,
+            refresh_at: Instant::now(),
+            expires_at: None,
+            retry: DEFAULT_RETRY,
+            refreshing: false,
+            notified: false,
+        }
+    }
+
+    pub fn origin(&self) -> &DomainName {
+        &self.origin
+    }
+
+    pub fn primary(&self) -> SocketAddr {
+        self.primary
+    }
+
+    // Returns when the zone next needs to be maintained, i.e. checked for changes or
+    // expired, if it does. A zone that's being checked only needs to be expired.
+    pub fn deadline(&self) -> Option<Instant> {
+        let refresh_at = (!self.refreshing).then_some(self.refresh_at);
+        refresh_at.into_iter().chain(self.expires_at).min()
+    }
+
+    // Schedules a check for changes right away, as a NOTIFY from the primary asks for.
+    pub fn notify(&mut self) {
+        self.refresh_at = Instant::now();
+        self.notified = self.refreshing;
+    }
+
+    // Returns true if the zone has expired, in which case it has to stop being served
+    // until it's transferred again.
+    pub fn expire(&mut self) -> bool {
+        let now = Instant::now();
+        if self.expires_at.is_none_or(|expires_at| expires_at > now) {
+            return false;
+        }
+        self.expires_at = None;
+        self.retry = DEFAULT_RETRY;
+        // the zone is transferred again as soon as the primary is back.
+        self.refresh_at = now;
+        true
+    }
+
+    // Returns true if a check for changes is due, in which case the check is considered
+    // under way until it's finished.
+    pub fn start_refresh(&mut self) -> bool {
+        if self.refreshing || self.refresh_at > Instant::now() {
+            return false;
+        }
+        self.refreshing = true;
+        self.notified = false;
+        true
+    }
+
+    // Checks the zone for changes, given the version of it that's served if there is one,
+    // and returns the version that's to be served from now on. The serial of the
+    // primary's version is queried first, and the zone is only transferred if that's
+    // newer, using IXFR once there's a version of it. This takes a while, so it's done
+    // without holding on to the served zones.
+    pub async fn refresh(&self, current: Option<Zone>) -> Result<Zone> {
+        let primary = self.primary.to_string();
+        let timed_out = |what: &str| {
+            DNSResolverError::TransferFailure(
+                self.origin.to_string(),
+                format!("{} timed out", what),
+            )
+        };
+        if let Some(current) = current {
+            let serial = transfer::serial(&primary, &self.origin).await?;
+            if !serial_gt(serial, current.serial()) {
+                return Ok(current);
+            }
+            let zone = timeout(TRANSFER_TIMEOUT, transfer::ixfr(&primary, &current))
+                .await
+                .map_err(|_| timed_out("the transfer"))??;
+            // the primary may have gone back to an older version in the meantime.
+            if !serial_gt(zone.serial(), current.serial()) {
+                return Ok(current);
+            }
+            return Ok(zone);
+        }
+        timeout(TRANSFER_TIMEOUT, transfer::axfr(&primary, &self.origin))
+            .await
+            .map_err(|_| timed_out("the transfer"))?
+    }
+
+    // Finishes the check that start_refresh started, given the version of the zone that's
+    // served from now on if it succeeded, and schedules the next one.
+    pub fn finish_refresh(&mut self, refreshed: Option<&Zone>) {
+        let now = Instant::now();
+        self.refreshing = false;
+        self.refresh_at = match refreshed {
+            Some(zone) => {
+                let soa = zone.soa_rdata();
+                self.retry = Duration::from_secs(u64::from(soa.retry())).max(MIN_RETRY);
+                self.expires_at = Some(now + Duration::from_secs(u64::from(soa.expire())));
+                now + Duration::from_secs(u64::from(soa.refresh())).max(MIN_REFRESH)
+            }
+            None => now + self.retry,
+        };
+        if self.notified {
+            self.refresh_at = now;
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::io::ErrorKind;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::error::{map_encode_err, DNSResolverError, Result};
+
+// Reads a message sent over TCP, where every message is preceded by its length as two
+// octets (RFC 1035 section 4.2.2). Returns None if the connection is closed before the
+// next message.
+pub async fn read_message(stream: &mut (impl AsyncRead + Unpin)) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; 2];
+    match stream.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => {
+            return Err(DNSResolverError::IOFailure(
+                String::from("receive"),
+                e.to_string(),
+            ))
+        }
+    }
+    let mut message = vec![0; usize::from(u16::from_be_bytes(len))];
+    stream
+        .read_exact(&mut message)
+        .await
+        .map_err(|e| DNSResolverError::IOFailure(String::from("receive"), e.to_string()))?;
+    Ok(Some(message))
+}
+
+// Writes a message over TCP, preceded by its length.
+pub async fn write_message(stream: &mut (impl AsyncWrite + Unpin), message: &[u8]) -> Result<()> {
+    let len = u16::try_from(message.len()).map_err(|e| map_encode_err("message", &e))?;
+    let mut framed = len.to_be_bytes().to_vec();
+    framed.extend(message);
+    stream
+        .write_all(&framed)
+        .await
+        .map_err(|e| DNSResolverError::IOFailure(String::from("send"), e.to_string()))
+}
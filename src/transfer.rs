@@ -0,0 +1,272 @@
+use std::iter;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::timeout;
+
+use crate::domain_name::{DomainName, NameCompressor};
+use crate::error::{DNSResolverError, Result};
+use crate::journal::Journal;
+use crate::message::{DNSMessage, MessageBuilder};
+use crate::query::{self, DNSQuestion, ResponseCode};
+use crate::rdata::RData;
+use crate::resource_record::DNSRecord;
+use crate::rr_types::{DNSClass, RRType};
+use crate::tcp;
+use crate::zone::{serial_gt, Zone};
+
+// The size that the messages of an outgoing zone transfer are kept under, which is well
+// below the 65535 octets that a message can have over TCP.
+const MAX_TRANSFER_MESSAGE_SIZE: usize = 16384;
+
+// The time that the server is given to answer a query for the serial of a zone.
+const SERIAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Pulls the zone with the provided origin from the server using AXFR (RFC 5936).
+pub async fn axfr(server: &str, origin: &DomainName) -> Result<Zone> {
+    let question = DNSQuestion::new(origin.clone(), RRType::AXFR, DNSClass::IN);
+    let mut records = transfer(server, question, None).await?;
+    // the zone ends with its SOA record again.
+    records.pop();
+    Zone::new(origin.clone(), records)
+}
+
+// Brings the zone up to date with the server using IXFR (RFC 1995), which transfers the
+// changes since the serial of the zone, or the whole zone if the server can't provide
+// them. The zone is returned as it is if it's up to date.
+pub async fn ixfr(server: &str, zone: &Zone) -> Result<Zone> {
+    let question = DNSQuestion::new(zone.origin().clone(), RRType::IXFR, zone.class().clone());
+    let records = transfer(server, question, Some(zone.soa().clone())).await?;
+    apply_ixfr(zone, records)
+}
+
+// Asks the server for the serial of its version of the zone with the provided origin,
+// which is how a secondary finds out whether it's up to date before it transfers the zone
+// (RFC 1996 section 3.11). The query is sent over UDP, where the SOA record always fits.
+pub async fn serial(server: &str, origin: &DomainName) -> Result<u32> {
+    let failed =
+        |reason: &str| DNSResolverError::TransferFailure(origin.to_string(), String::from(reason));
+    let local = match server.parse::<SocketAddr>() {
+        Ok(SocketAddr::V6(_)) => "[::]:0",
+        _ => "0.0.0.0:0",
+    };
+    let socket = UdpSocket::bind(local)
+        .await
+        .map_err(|e| DNSResolverError::ConnectionFailure(server.to_owned(), e.to_string()))?;
+    socket
+        .connect(server)
+        .await
+        .map_err(|e| DNSResolverError::ConnectionFailure(server.to_owned(), e.to_string()))?;
+    let id = rand::random();
+    let query = query::build_query(id, origin.to_string(), RRType::SOA, DNSClass::IN, None)?;
+    socket
+        .send(&query)
+        .await
+        .map_err(|e| DNSResolverError::IOFailure(String::from("send"), e.to_string()))?;
+
+    // replies with another ID are dropped, like the resolver does, for as long as the
+    // server is given to answer.
+    let mut buf = [0; 512];
+    let receive = async {
+        loop {
+            let no = socket
+                .recv(&mut buf)
+                .await
+                .map_err(|e| DNSResolverError::IOFailure(String::from("receive"), e.to_string()))?;
+            if buf[..no].starts_with(&id.to_be_bytes()) {
+                return DNSMessage::decode(&buf[..no]);
+            }
+        }
+    };
+    let message = timeout(SERIAL_TIMEOUT, receive)
+        .await
+        .map_err(|_| failed("the query for the serial timed out"))??;
+    if message.header().response_code() != ResponseCode::NoError {
+        return Err(failed(&format!(
+            "the server responded with {}",
+            message.header().response_code()
+        )));
+    }
+    message
+        .answers()
+        .iter()
+        .filter(|record| record.name() == origin)
+        .find_map(soa_serial)
+        .ok_or_else(|| failed("the response has no SOA record"))
+}
+
+// Returns the messages of an outgoing AXFR of the zone in response to the query: the
+// SOA record, all the other records and the SOA record again, spread over as many
+// messages as needed.
+pub fn axfr_responses(zone: &Zone, query: &DNSMessage) -> Result<Vec<DNSMessage>> {
+    let soa = zone.soa();
+    let others = zone
+        .records()
+        .filter(|record| record.r_type() != &RRType::SOA);
+    transfer_responses(query, iter::once(soa).chain(others).chain(iter::once(soa)))
+}
+
+// Returns the messages of an outgoing IXFR of the zone in response to the query, whose
+// authority section carries the SOA record of the client's version of the zone. The
+// response is the SOA record alone if the client is up to date, and otherwise the
+// changes since the client's version as recorded in the journal of the zone. The whole
+// zone is sent like AXFR if those changes aren't at hand, as RFC 1995 section 4 allows.
+pub fn ixfr_responses(
+    zone: &Zone,
+    query: &DNSMessage,
+    journal: Option<&Journal>,
+) -> Result<Vec<DNSMessage>> {
+    let Some(client_serial) = query.authorities().iter().find_map(soa_serial) else {
+        return axfr_responses(zone, query);
+    };
+    if !serial_gt(zone.serial(), client_serial) {
+        return Ok(vec![transfer_response(query, vec![zone.soa().clone()])?]);
+    }
+    let changes = match journal {
+        Some(journal) => journal.changes_since(client_serial)?,
+        None => None,
+    };
+    // the changes have to lead up to the version of the zone that's served.
+    let last_serial = changes
+        .as_ref()
+        .and_then(|changes| changes.iter().rev().find_map(soa_serial));
+    match changes {
+        Some(changes) if last_serial == Some(zone.serial()) => {
+            let soa = zone.soa();
+            transfer_responses(
+                query,
+                iter::once(soa).chain(&changes).chain(iter::once(soa)),
+            )
+        }
+        _ => axfr_responses(zone, query),
+    }
+}
+
+// Spreads the records of an outgoing transfer over as many messages as needed.
+fn transfer_responses<'a>(
+    query: &DNSMessage,
+    transferred: impl Iterator<Item = &'a DNSRecord>,
+) -> Result<Vec<DNSMessage>> {
+    let mut messages = vec![];
+    let mut records = vec![];
+    let mut size = 0;
+    for record in transferred {
+        let mut encoded = vec![];
+        record.encode(&mut encoded, &mut NameCompressor::new())?;
+        if !records.is_empty() && size + encoded.len() > MAX_TRANSFER_MESSAGE_SIZE {
+            messages.push(transfer_response(query, std::mem::take(&mut records))?);
+            size = 0;
+        }
+        size += encoded.len();
+        records.push(record.clone());
+    }
+    messages.push(transfer_response(query, records)?);
+    Ok(messages)
+}
+
+fn transfer_response(query: &DNSMessage, records: Vec<DNSRecord>) -> Result<DNSMessage> {
+    DNSMessage::response_to(query)
+        .authoritative(true)
+        .answers(records)
+        .build()
+}
+
+// Sends the transfer query to the server over TCP and collects the records of all the
+// messages of the response, which starts and ends with the SOA record of the zone. The
+// SOA record of the client's version of the zone is sent along for IXFR.
+async fn transfer(
+    server: &str,
+    question: DNSQuestion,
+    soa: Option<DNSRecord>,
+) -> Result<Vec<DNSRecord>> {
+    let failed = |reason: &str| {
+        DNSResolverError::TransferFailure(question.name().to_string(), String::from(reason))
+    };
+    let client_serial = soa.as_ref().and_then(soa_serial);
+    let mut stream = TcpStream::connect(server)
+        .await
+        .map_err(|e| DNSResolverError::ConnectionFailure(server.to_owned(), e.to_string()))?;
+    let id = rand::random();
+    let query = MessageBuilder::query()
+        .id(id)
+        .recursion_desired(false)
+        .question(question.clone())
+        .authorities(soa)
+        .build()?;
+    tcp::write_message(&mut stream, &query.encode()?).await?;
+
+    let mut records = vec![];
+    loop {
+        let message = tcp::read_message(&mut stream)
+            .await?
+            .ok_or_else(|| failed("the connection was closed before the end of the zone"))?;
+        let message = DNSMessage::decode(&message)?;
+        if message.header().id() != id {
+            return Err(failed("the response has a different ID than the query"));
+        }
+        if message.header().response_code() != ResponseCode::NoError {
+            return Err(failed(&format!(
+                "the server responded with {}",
+                message.header().response_code()
+            )));
+        }
+        records.extend(message.into_parts().2);
+        match records.first().and_then(soa_serial) {
+            Some(_) if is_complete(&records, client_serial) => return Ok(records),
+            Some(_) => {}
+            None if records.is_empty() => {}
+            None => return Err(failed("the zone doesn't start with a SOA record")),
+        }
+    }
+}
+
+// Returns true if the records make up a whole transfer, which ends with the SOA record
+// that it starts with. For IXFR, where the serial of the client's version of the zone
+// is provided, a single SOA record that isn't newer than that version means that the
+// client is up to date. Otherwise the transfer goes on, even if its first message
+// carries the SOA record alone, and the SOA records in between the first and the last
+// one alternate between the old and the new version of each change (RFC 1995
+// section 4).
+fn is_complete(records: &[DNSRecord], client_serial: Option<u32>) -> bool {
+    let serial = records.first().and_then(soa_serial);
+    if let ([_], Some(serial), Some(client_serial)) = (records, serial, client_serial) {
+        return !serial_gt(serial, client_serial);
+    }
+    if records.len() < 2 || records.last().and_then(soa_serial) != serial {
+        return false;
+    }
+    if client_serial.is_none() || soa_serial(&records[1]).is_none() {
+        return true;
+    }
+    // the last SOA record ends the transfer if it's where the old version of a change
+    // would otherwise start.
+    records[1..]
+        .iter()
+        .filter(|record| soa_serial(record).is_some())
+        .count()
+        % 2
+        == 1
+}
+
+// Applies the records of an IXFR response to the zone, which are either the changes to
+// the zone or the whole zone in the format of AXFR.
+fn apply_ixfr(zone: &Zone, records: Vec<DNSRecord>) -> Result<Zone> {
+    let origin = zone.origin().clone();
+    if records.len() == 1 {
+        return Ok(zone.clone());
+    }
+    let body = &records[1..records.len() - 1];
+    if body.first().and_then(soa_serial).is_none() {
+        return Zone::new(origin, records[..records.len() - 1].to_vec());
+    }
+    zone.apply(body)
+}
+
+// Returns the serial of the record if it's a SOA record.
+pub(crate) fn soa_serial(record: &DNSRecord) -> Option<u32> {
+    match record.rdata() {
+        Some(RData::SOA(soa)) if record.r_type() == &RRType::SOA => Some(soa.serial()),
+        _ => None,
+    }
+}
@@ -0,0 +1,312 @@
+use itertools::Itertools;
+
+use crate::domain_name::DomainName;
+use crate::message::DNSMessage;
+use crate::query::ResponseCode;
+use crate::rdata::RData;
+use crate::resource_record::DNSRecord;
+use crate::rr_types::{DNSClass, RRType};
+use crate::zone::{same_record, serial_gt, Zone, ANY_TYPE};
+
+// The value of the OPT record type, which is a meta type like the ones from 128 onwards.
+const OPT_TYPE: u16 = 41;
+
+// Change is a change to a zone in the format of IXFR (RFC 1995 section 4): the deleted
+// records, starting with the SOA record of the old version of the zone, and the added
+// records, starting with the SOA record of the new version.
+#[derive(Debug, Clone)]
+pub struct Change {
+    deleted: Vec<DNSRecord>,
+    added: Vec<DNSRecord>,
+}
+
+impl Change {
+    pub fn deleted(&self) -> &[DNSRecord] {
+        &self.deleted
+    }
+
+    pub fn added(&self) -> &[DNSRecord] {
+        &self.added
+    }
+
+    // Returns the records of the change in the order of IXFR, which Zone::apply takes.
+    pub fn records(&self) -> impl Iterator<Item = &DNSRecord> {
+        self.deleted.iter().chain(&self.added)
+    }
+}
+
+// Works out the change that the dynamic update (RFC 2136) makes to the zone, which the
+// update has to be for. The prerequisites in the answer section have to hold for the
+// updates in the authority section to be made, which are made all together or not at
+// all; the RCODE to respond with is returned otherwise. Returns None if the update
+// doesn't change the zone, and otherwise increments the serial of the zone unless the
+// update sets a newer SOA record itself.
+pub fn update(zone: &Zone, message: &DNSMessage) -> Result<Option<Change>, ResponseCode> {
+    check_prerequisites(zone, message.answers())?;
+    prescan(zone, message.authorities())?;
+
+    let mut editor = Editor::new(zone);
+    for update in message.authorities() {
+        editor.update(zone, update);
+    }
+    if editor.deleted.is_empty() && editor.added.is_empty() {
+        return Ok(None);
+    }
+    if !editor.added.iter().any(is_soa) {
+        let soa = zone.soa();
+        let mut rdata = zone.soa_rdata().clone();
+        rdata.set_serial(rdata.serial().wrapping_add(1));
+        let soa = DNSRecord::new(
+            soa.name().clone(),
+            RRType::SOA,
+            soa.class().clone(),
+            soa.ttl(),
+            RData::SOA(rdata),
+        )
+        .map_err(|_| ResponseCode::ServFail)?;
+        editor.delete(is_soa);
+        editor.add(soa);
+    }
+
+    // the SOA records go first.
+    let (mut deleted, others): (Vec<_>, Vec<_>) = editor.deleted.into_iter().partition(is_soa);
+    deleted.extend(others);
+    let (mut added, others): (Vec<_>, Vec<_>) = editor.added.into_iter().partition(is_soa);
+    added.extend(others);
+    Ok(Some(Change { deleted, added }))
+}
+
+// Checks the prerequisites of an update against the zone (RFC 2136 section 3.2). Records
+// of the ANY class require that a name or an RRset exists, records of the NONE class
+// require that it doesn't, and records of the class of the zone require that the RRset
+// exists with exactly the data of those records.
+fn check_prerequisites(zone: &Zone, prerequisites: &[DNSRecord]) -> Result<(), ResponseCode> {
+    let mut rrsets = vec![];
+    for prerequisite in prerequisites {
+        if !prerequisite.ttl().is_zero() {
+            return Err(ResponseCode::FormErr);
+        }
+        if !prerequisite.name().is_subdomain_of(zone.origin()) {
+            return Err(ResponseCode::NotZone);
+        }
+        let records = zone.records_at(prerequisite.name());
+        let any_type = prerequisite.r_type().code() == ANY_TYPE;
+        let rrset_exists = records
+            .iter()
+            .any(|record| record.r_type() == prerequisite.r_type());
+        match prerequisite.class() {
+            DNSClass::ANY | DNSClass::NONE if !prerequisite.raw_data().is_empty() => {
+                return Err(ResponseCode::FormErr)
+            }
+            DNSClass::ANY if any_type && records.is_empty() => return Err(ResponseCode::NXDomain),
+            DNSClass::ANY if !any_type && !rrset_exists => return Err(ResponseCode::NXRRSet),
+            DNSClass::NONE if any_type && !records.is_empty() => {
+                return Err(ResponseCode::YXDomain)
+            }
+            DNSClass::NONE if !any_type && rrset_exists => return Err(ResponseCode::YXRRSet),
+            DNSClass::ANY | DNSClass::NONE => {}
+            class if class == zone.class() && !any_type => rrsets.push(prerequisite),
+            _ => return Err(ResponseCode::FormErr),
+        }
+    }
+
+    let rrsets = rrsets
+        .into_iter()
+        .into_group_map_by(|record| (record.name().clone(), record.r_type().clone()));
+    for ((name, r_type), expected) in rrsets {
+        let rrset = zone
+            .records_at(&name)
+            .iter()
+            .filter(|record| record.r_type() == &r_type)
+            .collect_vec();
+        let same = rrset
+            .iter()
+            .all(|record| expected.iter().any(|other| same_record(record, other)))
+            && expected
+                .iter()
+                .all(|record| rrset.iter().any(|other| same_record(record, other)));
+        if !same {
+            return Err(ResponseCode::NXRRSet);
+        }
+    }
+    Ok(())
+}
+
+// Checks that the updates are well-formed before any of them is made (RFC 2136 section
+// 3.4.1). Records of the class of the zone are added, records of the ANY class delete an
+// RRset or all the RRsets of a name, and records of the NONE class delete a record.
+fn prescan(zone: &Zone, updates: &[DNSRecord]) -> Result<(), ResponseCode> {
+    for update in updates {
+        if !update.name().is_subdomain_of(zone.origin()) {
+            return Err(ResponseCode::NotZone);
+        }
+        let meta = is_meta(update.r_type());
+        let valid = match update.class() {
+            class if class == zone.class() => !meta,
+            DNSClass::ANY => {
+                update.ttl().is_zero()
+                    && update.raw_data().is_empty()
+                    && (!meta || update.r_type().code() == ANY_TYPE)
+            }
+            DNSClass::NONE => update.ttl().is_zero() && !meta,
+            _ => false,
+        };
+        if !valid {
+            return Err(ResponseCode::FormErr);
+        }
+    }
+    Ok(())
+}
+
+// Returns true if the type is a meta type, i.e. one that records can't be stored with.
+fn is_meta(r_type: &RRType) -> bool {
+    matches!(r_type.code(), OPT_TYPE | 128..=255)
+}
+
+fn is_soa(record: &DNSRecord) -> bool {
+    record.r_type() == &RRType::SOA
+}
+
+// Editor makes the updates to the records of a zone while keeping track of the records
+// that have been deleted and added by them.
+struct Editor {
+    records: Vec<DNSRecord>,
+    deleted: Vec<DNSRecord>,
+    added: Vec<DNSRecord>,
+}
+
+impl Editor {
+    fn new(zone: &Zone) -> Editor {
+        Editor {
+            records: zone.records().cloned().collect_vec(),
+            deleted: vec![],
+            added: vec![],
+        }
+    }
+
+    // Makes a single update to the records (RFC 2136 section 3.4.2). The SOA record and
+    // the NS records at the origin are never deleted all together, and records that
+    // would give a name a CNAME record alongside other records are ignored.
+    fn update(&mut self, zone: &Zone, update: &DNSRecord) {
+        let name = update.name();
+        let at_origin = name == zone.origin();
+        match update.class() {
+            DNSClass::ANY => {
+                let any_type = update.r_type().code() == ANY_TYPE;
+                self.delete(|record| {
+                    record.name() == name
+                        && (any_type || record.r_type() == update.r_type())
+                        && !(at_origin && matches!(record.r_type(), RRType::SOA | RRType::NS))
+                });
+            }
+            DNSClass::NONE => {
+                let last_ns = at_origin
+                    && update.r_type() == &RRType::NS
+                    && self.count(name, &RRType::NS) == 1;
+                if update.r_type() == &RRType::SOA || last_ns {
+                    return;
+                }
+                self.delete(|record| same_record(record, update));
+            }
+            _ => {
+                match update.r_type() {
+                    RRType::SOA => {
+                        let newer = match update.rdata() {
+                            Some(RData::SOA(soa)) => serial_gt(soa.serial(), self.serial()),
+                            _ => false,
+                        };
+                        if !at_origin || !newer {
+                            return;
+                        }
+                        self.delete(is_soa);
+                    }
+                    RRType::CNAME => {
+                        let others = self.records.iter().any(|record| {
+                            record.name() == name
+                                && !matches!(
+                                    record.r_type(),
+                                    RRType::CNAME | RRType::RRSIG | RRType::NSEC
+                                )
+                        });
+                        if others {
+                            return;
+                        }
+                        // a name has a single CNAME record, which is replaced.
+                        self.delete(|record| {
+                            record.name() == name && record.r_type() == &RRType::CNAME
+                        });
+                    }
+                    RRType::RRSIG | RRType::NSEC => {}
+                    _ if self.count(name, &RRType::CNAME) > 0 => return,
+                    _ => {}
+                }
+                // records with the same data are replaced, which only changes the TTL.
+                let duplicate = self
+                    .records
+                    .iter()
+                    .find(|record| same_record(record, update));
+                if duplicate.is_some_and(|duplicate| duplicate.ttl() == update.ttl()) {
+                    return;
+                }
+                self.delete(|record| same_record(record, update));
+                self.add(update.clone());
+            }
+        }
+    }
+
+    // Returns the current serial of the zone.
+    fn serial(&self) -> u32 {
+        self.records
+            .iter()
+            .find_map(|record| match record.rdata() {
+                Some(RData::SOA(soa)) if is_soa(record) => Some(soa.serial()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    // Returns the number of records of the type at the name.
+    fn count(&self, name: &DomainName, r_type: &RRType) -> usize {
+        self.records
+            .iter()
+            .filter(|record| record.name() == name && record.r_type() == r_type)
+            .count()
+    }
+
+    // Deletes the matching records. Records that were added by an earlier update are
+    // simply taken back.
+    fn delete(&mut self, matching: impl Fn(&DNSRecord) -> bool) {
+        let (deleted, kept) = std::mem::take(&mut self.records)
+            .into_iter()
+            .partition(|record| matching(record));
+        self.records = kept;
+        for record in deleted {
+            let added = self
+                .added
+                .iter()
+                .position(|other| same_record(other, &record) && other.ttl() == record.ttl());
+            match added {
+                Some(position) => {
+                    self.added.remove(position);
+                }
+                None => self.deleted.push(record),
+            }
+        }
+    }
+
+    // Adds the record. Records that were deleted by an earlier update are simply taken
+    // back.
+    fn add(&mut self, record: DNSRecord) {
+        let deleted = self
+            .deleted
+            .iter()
+            .position(|other| same_record(other, &record) && other.ttl() == record.ttl());
+        match deleted {
+            Some(position) => {
+                self.deleted.remove(position);
+            }
+            None => self.added.push(record.clone()),
+        }
+        self.records.push(record);
+    }
+}
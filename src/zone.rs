@@ -0,0 +1,415 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::path::Path;
+use std::time::Duration;
+
+use itertools::Itertools;
+
+use crate::domain_name::DomainName;
+use crate::error::{DNSResolverError, Result};
+use crate::message::{DNSMessage, MessageBuilder};
+use crate::query::{DNSQuestion, ResponseCode};
+use crate::rdata::{RData, SOA};
+use crate::resource_record::DNSRecord;
+use crate::rr_types::{DNSClass, RRType};
+use crate::zone_file;
+
+// The maximum number of CNAME records followed within a zone while answering a question.
+const MAX_CNAME_CHAIN: usize = 8;
+
+// The value of the ANY (*) question type, which matches records of every type.
+pub(crate) const ANY_TYPE: u16 = 255;
+
+// Zone is a zone that is served authoritatively: the records of every name at or below
+// its origin, including the NS records at the zone cuts where subzones are delegated
+// along with the glue needed to reach their nameservers.
+#[derive(Debug, Clone)]
+pub struct Zone {
+    origin: DomainName,
+    class: DNSClass,
+    // The records of the zone by their owner, kept in the canonical order, which puts
+    // the descendants of a name right after it.
+    names: BTreeMap<DomainName, Vec<DNSRecord>>,
+}
+
+// Lookup is the outcome of looking up the records of a type at a name within a zone
+// (RFC 1034 section 4.3.2).
+#[derive(Debug, Clone)]
+pub enum Lookup {
+    // The records of the type at the name, which are synthesised from a wildcard if the
+    // name doesn't exist (RFC 4592).
+    Records(Vec<DNSRecord>),
+    // The name is an alias; the CNAME record of the name.
+    Alias(DNSRecord),
+    // The name is within a subzone that is delegated; the NS records at the zone cut
+    // along with the addresses of the nameservers that the zone knows about.
+    Delegation(Vec<DNSRecord>, Vec<DNSRecord>),
+    // The name exists but has no records of the type.
+    NoData,
+    // The name doesn't exist.
+    NXDomain,
+}
+
+impl Zone {
+    // Creates a zone out of its records, which have to include a single SOA record at the
+    // origin and be of the same class. All the records have to be at or below the origin,
+    // and names with a CNAME record can't have any other records apart from DNSSEC ones.
+    pub fn new(origin: DomainName, records: Vec<DNSRecord>) -> Result<Zone> {
+        let invalid =
+            |reason: String| Err(DNSResolverError::InvalidZone(origin.to_string(), reason));
+        let mut names: BTreeMap<DomainName, Vec<DNSRecord>> = BTreeMap::new();
+        for record in records {
+            if !record.name().is_subdomain_of(&origin) {
+                return invalid(format!("{} is outside of the zone", record.name()));
+            }
+            names.entry(record.name().clone()).or_default().push(record);
+        }
+
+        let soas = names
+            .get(&origin)
+            .map(|records| {
+                records
+                    .iter()
+                    .filter(|record| record.r_type() == &RRType::SOA)
+                    .collect_vec()
+            })
+            .unwrap_or_default();
+        let [soa] = soas[..] else {
+            return invalid(String::from(
+                "zones need a single SOA record at their origin",
+            ));
+        };
+        if !matches!(soa.rdata(), Some(RData::SOA(_))) {
+            return invalid(String::from("the SOA record can't be parsed"));
+        }
+        let class = soa.class().clone();
+        for (name, records) in &names {
+            if records.iter().any(|record| record.class() != &class) {
+                return invalid(format!("{} has records of another class", name));
+            }
+            let alias = records
+                .iter()
+                .any(|record| record.r_type() == &RRType::CNAME);
+            let others = records.iter().any(|record| {
+                !matches!(
+                    record.r_type(),
+                    RRType::CNAME | RRType::RRSIG | RRType::NSEC
+                )
+            });
+            if alias && others {
+                return invalid(format!("{} has other records besides a CNAME record", name));
+            }
+        }
+        Ok(Zone {
+            origin,
+            class,
+            names,
+        })
+    }
+
+    // Reads a zone from a master file; see zone_file::read_zone.
+    pub fn read(path: &Path, origin: DomainName) -> Result<Zone> {
+        let records = zone_file::read_zone(path, &origin)?;
+        Zone::new(origin, records)
+    }
+
+    pub fn origin(&self) -> &DomainName {
+        &self.origin
+    }
+
+    pub fn class(&self) -> &DNSClass {
+        &self.class
+    }
+
+    // Returns the SOA record at the origin of the zone.
+    pub fn soa(&self) -> &DNSRecord {
+        self.names[&self.origin]
+            .iter()
+            .find(|record| record.r_type() == &RRType::SOA)
+            .expect("zones have a SOA record")
+    }
+
+    // Returns the data of the SOA record at the origin of the zone.
+    pub fn soa_rdata(&self) -> &SOA {
+        match self.soa().rdata() {
+            Some(RData::SOA(soa)) => soa,
+            _ => unreachable!("zones have a parsed SOA record"),
+        }
+    }
+
+    // Returns the serial of the zone, i.e. the version of the zone.
+    pub fn serial(&self) -> u32 {
+        self.soa_rdata().serial()
+    }
+
+    // Returns all the records of the zone in the canonical order of their owners, which
+    // starts with the records at the origin.
+    pub fn records(&self) -> impl Iterator<Item = &DNSRecord> {
+        self.names.values().flatten()
+    }
+
+    // Returns the records that the name owns, without following wildcards or delegations.
+    pub fn records_at(&self, name: &DomainName) -> &[DNSRecord] {
+        self.names.get(name).map_or(&[], |records| &records[..])
+    }
+
+    // Returns a new version of the zone with the changes applied, which are in the format
+    // of IXFR (RFC 1995 section 4): every change is the old SOA record followed by the
+    // deleted records, and the new SOA record followed by the added records.
+    pub fn apply<'a>(&self, changes: impl IntoIterator<Item = &'a DNSRecord>) -> Result<Zone> {
+        let mut current = self.records().cloned().collect_vec();
+        let mut deleting = false;
+        for record in changes {
+            if record.r_type() == &RRType::SOA {
+                deleting = !deleting;
+            }
+            if !deleting {
+                current.push(record.clone());
+                continue;
+            }
+            let Some(position) = current.iter().position(|other| same_record(other, record)) else {
+                return Err(DNSResolverError::InvalidZone(
+                    self.origin.to_string(),
+                    format!("{} is deleted but isn't in the zone", record),
+                ));
+            };
+            current.remove(position);
+        }
+        Zone::new(self.origin.clone(), current)
+    }
+
+    // Looks up the records of the provided type at the name, which has to be within the
+    // zone. The ANY type matches records of every type.
+    pub fn lookup(&self, name: &DomainName, r_type: &RRType) -> Lookup {
+        if let Some(delegation) = self.delegation(name, r_type) {
+            return delegation;
+        }
+        if let Some(records) = self.names.get(name) {
+            return select(records, name, r_type);
+        }
+        // names without records of their own exist if there are names below them.
+        if self.exists(name) {
+            return Lookup::NoData;
+        }
+        // the name doesn't exist, but a wildcard at the closest ancestor that does exist
+        // stands in for it (RFC 4592 section 3.3.1).
+        let closest_encloser = std::iter::successors(name.parent(), |name| name.parent())
+            .find(|ancestor| self.exists(ancestor))
+            .unwrap_or_else(|| self.origin.clone());
+        match self.names.get(&closest_encloser.child(b"*")) {
+            Some(records) => select(records, name, r_type),
+            None => Lookup::NXDomain,
+        }
+    }
+
+    // Returns a builder for an authoritative response to the question, which has to be
+    // about a name within the zone. CNAME records are followed as long as they point
+    // within the zone, and the addresses of the names that the answers refer to are added
+    // to the additional section if the zone has them.
+    pub fn answer(&self, query: &DNSMessage, question: &DNSQuestion) -> MessageBuilder {
+        let mut response = DNSMessage::response_to(query).authoritative(true);
+        let mut answers: Vec<DNSRecord> = vec![];
+        let mut name = question.name().clone();
+        loop {
+            match self.lookup(&name, question.q_type()) {
+                Lookup::Records(records) => answers.extend(records),
+                Lookup::Alias(cname) => {
+                    let target = match cname.rdata() {
+                        Some(RData::CNAME(target)) => target.clone(),
+                        _ => DomainName::root(),
+                    };
+                    answers.push(cname);
+                    let seen = answers.iter().any(|answer| answer.name() == &target);
+                    if target.is_subdomain_of(&self.origin)
+                        && !seen
+                        && answers.len() < MAX_CNAME_CHAIN
+                    {
+                        name = target;
+                        continue;
+                    }
+                }
+                Lookup::Delegation(ns, glue) => {
+                    // referrals aren't authoritative, unless they follow an alias that is.
+                    response = response
+                        .authoritative(!answers.is_empty())
+                        .authorities(ns)
+                        .additionals(glue);
+                }
+                Lookup::NoData => response = response.authority(self.negative_soa()),
+                Lookup::NXDomain => {
+                    response = response
+                        .response_code(ResponseCode::NXDomain)
+                        .authority(self.negative_soa())
+                }
+            }
+            break;
+        }
+        let additionals = self.additional_addresses(&answers);
+        response.answers(answers).additionals(additionals)
+    }
+
+    // Returns the NS records at the zone cut that the name is at or below along with their
+    // glue, if any. The DS records at a zone cut belong to the parent side of the cut.
+    fn delegation(&self, name: &DomainName, r_type: &RRType) -> Option<Lookup> {
+        let depth = self.origin.labels().len();
+        for n in depth + 1..=name.labels().len() {
+            let cut = name.suffix(n);
+            if cut == *name && r_type == &RRType::DS {
+                break;
+            }
+            let ns = self
+                .names
+                .get(&cut)
+                .into_iter()
+                .flatten()
+                .filter(|record| record.r_type() == &RRType::NS)
+                .cloned()
+                .collect_vec();
+            if ns.is_empty() {
+                continue;
+            }
+            let glue = ns
+                .iter()
+                .filter_map(|record| match record.rdata() {
+                    Some(RData::NS(target)) => Some(self.addresses(target)),
+                    _ => None,
+                })
+                .flatten()
+                .collect_vec();
+            return Some(Lookup::Delegation(ns, glue));
+        }
+        None
+    }
+
+    // Returns true if the name has records or if there are names below it that do.
+    fn exists(&self, name: &DomainName) -> bool {
+        self.names.contains_key(name)
+            || self
+                .names
+                .range((Bound::Excluded(name), Bound::Unbounded))
+                .next()
+                .is_some_and(|(next, _)| next.is_subdomain_of(name))
+    }
+
+    // Returns the address records of the name, if it's within the zone.
+    fn addresses(&self, name: &DomainName) -> Vec<DNSRecord> {
+        self.names
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|record| matches!(record.r_type(), RRType::A | RRType::AAAA))
+            .cloned()
+            .collect_vec()
+    }
+
+    // Returns the addresses of the nameservers, mail exchanges and service targets that
+    // the answers refer to, which the zone is authoritative for and that aren't part of
+    // the answers already.
+    fn additional_addresses(&self, answers: &[DNSRecord]) -> Vec<DNSRecord> {
+        answers
+            .iter()
+            .filter_map(|answer| match answer.rdata() {
+                Some(RData::NS(target)) => Some(target),
+                Some(RData::MX(mx)) => Some(mx.exchange()),
+                Some(RData::SRV(srv)) => Some(srv.target()),
+                _ => None,
+            })
+            .unique()
+            .filter(|target| self.delegation(target, &RRType::A).is_none())
+            .filter(|target| answers.iter().all(|answer| answer.name() != *target))
+            .flat_map(|target| self.addresses(target))
+            .collect_vec()
+    }
+
+    // Returns the SOA record that goes into the authority section of negative answers,
+    // whose TTL is how long the answer can be cached (RFC 2308 section 3).
+    fn negative_soa(&self) -> DNSRecord {
+        let mut soa = self.soa().clone();
+        if let Some(RData::SOA(data)) = soa.rdata() {
+            let ttl = soa
+                .ttl()
+                .min(Duration::from_secs(u64::from(data.minimum())));
+            soa.set_ttl(ttl);
+        }
+        soa
+    }
+}
+
+// Selects the records of the provided type among the records at a name, renaming them
+// to the name in case they belong to a wildcard.
+fn select(records: &[DNSRecord], name: &DomainName, r_type: &RRType) -> Lookup {
+    let renamed = |record: &DNSRecord| {
+        let mut record = record.clone();
+        record.set_name(name.clone());
+        record
+    };
+    let matching = records
+        .iter()
+        .filter(|record| record.r_type() == r_type || r_type.code() == ANY_TYPE)
+        .map(renamed)
+        .collect_vec();
+    if !matching.is_empty() {
+        return Lookup::Records(matching);
+    }
+    match records
+        .iter()
+        .find(|record| record.r_type() == &RRType::CNAME)
+    {
+        Some(cname) => Lookup::Alias(renamed(cname)),
+        None => Lookup::NoData,
+    }
+}
+
+// Returns true if the records have the same owner, type and data, regardless of their
+// class and TTL.
+pub(crate) fn same_record(a: &DNSRecord, b: &DNSRecord) -> bool {
+    let same_data = match (a.rdata(), b.rdata()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.raw_data() == b.raw_data(),
+    };
+    a.name() == b.name() && a.r_type() == b.r_type() && same_data
+}
+
+// Returns true if serial a is greater than serial b, where serials wrap around as per
+// serial number arithmetic (RFC 1982).
+pub fn serial_gt(a: u32, b: u32) -> bool {
+    a != b && (a.wrapping_sub(b) as i32) > 0
+}
+
+// Zones is the set of zones that are served authoritatively, by their origin.
+#[derive(Debug, Default)]
+pub struct Zones {
+    zones: HashMap<DomainName, Zone>,
+}
+
+impl Zones {
+    pub fn new() -> Zones {
+        Zones {
+            zones: HashMap::new(),
+        }
+    }
+
+    // Adds the zone, replacing any zone with the same origin.
+    pub fn insert(&mut self, zone: Zone) {
+        self.zones.insert(zone.origin().clone(), zone);
+    }
+
+    pub fn get(&self, origin: &DomainName) -> Option<&Zone> {
+        self.zones.get(origin)
+    }
+
+    pub fn remove(&mut self, origin: &DomainName) -> Option<Zone> {
+        self.zones.remove(origin)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.zones.is_empty()
+    }
+
+    // Returns the zone that the name belongs to, i.e. the zone with the longest origin
+    // that the name is at or below.
+    pub fn find(&self, name: &DomainName) -> Option<&Zone> {
+        std::iter::successors(Some(name.clone()), |name| name.parent())
+            .find_map(|ancestor| self.zones.get(&ancestor))
+    }
+}
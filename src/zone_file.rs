@@ -0,0 +1,824 @@
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use data_encoding::{BASE32HEX_NOPAD, BASE64, HEXUPPER};
+
+use crate::decoder::Decoder;
+use crate::domain_name::DomainName;
+use crate::error::{DNSResolverError, Result};
+use crate::rdata::{
+    RData, SvcParam, CAA, DNSKEY, DS, HINFO, MINFO, MX, NAPTR, NSEC, NSEC3, NSEC3PARAM, RRSIG, SOA,
+    SRV, SSHFP, SVCB, TLSA, URI,
+};
+use crate::resource_record::DNSRecord;
+use crate::rr_types::{DNSClass, RRType};
+
+// The maximum depth of nested $INCLUDE directives, which stops files that include
+// themselves from being included forever.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// Parses the records in a master file (RFC 1035 section 5) provided as a string. Relative
+// names are relative to the provided origin until a $ORIGIN directive changes it. Files
+// named by $INCLUDE directives are looked up relative to the current directory.
+pub fn parse_zone(input: &str, origin: &DomainName) -> Result<Vec<DNSRecord>> {
+    let mut parser = ZoneParser::new(origin.clone());
+    parser.parse(input, "input", None, 0)?;
+    Ok(parser.records)
+}
+
+// Reads the records in the master file at the provided path like parse_zone does, except
+// that files named by $INCLUDE directives are looked up relative to the directory of the
+// file that includes them.
+pub fn read_zone(path: &Path, origin: &DomainName) -> Result<Vec<DNSRecord>> {
+    let input = fs::read_to_string(path).map_err(|e| {
+        DNSResolverError::IOFailure(String::from("read"), format!("{}: {}", path.display(), e))
+    })?;
+    let mut parser = ZoneParser::new(origin.clone());
+    parser.parse(&input, &path.display().to_string(), path.parent(), 0)?;
+    Ok(parser.records)
+}
+
+// ZoneParser keeps track of the state that the entries of a master file inherit from
+// the entries before them.
+struct ZoneParser {
+    // The name that relative names are relative to.
+    origin: DomainName,
+    // The TTL of records without one, as set by the $TTL directive (RFC 2308 section 4).
+    default_ttl: Option<u32>,
+    // The owner, TTL and class of the previous record, which are used for records
+    // that omit them.
+    last_owner: Option<DomainName>,
+    last_ttl: Option<u32>,
+    last_class: DNSClass,
+    records: Vec<DNSRecord>,
+}
+
+// Token is a single field of an entry. Quoted fields are kept without their quotes, while
+// escapes are kept as they are, since names and character strings interpret them
+// differently.
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+// Entry is a single entry of a master file, which spans several lines if its fields are
+// within parentheses.
+struct Entry {
+    line: usize,
+    // Whether the entry starts with a blank, in which case the owner is omitted and the
+    // owner of the previous record is used.
+    blank_owner: bool,
+    tokens: Vec<Token>,
+}
+
+impl ZoneParser {
+    fn new(origin: DomainName) -> ZoneParser {
+        ZoneParser {
+            origin,
+            default_ttl: None,
+            last_owner: None,
+            last_ttl: None,
+            last_class: DNSClass::IN,
+            records: vec![],
+        }
+    }
+
+    // Parses the entries of a master file, where source names the file in errors and dir
+    // is the directory that included files are looked up in.
+    fn parse(&mut self, input: &str, source: &str, dir: Option<&Path>, depth: usize) -> Result<()> {
+        let invalid = |line: usize, reason: String| {
+            DNSResolverError::ZoneFile(source.to_owned(), line, reason)
+        };
+        let entries = tokenize(input).map_err(|(line, reason)| invalid(line, reason))?;
+        for entry in entries {
+            let first = &entry.tokens[0];
+            if !entry.blank_owner && !first.quoted && first.text.eq_ignore_ascii_case("$INCLUDE") {
+                self.include(&entry, dir, depth)
+                    .map_err(|reason| invalid(entry.line, reason))??;
+            } else {
+                self.parse_entry(&entry)
+                    .map_err(|reason| invalid(entry.line, reason))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Parses the file named by a $INCLUDE directive, optionally with a different origin.
+    // The origin of the including file is restored afterwards (RFC 1035 section 5.1).
+    // The outer result reports problems with the directive itself, while the inner one
+    // carries the errors within the included file.
+    fn include(
+        &mut self,
+        entry: &Entry,
+        dir: Option<&Path>,
+        depth: usize,
+    ) -> std::result::Result<Result<()>, String> {
+        let mut fields = Fields::new(&entry.tokens[1..], &self.origin);
+        let file = &fields.next("file name")?.text;
+        let origin = match fields.peek() {
+            Some(_) => fields.name("origin")?,
+            None => self.origin.clone(),
+        };
+        fields.finish()?;
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(String::from("too many nested $INCLUDE directives"));
+        }
+
+        let path = match dir {
+            Some(dir) => dir.join(file),
+            None => PathBuf::from(file),
+        };
+        let input = fs::read_to_string(&path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        let parent_origin = std::mem::replace(&mut self.origin, origin);
+        let result = self.parse(
+            &input,
+            &path.display().to_string(),
+            path.parent(),
+            depth + 1,
+        );
+        self.origin = parent_origin;
+        Ok(result)
+    }
+
+    // Parses an entry, which is either a $ORIGIN or $TTL directive or a resource record
+    // in the format <owner> [<TTL>] [<class>] <type> <data>, where the TTL and the class
+    // may come in either order.
+    fn parse_entry(&mut self, entry: &Entry) -> std::result::Result<(), String> {
+        let origin = self.origin.clone();
+        let mut fields = Fields::new(&entry.tokens, &origin);
+        let first = &entry.tokens[0];
+        if !entry.blank_owner && !first.quoted && first.text.starts_with('$') {
+            fields.next("directive")?;
+            match first.text.to_ascii_uppercase().as_str() {
+                "$ORIGIN" => {
+                    let origin = fields.name("origin")?;
+                    fields.finish()?;
+                    self.origin = origin;
+                }
+                "$TTL" => {
+                    let ttl = parse_ttl(&fields.next("TTL")?.text)?;
+                    fields.finish()?;
+                    self.default_ttl = Some(ttl);
+                }
+                _ => return Err(format!("unknown directive {}", first.text)),
+            }
+            return Ok(());
+        }
+
+        let owner = if entry.blank_owner {
+            self.last_owner
+                .clone()
+                .ok_or_else(|| String::from("the first record needs an owner"))?
+        } else {
+            fields.name("owner")?
+        };
+        let mut ttl = None;
+        let mut class = None;
+        let r_type = loop {
+            let token = fields.next("record type")?;
+            if ttl.is_none() && token.text.starts_with(|c: char| c.is_ascii_digit()) {
+                ttl = Some(parse_ttl(&token.text)?);
+                continue;
+            }
+            if class.is_none() {
+                if let Ok(parsed) = token.text.parse::<DNSClass>() {
+                    class = Some(parsed);
+                    continue;
+                }
+            }
+            break token
+                .text
+                .parse::<RRType>()
+                .map_err(|_| format!("invalid record type {}", token.text))?;
+        };
+        let rdata = parse_rdata(&r_type, &mut fields)?;
+        fields.finish()?;
+
+        // records without a TTL take the one set by $TTL or else the one of the previous
+        // record. Failing both, a SOA record takes its minimum TTL, like BIND does.
+        let ttl = match (ttl.or(self.default_ttl).or(self.last_ttl), &rdata) {
+            (Some(ttl), _) => ttl,
+            (None, RData::SOA(soa)) => soa.minimum(),
+            (None, _) => return Err(String::from("no TTL given and no $TTL directive")),
+        };
+        let class = class.unwrap_or_else(|| self.last_class.clone());
+        let record = DNSRecord::new(
+            owner.clone(),
+            r_type,
+            class.clone(),
+            Duration::from_secs(u64::from(ttl)),
+            rdata,
+        )
+        .map_err(describe)?;
+
+        self.last_owner = Some(owner);
+        self.last_ttl = Some(ttl);
+        self.last_class = class;
+        self.records.push(record);
+        Ok(())
+    }
+}
+
+// Splits a master file into its entries. Entries end at the end of a line unless the line
+// is within parentheses, and comments run from a semicolon to the end of the line. Errors
+// carry the line they occurred on along with the reason.
+fn tokenize(input: &str) -> std::result::Result<Vec<Entry>, (usize, String)> {
+    let mut entries = vec![];
+    let mut entry = Entry {
+        line: 1,
+        blank_owner: false,
+        tokens: vec![],
+    };
+    let mut token: Option<String> = None;
+    let mut line = 1;
+    let mut depth = 0;
+    let mut line_start = true;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if line_start && depth == 0 {
+            entry.line = line;
+            entry.blank_owner = c == ' ' || c == '\t';
+        }
+        line_start = false;
+        if matches!(c, '\n' | ' ' | '\t' | '\r' | ';' | '(' | ')' | '"') {
+            if let Some(text) = token.take() {
+                entry.tokens.push(Token {
+                    text,
+                    quoted: false,
+                });
+            }
+        }
+        match c {
+            '\n' => {
+                line += 1;
+                line_start = true;
+                if depth == 0 && !entry.tokens.is_empty() {
+                    entries.push(Entry {
+                        line: entry.line,
+                        blank_owner: entry.blank_owner,
+                        tokens: std::mem::take(&mut entry.tokens),
+                    });
+                }
+            }
+            ' ' | '\t' | '\r' => {}
+            ';' => while chars.next_if(|c| *c != '\n').is_some() {},
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err((line, String::from("unbalanced parentheses"))),
+            ')' => depth -= 1,
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        None | Some('\n') => {
+                            return Err((line, String::from("unterminated quoted string")))
+                        }
+                        Some('"') => break,
+                        Some('\\') => {
+                            text.push('\\');
+                            text.extend(chars.next_if(|c| *c != '\n'));
+                        }
+                        Some(c) => text.push(c),
+                    }
+                }
+                entry.tokens.push(Token { text, quoted: true });
+            }
+            '\\' => {
+                let text = token.get_or_insert_with(String::new);
+                text.push('\\');
+                text.extend(chars.next_if(|c| *c != '\n'));
+            }
+            _ => token.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if depth > 0 {
+        return Err((line, String::from("unbalanced parentheses")));
+    }
+    if let Some(text) = token {
+        entry.tokens.push(Token {
+            text,
+            quoted: false,
+        });
+    }
+    if !entry.tokens.is_empty() {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// Fields is a cursor over the fields of an entry, which parses them into the values that
+// make up a record.
+struct Fields<'a> {
+    tokens: std::slice::Iter<'a, Token>,
+    origin: &'a DomainName,
+}
+
+impl<'a> Fields<'a> {
+    fn new(tokens: &'a [Token], origin: &'a DomainName) -> Fields<'a> {
+        Fields {
+            tokens: tokens.iter(),
+            origin,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.as_slice().first()
+    }
+
+    fn next(&mut self, what: &str) -> std::result::Result<&'a Token, String> {
+        self.tokens
+            .next()
+            .ok_or_else(|| format!("missing {}", what))
+    }
+
+    // Fails unless all the fields have been parsed.
+    fn finish(&self) -> std::result::Result<(), String> {
+        match self.peek() {
+            Some(token) => Err(format!("unexpected field {}", token.text)),
+            None => Ok(()),
+        }
+    }
+
+    fn number<T: FromStr>(&mut self, what: &str) -> std::result::Result<T, String> {
+        let token = self.next(what)?;
+        token
+            .text
+            .parse()
+            .map_err(|_| format!("invalid {} {}", what, token.text))
+    }
+
+    fn ttl(&mut self, what: &str) -> std::result::Result<u32, String> {
+        parse_ttl(&self.next(what)?.text)
+    }
+
+    fn name(&mut self, what: &str) -> std::result::Result<DomainName, String> {
+        parse_name(&self.next(what)?.text, self.origin)
+    }
+
+    fn string(&mut self, what: &str) -> std::result::Result<Vec<u8>, String> {
+        parse_character_string(&self.next(what)?.text)
+    }
+
+    fn hex(&mut self, what: &str) -> std::result::Result<Vec<u8>, String> {
+        let text = self.rest(what)?.to_ascii_uppercase();
+        HEXUPPER
+            .decode(text.as_bytes())
+            .map_err(|_| format!("invalid {} {}", what, text))
+    }
+
+    fn base64(&mut self, what: &str) -> std::result::Result<Vec<u8>, String> {
+        let text = self.rest(what)?;
+        BASE64
+            .decode(text.as_bytes())
+            .map_err(|_| format!("invalid {} {}", what, text))
+    }
+
+    // Returns all the remaining fields joined together, which is how hex and base64 data
+    // can be split into several fields.
+    fn rest(&mut self, what: &str) -> std::result::Result<String, String> {
+        let text = self
+            .tokens
+            .by_ref()
+            .map(|token| token.text.as_str())
+            .collect::<String>();
+        if text.is_empty() {
+            return Err(format!("missing {}", what));
+        }
+        Ok(text)
+    }
+
+    fn types(&mut self) -> std::result::Result<Vec<RRType>, String> {
+        self.tokens
+            .by_ref()
+            .map(|token| {
+                token
+                    .text
+                    .parse::<RRType>()
+                    .map_err(|_| format!("invalid record type {}", token.text))
+            })
+            .collect()
+    }
+}
+
+// Parses the data of a record of the provided type from its presentation format. The
+// data of any type can also be given in the generic format (RFC 3597 section 5), i.e.
+// \# <length> <hex data>, which is the only format for types that we don't understand.
+fn parse_rdata(r_type: &RRType, fields: &mut Fields) -> std::result::Result<RData, String> {
+    if fields
+        .peek()
+        .is_some_and(|token| !token.quoted && token.text == "\\#")
+    {
+        fields.next("data")?;
+        let len = fields.number::<usize>("data length")?;
+        let data = if len == 0 {
+            vec![]
+        } else {
+            fields.hex("data")?
+        };
+        if data.len() != len {
+            return Err(format!(
+                "data length is {} but the data has {} octets",
+                len,
+                data.len()
+            ));
+        }
+        return RData::decode(r_type, &mut Decoder::new(&data)).map_err(describe);
+    }
+
+    let rdata = match r_type {
+        RRType::A => RData::A(fields.number::<Ipv4Addr>("address")?),
+        RRType::AAAA => RData::AAAA(fields.number::<Ipv6Addr>("address")?),
+        RRType::NS => RData::NS(fields.name("name")?),
+        RRType::MD => RData::MD(fields.name("name")?),
+        RRType::MF => RData::MF(fields.name("name")?),
+        RRType::CNAME => RData::CNAME(fields.name("name")?),
+        RRType::MB => RData::MB(fields.name("name")?),
+        RRType::MG => RData::MG(fields.name("name")?),
+        RRType::MR => RData::MR(fields.name("name")?),
+        RRType::PTR => RData::PTR(fields.name("name")?),
+        RRType::SOA => RData::SOA(SOA::new(
+            fields.name("mname")?,
+            fields.name("rname")?,
+            fields.number("serial")?,
+            fields.ttl("refresh")?,
+            fields.ttl("retry")?,
+            fields.ttl("expire")?,
+            fields.ttl("minimum")?,
+        )),
+        RRType::HINFO => RData::HINFO(HINFO::new(fields.string("CPU")?, fields.string("OS")?)),
+        RRType::MINFO => RData::MINFO(MINFO::new(fields.name("rmailbx")?, fields.name("emailbx")?)),
+        RRType::MX => RData::MX(MX::new(
+            fields.number("preference")?,
+            fields.name("exchange")?,
+        )),
+        RRType::TXT => {
+            let mut strings = vec![fields.string("text")?];
+            while fields.peek().is_some() {
+                strings.push(fields.string("text")?);
+            }
+            RData::TXT(strings)
+        }
+        RRType::SRV => RData::SRV(SRV::new(
+            fields.number("priority")?,
+            fields.number("weight")?,
+            fields.number("port")?,
+            fields.name("target")?,
+        )),
+        RRType::NAPTR => RData::NAPTR(NAPTR::new(
+            fields.number("order")?,
+            fields.number("preference")?,
+            fields.string("flags")?,
+            fields.string("services")?,
+            fields.string("regexp")?,
+            fields.name("replacement")?,
+        )),
+        RRType::DS => RData::DS(DS::new(
+            fields.number("key tag")?,
+            fields.number("algorithm")?,
+            fields.number("digest type")?,
+            fields.hex("digest")?,
+        )),
+        RRType::SSHFP => RData::SSHFP(SSHFP::new(
+            fields.number("algorithm")?,
+            fields.number("fingerprint type")?,
+            fields.hex("fingerprint")?,
+        )),
+        RRType::RRSIG => {
+            let type_covered = fields.next("type covered")?;
+            RData::RRSIG(RRSIG::new(
+                type_covered
+                    .text
+                    .parse()
+                    .map_err(|_| format!("invalid record type {}", type_covered.text))?,
+                fields.number("algorithm")?,
+                fields.number("labels")?,
+                fields.ttl("original TTL")?,
+                parse_timestamp(&fields.next("expiration")?.text)?,
+                parse_timestamp(&fields.next("inception")?.text)?,
+                fields.number("key tag")?,
+                fields.name("signer's name")?,
+                fields.base64("signature")?,
+            ))
+        }
+        RRType::NSEC => RData::NSEC(NSEC::new(fields.name("next domain name")?, fields.types()?)),
+        RRType::DNSKEY => RData::DNSKEY(DNSKEY::new(
+            fields.number("flags")?,
+            fields.number("protocol")?,
+            fields.number("algorithm")?,
+            fields.base64("public key")?,
+        )),
+        RRType::NSEC3 => {
+            let hash_algorithm = fields.number("hash algorithm")?;
+            let flags = fields.number("flags")?;
+            let iterations = fields.number("iterations")?;
+            let salt = parse_salt(&fields.next("salt")?.text)?;
+            let next = fields.next("next hashed owner name")?;
+            let next_hashed_owner_name = BASE32HEX_NOPAD
+                .decode(next.text.to_ascii_uppercase().as_bytes())
+                .map_err(|_| format!("invalid next hashed owner name {}", next.text))?;
+            RData::NSEC3(NSEC3::new(
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+                next_hashed_owner_name,
+                fields.types()?,
+            ))
+        }
+        RRType::NSEC3PARAM => RData::NSEC3PARAM(NSEC3PARAM::new(
+            fields.number("hash algorithm")?,
+            fields.number("flags")?,
+            fields.number("iterations")?,
+            parse_salt(&fields.next("salt")?.text)?,
+        )),
+        RRType::TLSA => RData::TLSA(TLSA::new(
+            fields.number("usage")?,
+            fields.number("selector")?,
+            fields.number("matching type")?,
+            fields.hex("certificate association data")?,
+        )),
+        RRType::SVCB | RRType::HTTPS => {
+            let svcb = SVCB::new(
+                fields.number("priority")?,
+                fields.name("target")?,
+                parse_svc_params(fields)?,
+            );
+            match r_type {
+                RRType::SVCB => RData::SVCB(svcb),
+                _ => RData::HTTPS(svcb),
+            }
+        }
+        RRType::URI => RData::URI(URI::new(
+            fields.number("priority")?,
+            fields.number("weight")?,
+            unescape(&fields.next("target")?.text)?,
+        )),
+        RRType::CAA => RData::CAA(CAA::new(
+            fields.number("flags")?,
+            fields.next("tag")?.text.as_bytes().to_vec(),
+            unescape(&fields.next("value")?.text)?,
+        )),
+        _ => {
+            return Err(format!(
+                "{} records can only be given in the generic format",
+                r_type
+            ))
+        }
+    };
+    Ok(rdata)
+}
+
+// Parses the SvcParams of a SVCB or HTTPS record (RFC 9460 section 2.1), which are given
+// as key=value pairs in any order but are kept ordered by their keys, as in their wire
+// format. The value may be quoted, in which case it's a separate field.
+fn parse_svc_params(fields: &mut Fields) -> std::result::Result<Vec<SvcParam>, String> {
+    let mut params = vec![];
+    while let Some(token) = fields.tokens.next() {
+        let (key, value) = match token.text.split_once('=') {
+            Some((key, "")) => match fields.peek() {
+                Some(next) if next.quoted => {
+                    fields.tokens.next();
+                    (key, Some(next.text.as_str()))
+                }
+                _ => (key, Some("")),
+            },
+            Some((key, value)) => (key, Some(value)),
+            None => (token.text.as_str(), None),
+        };
+        let invalid = || format!("invalid value for SvcParam {}", key);
+        let param = match (parse_svc_param_key(key)?, value) {
+            (2, None) => SvcParam::NoDefaultAlpn,
+            (_, None) | (2, Some(_)) => return Err(invalid()),
+            (0, Some(value)) => SvcParam::Mandatory(
+                value
+                    .split(',')
+                    .map(parse_svc_param_key)
+                    .collect::<std::result::Result<_, _>>()?,
+            ),
+            (1, Some(value)) => SvcParam::Alpn(
+                split_escaped(value)
+                    .iter()
+                    .map(|id| parse_character_string(id))
+                    .collect::<std::result::Result<_, _>>()?,
+            ),
+            (3, Some(value)) => SvcParam::Port(value.parse().map_err(|_| invalid())?),
+            (4, Some(value)) => SvcParam::Ipv4Hint(
+                value
+                    .split(',')
+                    .map(|ip| ip.parse().map_err(|_| invalid()))
+                    .collect::<std::result::Result<_, _>>()?,
+            ),
+            (5, Some(value)) => {
+                SvcParam::Ech(BASE64.decode(value.as_bytes()).map_err(|_| invalid())?)
+            }
+            (6, Some(value)) => SvcParam::Ipv6Hint(
+                value
+                    .split(',')
+                    .map(|ip| ip.parse().map_err(|_| invalid()))
+                    .collect::<std::result::Result<_, _>>()?,
+            ),
+            (key, Some(value)) => SvcParam::Unknown(key, unescape(value)?),
+        };
+        params.push(param);
+    }
+    params.sort_by_key(|param| param.key());
+    if params.windows(2).any(|pair| pair[0].key() == pair[1].key()) {
+        return Err(String::from("SvcParam keys can't be repeated"));
+    }
+    Ok(params)
+}
+
+// Parses the presentation format name of a SvcParam key, i.e. its name or key<number>.
+fn parse_svc_param_key(key: &str) -> std::result::Result<u16, String> {
+    match key {
+        "mandatory" => Ok(0),
+        "alpn" => Ok(1),
+        "no-default-alpn" => Ok(2),
+        "port" => Ok(3),
+        "ipv4hint" => Ok(4),
+        "ech" => Ok(5),
+        "ipv6hint" => Ok(6),
+        _ => key
+            .strip_prefix("key")
+            .and_then(|number| number.parse().ok())
+            .ok_or_else(|| format!("unknown SvcParam key {}", key)),
+    }
+}
+
+// Splits a comma separated list whose items may contain escaped commas, keeping the
+// escapes within the items.
+fn split_escaped(text: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ',' => items.push(String::new()),
+            '\\' => {
+                let item = items.last_mut().unwrap();
+                item.push('\\');
+                item.extend(chars.next());
+            }
+            _ => items.last_mut().unwrap().push(c),
+        }
+    }
+    items
+}
+
+// Parses a domain name, which is relative to the origin unless it ends with a dot. "@"
+// stands for the origin itself.
+fn parse_name(text: &str, origin: &DomainName) -> std::result::Result<DomainName, String> {
+    if text == "@" {
+        return Ok(origin.clone());
+    }
+    let name = text.parse::<DomainName>().map_err(describe)?;
+    // the name is absolute if its trailing dot isn't escaped, i.e. if it's preceded by an
+    // even number of backslashes.
+    let backslashes = text
+        .strip_suffix('.')
+        .map(|rest| rest.len() - rest.trim_end_matches('\\').len());
+    if backslashes.is_some_and(|n| n % 2 == 0) {
+        return Ok(name);
+    }
+    let name = DomainName::from_labels([name.labels(), origin.labels()].concat());
+    name.validate().map_err(describe)?;
+    Ok(name)
+}
+
+// Parses a <character-string>, which is at most 255 octets long.
+fn parse_character_string(text: &str) -> std::result::Result<Vec<u8>, String> {
+    let string = unescape(text)?;
+    if string.len() > 255 {
+        return Err(String::from(
+            "character strings can't be longer than 255 octets",
+        ));
+    }
+    Ok(string)
+}
+
+// Replaces the escapes in a field with the octets they stand for, i.e. \X with the
+// character X and \DDD with the octet with the decimal value DDD.
+fn unescape(text: &str) -> std::result::Result<Vec<u8>, String> {
+    let mut unescaped = vec![];
+    let mut bytes = text.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            unescaped.push(byte);
+            continue;
+        }
+        let escaped = bytes
+            .next()
+            .ok_or_else(|| String::from("incomplete escape"))?;
+        if !escaped.is_ascii_digit() {
+            unescaped.push(escaped);
+            continue;
+        }
+        let mut value = u16::from(escaped - b'0');
+        for _ in 0..2 {
+            match bytes.next() {
+                Some(digit) if digit.is_ascii_digit() => {
+                    value = value * 10 + u16::from(digit - b'0')
+                }
+                _ => return Err(String::from("\\DDD escapes need three digits")),
+            }
+        }
+        let value = u8::try_from(value)
+            .map_err(|_| String::from("\\DDD escapes can't be larger than 255"))?;
+        unescaped.push(value);
+    }
+    Ok(unescaped)
+}
+
+// Parses a TTL, which is either a number of seconds or a sequence of numbers followed by
+// their units (s, m, h, d or w), such as 1h30m, as BIND allows.
+fn parse_ttl(text: &str) -> std::result::Result<u32, String> {
+    let invalid = || format!("invalid TTL {}", text);
+    if let Ok(ttl) = text.parse::<u32>() {
+        return Ok(ttl);
+    }
+    let mut ttl: u32 = 0;
+    let mut value: Option<u32> = None;
+    for c in text.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            let next = value
+                .unwrap_or(0)
+                .checked_mul(10)
+                .and_then(|v| v.checked_add(digit));
+            value = Some(next.ok_or_else(invalid)?);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let value = value.take().ok_or_else(invalid)?;
+        ttl = value
+            .checked_mul(unit)
+            .and_then(|secs| ttl.checked_add(secs))
+            .ok_or_else(invalid)?;
+    }
+    // every number needs a unit, unless the TTL is just a number.
+    if text.is_empty() || value.is_some() {
+        return Err(invalid());
+    }
+    Ok(ttl)
+}
+
+// Parses the salt of a NSEC3 or NSEC3PARAM record; hex or "-" if there's no salt.
+fn parse_salt(text: &str) -> std::result::Result<Vec<u8>, String> {
+    if text == "-" {
+        return Ok(vec![]);
+    }
+    HEXUPPER
+        .decode(text.to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("invalid salt {}", text))
+}
+
+// Parses a timestamp of a RRSIG record, which is either in the YYYYMMDDHHmmSS format or
+// the number of seconds since the UNIX epoch (RFC 4034 section 3.2).
+fn parse_timestamp(text: &str) -> std::result::Result<u32, String> {
+    let invalid = || format!("invalid timestamp {}", text);
+    if text.len() != 14 {
+        return text.parse().map_err(|_| invalid());
+    }
+    if !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let field = |start: usize, len: usize| text[start..start + len].parse::<i64>().unwrap();
+    let (year, month, day) = (field(0, 4), field(4, 2), field(6, 2));
+    let (hour, minute, second) = (field(8, 2), field(10, 2), field(12, 2));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return Err(invalid());
+    }
+    // convert the civil date into the days since the epoch; see
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_of_year = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_of_year + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    u32::try_from(days * 86400 + hour * 3600 + minute * 60 + second).map_err(|_| invalid())
+}
+
+// Describes an error in a field of an entry, which is reported along with the line of
+// the entry.
+fn describe(err: DNSResolverError) -> String {
+    match err {
+        DNSResolverError::InvalidName(name, reason) => format!("invalid name {}: {}", name, reason),
+        DNSResolverError::Decode(what, reason) | DNSResolverError::Encode(what, reason) => {
+            format!("invalid {}: {}", what, reason)
+        }
+        _ => err.to_string(),
+    }
+}
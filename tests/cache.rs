@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use dns_resolver::cache::DNSCache;
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::rdata::RData;
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::{DNSClass, RRType};
+
+fn txt(name: &DomainName, class: DNSClass, data: &str) -> DNSRecord {
+    let rdata = RData::TXT(vec![data.as_bytes().to_vec()]);
+    DNSRecord::new(
+        name.clone(),
+        RRType::TXT,
+        class,
+        Duration::from_secs(300),
+        rdata,
+    )
+    .unwrap()
+}
+
+#[test]
+fn keeps_the_answers_of_each_class_apart() {
+    let mut cache = DNSCache::new(10);
+    let name = DomainName::new(String::from("version.bind."));
+    cache.insert(
+        &name,
+        &RRType::TXT,
+        &DNSClass::IN,
+        vec![txt(&name, DNSClass::IN, "internet")],
+        false,
+    );
+    assert!(cache.get(&name, &RRType::TXT, &DNSClass::CH).is_none());
+
+    cache.insert(
+        &name,
+        &RRType::TXT,
+        &DNSClass::CH,
+        vec![txt(&name, DNSClass::CH, "chaos")],
+        false,
+    );
+    for (class, data) in [(DNSClass::IN, "internet"), (DNSClass::CH, "chaos")] {
+        let answer = cache.get(&name, &RRType::TXT, &class).unwrap().data();
+        assert_eq!(answer[0].class(), &class);
+        assert_eq!(
+            answer[0].parsed_data().as_deref(),
+            Some(format!("\"{}\"", data).as_str())
+        );
+    }
+}
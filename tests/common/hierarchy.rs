@@ -0,0 +1,127 @@
+// A hierarchy of signed zones for the tests of DNSSEC validation: the root zone signed
+// using RSASHA256, example. signed using ECDSAP256SHA256 with NSEC records and test.
+// signed using ED25519 with Opt-Out NSEC3 records. Each of them delegates to an unsigned
+// zone, and the address of bogus.example. doesn't match its signature.
+
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::rdata::{RData, DS};
+use dns_resolver::zone::Zone;
+use itertools::Itertools;
+
+use super::signer::{record, Algorithm, Denial, Key, Signer};
+use super::{zone, Nameserver};
+
+const EXAMPLE: &str = "
+$TTL 3600
+@           SOA     ns.example. hostmaster.example. 1 7200 900 1209600 300
+            NS      ns
+ns          A       127.0.0.1
+www         A       192.0.2.1
+bogus       A       192.0.2.2
+*.wild      TXT     \"synthesised\"
+insecure    NS      ns.insecure
+ns.insecure A       127.0.0.1
+";
+
+const INSECURE_EXAMPLE: &str = "
+$TTL 3600
+@           SOA     ns.insecure.example. hostmaster.example. 1 7200 900 1209600 300
+            NS      ns
+ns          A       127.0.0.1
+www         A       192.0.2.3
+";
+
+const TEST: &str = "
+$TTL 3600
+@           SOA     ns.test. hostmaster.test. 1 7200 900 1209600 300
+            NS      ns
+ns          A       127.0.0.1
+www         A       192.0.2.4
+*.wild      TXT     \"synthesised\"
+unsigned    NS      ns.unsigned
+ns.unsigned A       127.0.0.1
+";
+
+const UNSIGNED_TEST: &str = "
+$TTL 3600
+@           SOA     ns.unsigned.test. hostmaster.test. 1 7200 900 1209600 300
+            NS      ns
+ns          A       127.0.0.1
+www         A       192.0.2.5
+";
+
+pub struct Hierarchy {
+    root: Signer,
+    example: Signer,
+    test: Signer,
+}
+
+impl Hierarchy {
+    pub fn new() -> Hierarchy {
+        Hierarchy {
+            root: Signer::new(Key::generate(Algorithm::RsaSha256), Denial::Nsec),
+            example: Signer::new(Key::generate(Algorithm::EcdsaP256Sha256), Denial::Nsec),
+            test: Signer::new(
+                Key::generate(Algorithm::Ed25519),
+                Denial::Nsec3 { opt_out: true },
+            ),
+        }
+    }
+
+    // Returns the DS record of the key of the root zone.
+    pub fn trust_anchor(&self) -> DS {
+        self.root.key().ds(&DomainName::root())
+    }
+
+    pub fn example_mut(&mut self) -> &mut Signer {
+        &mut self.example
+    }
+
+    // Returns a nameserver for all the zones of the hierarchy.
+    pub fn nameserver(&self) -> Nameserver {
+        let example = DomainName::new(String::from("example."));
+        let test = DomainName::new(String::from("test."));
+        let root = format!(
+            "$TTL 86400
+.               SOA     ns.root. hostmaster.root. 1 1800 900 604800 86400
+.               NS      ns.root.
+ns.root.        A       127.0.0.1
+example.        NS      ns.example.
+example.        DS      {}
+ns.example.     A       127.0.0.1
+test.           NS      ns.test.
+test.           DS      {}
+ns.test.        A       127.0.0.1
+",
+            self.example.key().ds(&example),
+            self.test.key().ds(&test),
+        );
+
+        let signed_example = self.example.sign(&zone("example.", EXAMPLE));
+        // the address of bogus.example. is changed after it has been signed.
+        let bogus = DomainName::new(String::from("bogus.example."));
+        let records = signed_example
+            .records()
+            .map(|rr| match rr.rdata() {
+                Some(RData::A(_)) if rr.name() == &bogus => {
+                    record(rr.name(), rr.ttl(), RData::A("192.0.2.99".parse().unwrap()))
+                }
+                _ => rr.clone(),
+            })
+            .collect_vec();
+        let tampered_example = Zone::new(example, records).unwrap();
+
+        Nameserver::new()
+            .insert(self.root.sign(&zone(".", &root)))
+            .insert(tampered_example)
+            .zone("insecure.example.", INSECURE_EXAMPLE)
+            .insert(self.test.sign(&zone("test.", TEST)))
+            .zone("unsigned.test.", UNSIGNED_TEST)
+    }
+}
+
+impl Default for Hierarchy {
+    fn default() -> Hierarchy {
+        Hierarchy::new()
+    }
+}
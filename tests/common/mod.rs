@@ -0,0 +1,233 @@
+// Helpers shared by the integration tests, which run against a local nameserver rather
+// than the nameservers on the internet.
+#![allow(dead_code)]
+
+pub mod hierarchy;
+pub mod server;
+pub mod signer;
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use data_encoding::BASE32HEX_NOPAD;
+use dns_resolver::dnssec;
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::message::DNSMessage;
+use dns_resolver::query::ResponseCode;
+use dns_resolver::rdata::RData;
+use dns_resolver::resolver::Resolver;
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::RRType;
+use dns_resolver::zone::{Lookup, Zone, Zones};
+use dns_resolver::zone_file;
+use itertools::Itertools;
+use tokio::net::UdpSocket;
+
+// QueryLog is where a nameserver records the questions that it's asked.
+pub type QueryLog = Arc<Mutex<Vec<(DomainName, RRType)>>>;
+
+// Nameserver is a nameserver that answers authoritatively from the zones that it's given,
+// standing in for the root nameservers and every nameserver that they refer to. Questions
+// about names outside of its zones are refused. The answers from signed zones carry the
+// RRSIGs over their records, along with the NSEC or NSEC3 records that prove which names
+// don't exist.
+#[derive(Default)]
+pub struct Nameserver {
+    zones: Zones,
+    // The questions that are answered with an error instead, along with the error.
+    failures: Vec<(DomainName, RRType, ResponseCode)>,
+    // Where the questions that the nameserver is asked are recorded, if anywhere.
+    log: Option<QueryLog>,
+    // Whether the NSEC and NSEC3 records are left out of the answers.
+    without_proofs: bool,
+}
+
+impl Nameserver {
+    pub fn new() -> Nameserver {
+        Nameserver::default()
+    }
+
+    // Adds the zone with the provided origin, which is given in the master file format.
+    pub fn zone(self, origin: &str, contents: &str) -> Nameserver {
+        self.insert(zone(origin, contents))
+    }
+
+    pub fn insert(mut self, zone: Zone) -> Nameserver {
+        self.zones.insert(zone);
+        self
+    }
+
+    // Makes the nameserver leave out the proofs that names don't exist from its answers.
+    pub fn without_proofs(mut self) -> Nameserver {
+        self.without_proofs = true;
+        self
+    }
+
+    // Makes the nameserver fail the questions about the records of the type at the name.
+    pub fn fail(self, name: &str, r_type: RRType) -> Nameserver {
+        self.answer_with(name, r_type, ResponseCode::ServFail)
+    }
+
+    // Makes the nameserver answer the questions about the records of the type at the name
+    // with the response code alone, the way that some broken nameservers do.
+    pub fn answer_with(mut self, name: &str, r_type: RRType, rcode: ResponseCode) -> Nameserver {
+        self.failures
+            .push((DomainName::new(name.to_string()), r_type, rcode));
+        self
+    }
+
+    // Makes the nameserver record the questions that it's asked in the log.
+    pub fn log(mut self, log: QueryLog) -> Nameserver {
+        self.log = Some(log);
+        self
+    }
+
+    // Starts serving over UDP on a port of the loopback address, which is returned.
+    pub async fn start(self) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            loop {
+                let Ok((len, peer)) = socket.recv_from(&mut buf).await else {
+                    continue;
+                };
+                let Ok(query) = DNSMessage::decode(&buf[..len]) else {
+                    continue;
+                };
+                let response = self.answer(&query).encode().unwrap();
+                let _ = socket.send_to(&response, peer).await;
+            }
+        });
+        addr
+    }
+
+    fn answer(&self, query: &DNSMessage) -> DNSMessage {
+        let question = &query.questions()[0];
+        if let Some(log) = &self.log {
+            let asked = (question.name().clone(), question.q_type().clone());
+            log.lock().unwrap().push(asked);
+        }
+        let failure = self
+            .failures
+            .iter()
+            .find(|(name, r_type, _)| name == question.name() && r_type == question.q_type())
+            .map(|(_, _, rcode)| rcode.clone());
+        // the DS records at a zone cut belong to the parent zone.
+        let zone = match question.q_type() {
+            RRType::DS => question
+                .name()
+                .parent()
+                .and_then(|parent| self.zones.find(&parent)),
+            _ => self.zones.find(question.name()),
+        };
+        let zone = match (zone, failure) {
+            (Some(zone), None) => zone,
+            (_, failure) => {
+                let rcode = failure.unwrap_or(ResponseCode::Refused);
+                return DNSMessage::response_to(query)
+                    .response_code(rcode)
+                    .build()
+                    .unwrap();
+            }
+        };
+
+        let mut response = zone.answer(query, question).build().unwrap();
+        let negative = response
+            .authorities()
+            .iter()
+            .any(|record| record.r_type() == &RRType::SOA);
+        let synthesised = response
+            .answers()
+            .iter()
+            .any(|answer| zone.records_at(answer.name()).is_empty());
+        if (negative || synthesised) && !self.without_proofs {
+            let proofs = proofs(zone, question.name());
+            response.authorities_mut().extend(proofs);
+        }
+        let answers = signatures(zone, response.answers());
+        response.answers_mut().extend(answers);
+        let authorities = signatures(zone, response.authorities());
+        response.authorities_mut().extend(authorities);
+        response
+    }
+}
+
+// Returns the RRSIGs from the zone over the records, which are taken from the wildcard
+// that the records were synthesised from if their owner doesn't exist.
+fn signatures(zone: &Zone, records: &[DNSRecord]) -> Vec<DNSRecord> {
+    let mut signatures = vec![];
+    for (owner, r_type) in records
+        .iter()
+        .map(|record| (record.name(), record.r_type()))
+        .unique()
+    {
+        let at_owner = zone.records_at(owner);
+        let rrsigs = if at_owner.is_empty() {
+            match zone.lookup(owner, &RRType::RRSIG) {
+                Lookup::Records(records) => records,
+                _ => vec![],
+            }
+        } else {
+            at_owner.to_vec()
+        };
+        signatures.extend(rrsigs.into_iter().filter(|rrsig| match rrsig.rdata() {
+            Some(RData::RRSIG(rrsig)) => rrsig.type_covered() == r_type,
+            _ => false,
+        }));
+    }
+    signatures
+}
+
+// Returns the NSEC and NSEC3 records of the zone that match or cover the name, its
+// ancestors within the zone or the wildcards at those.
+fn proofs(zone: &Zone, name: &DomainName) -> Vec<DNSRecord> {
+    let mut names = vec![];
+    for ancestor in std::iter::successors(Some(name.clone()), |name| name.parent())
+        .take_while(|ancestor| ancestor.is_subdomain_of(zone.origin()))
+    {
+        names.push(ancestor.child(b"*"));
+        names.push(ancestor);
+    }
+    zone.records()
+        .filter(|record| match record.rdata() {
+            Some(RData::NSEC(nsec)) => names
+                .iter()
+                .any(|name| between(record.name(), nsec.next_domain_name(), name)),
+            Some(RData::NSEC3(nsec3)) => {
+                let label = &record.name().labels()[0];
+                let owner = BASE32HEX_NOPAD.decode(&label.to_ascii_uppercase()).unwrap();
+                names.iter().any(|name| {
+                    let hash = dnssec::nsec3_hash(name, nsec3.salt(), nsec3.iterations());
+                    between(owner.as_slice(), nsec3.next_hashed_owner_name(), &hash)
+                })
+            }
+            _ => false,
+        })
+        .cloned()
+        .collect_vec()
+}
+
+// Returns true if the value matches owner or falls between owner and next, where next
+// wraps around to the start of the zone if it's not after owner.
+fn between<T: PartialOrd + ?Sized>(owner: &T, next: &T, value: &T) -> bool {
+    if owner < next {
+        owner <= value && value < next
+    } else {
+        owner <= value || value < next
+    }
+}
+
+// Returns the zone with the provided origin out of its master file.
+pub fn zone(origin: &str, contents: &str) -> Zone {
+    let origin = DomainName::new(origin.to_string());
+    let records = zone_file::parse_zone(contents, &origin).unwrap();
+    Zone::new(origin, records).unwrap()
+}
+
+// Returns a resolver that starts resolving at the provided nameserver.
+pub async fn resolver(root_server: SocketAddr) -> Resolver {
+    let mut resolver = Resolver::new("127.0.0.1:0").await.unwrap();
+    resolver.set_root_server(root_server);
+    resolver
+}
@@ -0,0 +1,99 @@
+// The server binary, run by the tests that exercise it end to end.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::net::SocketAddr;
+use std::process::{self, Child, Command, Stdio};
+use std::thread;
+
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::message::{DNSMessage, MessageBuilder};
+use dns_resolver::query::{DNSQuestion, Opcode};
+use dns_resolver::rr_types::{DNSClass, RRType};
+use tokio::net::UdpSocket;
+
+// Server is the server binary running on a port of the loopback address, which is
+// stopped once it's dropped.
+pub struct Server {
+    child: Child,
+    pub addr: SocketAddr,
+}
+
+impl Server {
+    pub fn start(args: &[String]) -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_server"))
+            .args(["--listen", "127.0.0.1:0"])
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        let addr = loop {
+            line.clear();
+            assert_ne!(stdout.read_line(&mut line).unwrap(), 0, "the server exited");
+            if let Some(addr) = line.trim().strip_prefix("Listening on ") {
+                break addr.parse().unwrap();
+            }
+        };
+        // the server keeps on logging, which mustn't fill up the pipe.
+        thread::spawn(move || stdout.read_to_end(&mut vec![]));
+        Server { child, addr }
+    }
+
+    pub async fn query(&self, name: &str, r_type: RRType) -> DNSMessage {
+        let question = DNSQuestion::new(DomainName::new(name.to_string()), r_type, DNSClass::IN);
+        self.send(question).await
+    }
+
+    pub async fn send(&self, question: DNSQuestion) -> DNSMessage {
+        let query = MessageBuilder::query()
+            .id(7)
+            .recursion_desired(true)
+            .question(question);
+        self.exchange(query).await
+    }
+
+    // Tells the server that example. has changed, as its primary would.
+    pub async fn notify(&self) -> DNSMessage {
+        let question = DNSQuestion::new(
+            DomainName::new(String::from("example.")),
+            RRType::SOA,
+            DNSClass::IN,
+        );
+        let query = MessageBuilder::query()
+            .id(8)
+            .opcode(Opcode::Notify)
+            .authoritative(true)
+            .question(question);
+        self.exchange(query).await
+    }
+
+    pub async fn exchange(&self, query: MessageBuilder) -> DNSMessage {
+        let query = query.build().unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket
+            .send_to(&query.encode().unwrap(), self.addr)
+            .await
+            .unwrap();
+        let mut buf = [0; 4096];
+        let len = socket.recv(&mut buf).await.unwrap();
+        DNSMessage::decode(&buf[..len]).unwrap()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// Writes the zone to a file of its own for the test, and returns the value of the --zone
+// flag that serves it.
+pub fn zone_flag(test: &str, zone: &str) -> String {
+    let path = std::env::temp_dir().join(format!("dns-resolver-{}-{}.zone", test, process::id()));
+    fs::write(&path, zone).unwrap();
+    let _ = fs::remove_file(path.with_extension("zone.jnl"));
+    format!("example.={}", path.display())
+}
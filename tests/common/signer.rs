@@ -0,0 +1,338 @@
+// Signs zones for the tests of DNSSEC validation, with keys of each of the algorithms
+// that can be validated.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use data_encoding::BASE32HEX_NOPAD;
+use dns_resolver::dnssec;
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::rdata::{RData, DNSKEY, DS, NSEC, NSEC3, NSEC3PARAM, RRSIG};
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::{DNSClass, RRType};
+use dns_resolver::zone::Zone;
+use itertools::Itertools;
+use ring::digest;
+use ring::rand::SystemRandom;
+use ring::signature::{
+    EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, RsaPublicKeyComponents,
+    ECDSA_P256_SHA256_FIXED_SIGNING, RSA_PKCS1_SHA256,
+};
+
+// The key used for RSASHA256, which ring can't generate.
+const RSA_KEY: &[u8] = include_bytes!("../fixtures/rsasha256.pk8");
+
+// The flags of a key signing key, i.e. a zone key that is a secure entry point.
+const KSK_FLAGS: u16 = 257;
+
+// The salt and the number of extra iterations of the NSEC3 hashes.
+const NSEC3_SALT: [u8; 2] = [0xAB, 0xCD];
+const NSEC3_ITERATIONS: u16 = 1;
+
+// The DNSSEC algorithms that keys can be made for.
+#[derive(Debug, Clone, Copy)]
+pub enum Algorithm {
+    RsaSha256 = 8,
+    EcdsaP256Sha256 = 13,
+    Ed25519 = 15,
+}
+
+enum Pair {
+    Rsa(RsaKeyPair),
+    Ecdsa(EcdsaKeyPair),
+    Ed25519(Ed25519KeyPair),
+}
+
+// Key is a key pair that signs the records of a zone.
+pub struct Key {
+    pair: Pair,
+    dnskey: DNSKEY,
+}
+
+impl Key {
+    // Returns a new key of the algorithm. Every RSA key is the same one.
+    pub fn generate(algorithm: Algorithm) -> Key {
+        let rng = SystemRandom::new();
+        let (pair, public_key) = match algorithm {
+            Algorithm::RsaSha256 => {
+                let pair = RsaKeyPair::from_pkcs8(RSA_KEY).unwrap();
+                let components = RsaPublicKeyComponents::<Vec<u8>>::from(pair.public());
+                // the exponent is short enough for its length to take a single octet.
+                let mut public_key = vec![components.e.len() as u8];
+                public_key.extend(&components.e);
+                public_key.extend(&components.n);
+                (Pair::Rsa(pair), public_key)
+            }
+            Algorithm::EcdsaP256Sha256 => {
+                let pkcs8 =
+                    EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+                let pair = EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    pkcs8.as_ref(),
+                    &rng,
+                )
+                .unwrap();
+                // the point is uncompressed, without its 0x04 prefix.
+                let public_key = pair.public_key().as_ref()[1..].to_vec();
+                (Pair::Ecdsa(pair), public_key)
+            }
+            Algorithm::Ed25519 => {
+                let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+                let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+                let public_key = pair.public_key().as_ref().to_vec();
+                (Pair::Ed25519(pair), public_key)
+            }
+        };
+        let dnskey = DNSKEY::new(KSK_FLAGS, 3, algorithm as u8, public_key);
+        Key { pair, dnskey }
+    }
+
+    pub fn dnskey(&self) -> &DNSKEY {
+        &self.dnskey
+    }
+
+    // Returns the DS record of the key, with a digest of the provided type, for the zone.
+    pub fn ds_with_digest(&self, zone: &DomainName, digest_type: u8) -> DS {
+        let algorithm = match digest_type {
+            1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            2 => &digest::SHA256,
+            4 => &digest::SHA384,
+            _ => panic!("unknown digest type {}", digest_type),
+        };
+        let mut data = vec![];
+        zone.encode_canonical(&mut data).unwrap();
+        data.extend(self.dnskey.flags().to_be_bytes());
+        data.push(self.dnskey.protocol());
+        data.push(self.dnskey.algorithm());
+        data.extend(self.dnskey.public_key());
+        let digest = digest::digest(algorithm, &data).as_ref().to_vec();
+        DS::new(
+            self.dnskey.key_tag(),
+            self.dnskey.algorithm(),
+            digest_type,
+            digest,
+        )
+    }
+
+    // Returns the DS record of the key for the zone, with a SHA-256 digest.
+    pub fn ds(&self, zone: &DomainName) -> DS {
+        self.ds_with_digest(zone, 2)
+    }
+
+    // Returns the signature over the data.
+    pub fn sign_data(&self, data: &[u8]) -> Vec<u8> {
+        let rng = SystemRandom::new();
+        match &self.pair {
+            Pair::Rsa(pair) => {
+                let mut signature = vec![0; pair.public().modulus_len()];
+                pair.sign(&RSA_PKCS1_SHA256, &rng, data, &mut signature)
+                    .unwrap();
+                signature
+            }
+            Pair::Ecdsa(pair) => pair.sign(&rng, data).unwrap().as_ref().to_vec(),
+            Pair::Ed25519(pair) => pair.sign(data).as_ref().to_vec(),
+        }
+    }
+}
+
+// How the names that don't exist in a signed zone are proven not to.
+#[derive(Debug, Clone, Copy)]
+pub enum Denial {
+    Nsec,
+    // Opt-out leaves the unsigned delegations out of the chain of NSEC3 records.
+    Nsec3 { opt_out: bool },
+}
+
+// Signer signs zones using a key, with signatures that are valid for a period of time.
+pub struct Signer {
+    key: Key,
+    denial: Denial,
+    inception: u32,
+    expiration: u32,
+}
+
+impl Signer {
+    // Creates a signer whose signatures are valid from an hour ago until a day from now.
+    pub fn new(key: Key, denial: Denial) -> Signer {
+        let now = dnssec::now();
+        Signer {
+            key,
+            denial,
+            inception: now - 3600,
+            expiration: now + 86400,
+        }
+    }
+
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    // Sets the period of time that the signatures are valid for.
+    pub fn set_validity(&mut self, inception: u32, expiration: u32) {
+        self.inception = inception;
+        self.expiration = expiration;
+    }
+
+    // Returns the RRSIG over the records, which have to make up a RRset, as made by the
+    // zone with the provided origin.
+    pub fn rrsig(&self, origin: &DomainName, records: &[&DNSRecord]) -> RRSIG {
+        let record = records[0];
+        let unsigned = RRSIG::new(
+            record.r_type().clone(),
+            self.key.dnskey.algorithm(),
+            dnssec::label_count(record.name()),
+            record.ttl().as_secs() as u32,
+            self.expiration,
+            self.inception,
+            self.key.dnskey.key_tag(),
+            origin.clone(),
+            vec![],
+        );
+        let data = dnssec::signed_data(&unsigned, records).unwrap();
+        RRSIG::new(
+            unsigned.type_covered().clone(),
+            unsigned.algorithm(),
+            unsigned.labels(),
+            unsigned.original_ttl(),
+            unsigned.expiration(),
+            unsigned.inception(),
+            unsigned.key_tag(),
+            unsigned.signer_name().clone(),
+            self.key.sign_data(&data),
+        )
+    }
+
+    // Returns the zone with its key, the NSEC or NSEC3 records that prove which names
+    // don't exist and the RRSIGs over all of its authoritative RRsets.
+    pub fn sign(&self, zone: &Zone) -> Zone {
+        let origin = zone.origin().clone();
+        let mut records = zone.records().cloned().collect_vec();
+        let dnskey = RData::DNSKEY(self.key.dnskey.clone());
+        records.push(record(&origin, zone.soa().ttl(), dnskey));
+
+        // the names at zone cuts, and whether their delegation is signed.
+        let cuts: BTreeMap<DomainName, bool> = records
+            .iter()
+            .filter(|record| record.r_type() == &RRType::NS && record.name() != &origin)
+            .map(|record| {
+                let signed = records
+                    .iter()
+                    .any(|other| other.name() == record.name() && other.r_type() == &RRType::DS);
+                (record.name().clone(), signed)
+            })
+            .collect();
+        // glue below a zone cut isn't authoritative, so it's neither signed nor proven.
+        let glue = |name: &DomainName| {
+            cuts.keys()
+                .any(|cut| name != cut && name.is_subdomain_of(cut))
+        };
+        let mut types: BTreeMap<DomainName, BTreeSet<u16>> = BTreeMap::new();
+        for record in records.iter().filter(|record| !glue(record.name())) {
+            types
+                .entry(record.name().clone())
+                .or_default()
+                .insert(record.r_type().code());
+        }
+
+        // the NSEC and NSEC3 records have the TTL of negative answers.
+        let ttl = Duration::from_secs(u64::from(zone.soa_rdata().minimum()));
+        match self.denial {
+            Denial::Nsec => {
+                let names = types.keys().cloned().collect_vec();
+                for (i, name) in names.iter().enumerate() {
+                    let next = names[(i + 1) % names.len()].clone();
+                    let mut present = types[name].clone();
+                    present.extend([RRType::RRSIG.code(), RRType::NSEC.code()]);
+                    let nsec = NSEC::new(next, to_types(&present));
+                    records.push(record(name, ttl, RData::NSEC(nsec)));
+                }
+            }
+            Denial::Nsec3 { opt_out } => {
+                // the names in between the origin and the names of the zone exist too.
+                let mut names = types.clone();
+                for name in types.keys() {
+                    for ancestor in std::iter::successors(name.parent(), |name| name.parent())
+                        .take_while(|ancestor| ancestor.is_subdomain_of(&origin))
+                    {
+                        names.entry(ancestor).or_default();
+                    }
+                }
+                names
+                    .get_mut(&origin)
+                    .unwrap()
+                    .insert(RRType::NSEC3PARAM.code());
+                if opt_out {
+                    names.retain(|name, _| cuts.get(name) != Some(&false));
+                }
+                let hashed = names
+                    .iter()
+                    .map(|(name, present)| {
+                        let hash = dnssec::nsec3_hash(name, &NSEC3_SALT, NSEC3_ITERATIONS);
+                        let mut present = present.clone();
+                        // the NS records of an unsigned delegation are the only ones
+                        // that aren't signed.
+                        if !present.is_empty() && cuts.get(name) != Some(&false) {
+                            present.insert(RRType::RRSIG.code());
+                        }
+                        (hash, present)
+                    })
+                    .sorted()
+                    .collect_vec();
+                for (i, (hash, present)) in hashed.iter().enumerate() {
+                    let next = hashed[(i + 1) % hashed.len()].0.clone();
+                    let nsec3 = NSEC3::new(
+                        1,
+                        u8::from(opt_out),
+                        NSEC3_ITERATIONS,
+                        NSEC3_SALT.to_vec(),
+                        next,
+                        to_types(present),
+                    );
+                    let label = BASE32HEX_NOPAD.encode(hash).to_ascii_lowercase();
+                    let owner = origin.child(label.as_bytes());
+                    records.push(record(&owner, ttl, RData::NSEC3(nsec3)));
+                }
+                let param = NSEC3PARAM::new(1, 0, NSEC3_ITERATIONS, NSEC3_SALT.to_vec());
+                records.push(record(&origin, ttl, RData::NSEC3PARAM(param)));
+            }
+        }
+
+        // only the DS and NSEC records at a zone cut belong to the zone.
+        let signed = records
+            .iter()
+            .filter(|record| !glue(record.name()))
+            .filter(|record| {
+                !cuts.contains_key(record.name())
+                    || matches!(record.r_type(), RRType::DS | RRType::NSEC)
+            })
+            .into_group_map_by(|record| (record.name().clone(), record.r_type().clone()));
+        let rrsigs = signed
+            .into_values()
+            .map(|rrset| {
+                let rrsig = self.rrsig(&origin, &rrset);
+                record(rrset[0].name(), rrset[0].ttl(), RData::RRSIG(rrsig))
+            })
+            .collect_vec();
+        records.extend(rrsigs);
+        Zone::new(origin, records).unwrap()
+    }
+}
+
+// Returns a record of the class IN with the provided data.
+pub fn record(name: &DomainName, ttl: Duration, rdata: RData) -> DNSRecord {
+    let r_type = match &rdata {
+        RData::DNSKEY(_) => RRType::DNSKEY,
+        RData::NSEC(_) => RRType::NSEC,
+        RData::NSEC3(_) => RRType::NSEC3,
+        RData::NSEC3PARAM(_) => RRType::NSEC3PARAM,
+        RData::RRSIG(_) => RRType::RRSIG,
+        RData::DS(_) => RRType::DS,
+        RData::A(_) => RRType::A,
+        _ => panic!("unexpected data {:?}", rdata),
+    };
+    DNSRecord::new(name.clone(), r_type, DNSClass::IN, ttl, rdata).unwrap()
+}
+
+fn to_types(codes: &BTreeSet<u16>) -> Vec<RRType> {
+    codes.iter().map(|code| RRType::from(*code)).collect_vec()
+}
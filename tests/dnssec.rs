@@ -0,0 +1,341 @@
+mod common;
+
+use common::signer::{record, Algorithm, Denial, Key, Signer};
+use dns_resolver::dnssec::{self, Denial as Proof};
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::rdata::{RData, DS, NSEC, NSEC3, RRSIG};
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::RRType;
+use dns_resolver::zone::Zone;
+
+const EXAMPLE: &str = "
+$TTL 3600
+@           SOA     ns.example. hostmaster.example. 1 7200 900 1209600 300
+            NS      ns
+ns          A       192.0.2.1
+www         A       192.0.2.2
+            A       192.0.2.3
+*.wild      TXT     \"synthesised\"
+insecure    NS      ns.insecure
+ns.insecure A       192.0.2.4
+";
+
+fn name(name: &str) -> DomainName {
+    DomainName::new(name.to_string())
+}
+
+// Returns the A records of www.example. along with a RRSIG over them.
+fn signed_rrset(signer: &Signer) -> (Vec<DNSRecord>, RRSIG) {
+    let zone = common::zone("example.", EXAMPLE);
+    let records = zone
+        .records_at(&name("www.example."))
+        .iter()
+        .filter(|record| record.r_type() == &RRType::A)
+        .cloned()
+        .collect::<Vec<_>>();
+    let rrsig = signer.rrsig(&name("example."), &records.iter().collect::<Vec<_>>());
+    (records, rrsig)
+}
+
+fn verify(rrsig: &RRSIG, records: &[DNSRecord], key: &Key, now: u32) -> Result<(), String> {
+    let records = records.iter().collect::<Vec<_>>();
+    dnssec::verify_rrsig(rrsig, &records, key.dnskey(), now)
+}
+
+fn nsecs(zone: &Zone) -> Vec<(DomainName, NSEC)> {
+    zone.records()
+        .filter_map(|record| match record.rdata() {
+            Some(RData::NSEC(nsec)) => Some((record.name().clone(), nsec.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn nsec3s(zone: &Zone) -> Vec<(DomainName, NSEC3)> {
+    zone.records()
+        .filter_map(|record| match record.rdata() {
+            Some(RData::NSEC3(nsec3)) => Some((record.name().clone(), nsec3.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn signed_zone(denial: Denial) -> Zone {
+    let signer = Signer::new(Key::generate(Algorithm::Ed25519), denial);
+    signer.sign(&common::zone("example.", EXAMPLE))
+}
+
+#[test]
+fn verifies_signatures_of_each_algorithm() {
+    for algorithm in [
+        Algorithm::RsaSha256,
+        Algorithm::EcdsaP256Sha256,
+        Algorithm::Ed25519,
+    ] {
+        let signer = Signer::new(Key::generate(algorithm), Denial::Nsec);
+        let (records, rrsig) = signed_rrset(&signer);
+        assert_eq!(
+            verify(&rrsig, &records, signer.key(), dnssec::now()),
+            Ok(()),
+            "{:?}",
+            algorithm
+        );
+
+        // the signature doesn't cover other data.
+        let mut tampered = records.clone();
+        tampered[0] = record(
+            tampered[0].name(),
+            tampered[0].ttl(),
+            RData::A("192.0.2.99".parse().unwrap()),
+        );
+        assert!(
+            verify(&rrsig, &tampered, signer.key(), dnssec::now()).is_err(),
+            "{:?}",
+            algorithm
+        );
+    }
+}
+
+#[test]
+fn rejects_signatures_outside_of_their_validity_period() {
+    let signer = Signer::new(Key::generate(Algorithm::EcdsaP256Sha256), Denial::Nsec);
+    let (records, rrsig) = signed_rrset(&signer);
+    let expired = rrsig.expiration() + 1;
+    let not_yet_valid = rrsig.inception() - 1;
+    for now in [expired, not_yet_valid] {
+        let result = verify(&rrsig, &records, signer.key(), now);
+        assert!(
+            result
+                .as_ref()
+                .is_err_and(|e| e.contains("not valid at this time")),
+            "got {:?}",
+            result
+        );
+    }
+    assert_eq!(
+        verify(&rrsig, &records, signer.key(), rrsig.expiration()),
+        Ok(())
+    );
+}
+
+#[test]
+fn rejects_signatures_made_by_other_keys() {
+    let signer = Signer::new(Key::generate(Algorithm::Ed25519), Denial::Nsec);
+    let (records, rrsig) = signed_rrset(&signer);
+
+    let other = Key::generate(Algorithm::Ed25519);
+    let result = verify(&rrsig, &records, &other, dnssec::now());
+    assert!(
+        result
+            .as_ref()
+            .is_err_and(|e| e.contains("not made by the key")),
+        "got {:?}",
+        result
+    );
+
+    let mismatched = RRSIG::new(
+        rrsig.type_covered().clone(),
+        rrsig.algorithm(),
+        rrsig.labels(),
+        rrsig.original_ttl(),
+        rrsig.expiration(),
+        rrsig.inception(),
+        rrsig.key_tag().wrapping_add(1),
+        rrsig.signer_name().clone(),
+        rrsig.signature().to_vec(),
+    );
+    let result = verify(&mismatched, &records, signer.key(), dnssec::now());
+    assert!(
+        result
+            .as_ref()
+            .is_err_and(|e| e.contains("not made by the key")),
+        "got {:?}",
+        result
+    );
+}
+
+#[test]
+fn matches_ds_records_to_keys() {
+    let key = Key::generate(Algorithm::EcdsaP256Sha256);
+    let zone = name("example.");
+    for digest_type in [1, 2, 4] {
+        let ds = key.ds_with_digest(&zone, digest_type);
+        assert!(
+            dnssec::ds_matches(&zone, key.dnskey(), &ds),
+            "{}",
+            digest_type
+        );
+        // the digest covers the name of the zone.
+        assert!(!dnssec::ds_matches(&name("other."), key.dnskey(), &ds));
+    }
+
+    let other = Key::generate(Algorithm::EcdsaP256Sha256);
+    assert!(!dnssec::ds_matches(&zone, other.dnskey(), &key.ds(&zone)));
+
+    let ds = key.ds(&zone);
+    let mut digest = ds.digest().to_vec();
+    digest[0] ^= 1;
+    let altered = DS::new(ds.key_tag(), ds.algorithm(), ds.digest_type(), digest);
+    assert!(!dnssec::ds_matches(&zone, key.dnskey(), &altered));
+}
+
+#[test]
+fn proves_nxdomain_using_nsec() {
+    let nsecs = nsecs(&signed_zone(Denial::Nsec));
+    let qname = name("missing.example.");
+    assert_eq!(
+        dnssec::nsec_denial(&qname, &RRType::A, true, &nsecs),
+        Some(Proof::NXDomain)
+    );
+
+    // the wildcard at the closest encloser has to be proven not to exist as well.
+    let wildcard = name("*.example.");
+    let without_wildcard = nsecs
+        .iter()
+        .filter(|(owner, nsec)| !(owner < &wildcard && &wildcard < nsec.next_domain_name()))
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!(
+        dnssec::nsec_denial(&qname, &RRType::A, true, &without_wildcard),
+        None
+    );
+    // names that exist can't be proven not to.
+    assert_eq!(
+        dnssec::nsec_denial(&name("www.example."), &RRType::A, true, &nsecs),
+        None
+    );
+}
+
+#[test]
+fn proves_nodata_using_nsec() {
+    let nsecs = nsecs(&signed_zone(Denial::Nsec));
+    let www = name("www.example.");
+    assert_eq!(
+        dnssec::nsec_denial(&www, &RRType::MX, false, &nsecs),
+        Some(Proof::NoData { delegation: false })
+    );
+    assert_eq!(dnssec::nsec_denial(&www, &RRType::A, false, &nsecs), None);
+    // the absence of DS records at an unsigned delegation.
+    assert_eq!(
+        dnssec::nsec_denial(&name("insecure.example."), &RRType::DS, false, &nsecs),
+        Some(Proof::NoData { delegation: true })
+    );
+}
+
+#[test]
+fn proves_nxdomain_and_nodata_using_nsec3() {
+    let nsec3s = nsec3s(&signed_zone(Denial::Nsec3 { opt_out: false }));
+    assert_eq!(
+        dnssec::nsec3_denial(&name("missing.example."), &RRType::A, true, &nsec3s),
+        Some(Proof::NXDomain)
+    );
+    assert_eq!(
+        dnssec::nsec3_denial(&name("www.example."), &RRType::A, true, &nsec3s),
+        None
+    );
+    assert_eq!(
+        dnssec::nsec3_denial(&name("www.example."), &RRType::MX, false, &nsec3s),
+        Some(Proof::NoData { delegation: false })
+    );
+    assert_eq!(
+        dnssec::nsec3_denial(&name("www.example."), &RRType::A, false, &nsec3s),
+        None
+    );
+    assert_eq!(
+        dnssec::nsec3_denial(&name("insecure.example."), &RRType::DS, false, &nsec3s),
+        Some(Proof::NoData { delegation: true })
+    );
+}
+
+#[test]
+fn proves_opt_out_using_nsec3() {
+    // the unsigned delegation is left out of the chain of NSEC3 records.
+    let nsec3s = nsec3s(&signed_zone(Denial::Nsec3 { opt_out: true }));
+    assert_eq!(
+        dnssec::nsec3_denial(&name("insecure.example."), &RRType::DS, false, &nsec3s),
+        Some(Proof::OptOut)
+    );
+
+    // without the Opt-Out flag, the span covering the name proves nothing about DS records.
+    let nsec3s = nsec3s
+        .into_iter()
+        .map(|(owner, nsec3)| {
+            let cleared = NSEC3::new(
+                nsec3.hash_algorithm(),
+                0,
+                nsec3.iterations(),
+                nsec3.salt().to_vec(),
+                nsec3.next_hashed_owner_name().to_vec(),
+                nsec3.types().clone(),
+            );
+            (owner, cleared)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        dnssec::nsec3_denial(&name("insecure.example."), &RRType::DS, false, &nsec3s),
+        None
+    );
+}
+
+#[test]
+fn proves_nothing_but_opt_out_for_nxdomain_in_opt_out_spans() {
+    let nsec3s = nsec3s(&signed_zone(Denial::Nsec3 { opt_out: true }));
+    assert_eq!(
+        dnssec::nsec3_denial(&name("missing.example."), &RRType::A, true, &nsec3s),
+        Some(Proof::OptOut)
+    );
+}
+
+#[test]
+fn does_not_check_nsec3_records_with_excessive_iterations() {
+    let zone = signed_zone(Denial::Nsec3 { opt_out: false });
+    let with_iterations = |iterations: u16| {
+        nsec3s(&zone)
+            .into_iter()
+            .map(|(owner, nsec3)| {
+                let costly = NSEC3::new(
+                    nsec3.hash_algorithm(),
+                    nsec3.flags(),
+                    iterations,
+                    nsec3.salt().to_vec(),
+                    nsec3.next_hashed_owner_name().to_vec(),
+                    nsec3.types().clone(),
+                );
+                (owner, costly)
+            })
+            .collect::<Vec<_>>()
+    };
+    let missing = name("missing.example.");
+    // the owners are hashed with a single iteration, so the records only prove anything
+    // when they say so.
+    assert_eq!(
+        dnssec::nsec3_denial(&missing, &RRType::A, true, &with_iterations(1)),
+        Some(Proof::NXDomain)
+    );
+    for iterations in [101, 65535] {
+        let nsec3s = with_iterations(iterations);
+        assert_eq!(
+            dnssec::nsec3_denial(&missing, &RRType::A, true, &nsec3s),
+            Some(Proof::ExcessiveIterations),
+            "{}",
+            iterations
+        );
+        let synthesised = name("anything.wild.example.");
+        assert!(!dnssec::wildcard_proof(&synthesised, 2, &[], &nsec3s));
+    }
+}
+
+#[test]
+fn proves_wildcard_answers() {
+    // the RRSIG over the wildcard *.wild.example. has two labels.
+    let synthesised = name("anything.wild.example.");
+    let existing = name("www.example.");
+
+    let nsecs = nsecs(&signed_zone(Denial::Nsec));
+    assert!(dnssec::wildcard_proof(&synthesised, 2, &nsecs, &[]));
+    assert!(!dnssec::wildcard_proof(&existing, 1, &nsecs, &[]));
+
+    let nsec3s = nsec3s(&signed_zone(Denial::Nsec3 { opt_out: false }));
+    assert!(dnssec::wildcard_proof(&synthesised, 2, &[], &nsec3s));
+    assert!(!dnssec::wildcard_proof(&existing, 1, &[], &nsec3s));
+}
@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use dns_resolver::cache::DNSCache;
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::rdata::RData;
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::{DNSClass, RRType};
+
+fn name(name: &str) -> DomainName {
+    DomainName::new(name.to_string())
+}
+
+#[test]
+fn compares_and_hashes_names_case_insensitively() {
+    assert_eq!(name("Google.COM"), name("google.com."));
+    assert_ne!(name("google.com"), name("google.co"));
+    let names = ["WWW.example.com", "www.EXAMPLE.com.", "www.example.com"]
+        .into_iter()
+        .map(name)
+        .collect::<HashSet<_>>();
+    assert_eq!(names.len(), 1);
+
+    let mut cache = DNSCache::new(10);
+    let record = DNSRecord::new(
+        name("Example.COM."),
+        RRType::A,
+        DNSClass::IN,
+        Duration::from_secs(300),
+        RData::A("192.0.2.1".parse().unwrap()),
+    )
+    .unwrap();
+    cache.insert(
+        &name("Example.COM."),
+        &RRType::A,
+        &DNSClass::IN,
+        vec![record],
+        false,
+    );
+    assert!(cache
+        .get(&name("example.com"), &RRType::A, &DNSClass::IN)
+        .is_some());
+}
+
+#[test]
+fn orders_names_canonically() {
+    // the example of RFC 4034 section 6.1, shuffled.
+    let ordered = [
+        "example.",
+        "a.example.",
+        "yljkjljk.a.example.",
+        "Z.a.example.",
+        "zABC.a.EXAMPLE.",
+        "z.example.",
+        "\\001.z.example.",
+        "*.z.example.",
+        "\\200.z.example.",
+    ]
+    .map(name);
+    let mut names = ordered.to_vec();
+    names.reverse();
+    names.swap(2, 6);
+    names.sort();
+    assert_eq!(names, ordered);
+}
+
+#[test]
+fn relates_names_to_their_ancestors() {
+    let www = name("www.Example.com.");
+    assert_eq!(
+        www.labels(),
+        [b"www".to_vec(), b"Example".to_vec(), b"com".to_vec()]
+    );
+    assert_eq!(www.parent(), Some(name("example.com")));
+    assert_eq!(DomainName::root().parent(), None);
+    assert!(www.is_subdomain_of(&name("EXAMPLE.com")));
+    assert!(www.is_subdomain_of(&www));
+    assert!(www.is_subdomain_of(&DomainName::root()));
+    assert!(!name("example.com").is_subdomain_of(&www));
+    // only whole labels are matched.
+    assert!(!name("notexample.com").is_subdomain_of(&name("example.com")));
+}
+
+#[test]
+fn round_trips_escaped_presentation_formats() {
+    for (presentation, labels) in [
+        ("a\\.b.example", vec![&b"a.b"[..], b"example"]),
+        ("\\000\\255.example", vec![&[0, 255][..], b"example"]),
+        (
+            r"semi\;colon\\.example",
+            vec![&b"semi;colon\\"[..], b"example"],
+        ),
+        ("sp\\032ace.example", vec![&b"sp ace"[..], b"example"]),
+    ] {
+        let parsed = presentation.parse::<DomainName>().unwrap();
+        assert_eq!(parsed.labels(), labels, "{}", presentation);
+        // octets that aren't printable are rendered as \DDD.
+        let rendered = parsed.to_string();
+        assert_eq!(
+            rendered.parse::<DomainName>().unwrap().labels(),
+            labels,
+            "{}",
+            rendered
+        );
+        let mut wire = vec![];
+        parsed.encode(&mut wire).unwrap();
+        let mut expected = vec![];
+        for label in &labels {
+            expected.push(label.len() as u8);
+            expected.extend(*label);
+        }
+        expected.push(0);
+        assert_eq!(wire, expected, "{}", presentation);
+    }
+    assert_eq!(name("sp\\032ace.example").to_string(), "sp\\032ace.example");
+    assert_eq!(name("a\\.b.example").to_string(), "a\\.b.example");
+    assert_eq!(DomainName::root().to_string(), ".");
+}
+
+#[test]
+fn rejects_invalid_names() {
+    let long_label = "a".repeat(64);
+    // 4 labels of 63 octets make a name of 257 octets in the wire format.
+    let long_name = vec!["a".repeat(63); 4].join(".");
+    for invalid in ["a..b", ".example", long_label.as_str(), long_name.as_str()] {
+        let result = invalid.parse::<DomainName>();
+        assert!(result.is_err(), "{}: got {:?}", invalid, result);
+        // names that aren't validated upfront can't be encoded either.
+        let mut wire = vec![];
+        assert!(name(invalid).encode(&mut wire).is_err(), "{}", invalid);
+    }
+    for escape in ["\\256.example", "\\12.example", "trailing\\"] {
+        let result = escape.parse::<DomainName>();
+        assert!(result.is_err(), "{}: got {:?}", escape, result);
+    }
+
+    // the longest labels and names are fine.
+    let longest_name = [vec!["a".repeat(63); 3], vec!["a".repeat(61)]]
+        .concat()
+        .join(".");
+    for valid in ["a".repeat(63), longest_name] {
+        assert!(valid.parse::<DomainName>().is_ok(), "{}", valid);
+    }
+}
+
+#[test]
+fn converts_internationalised_names() {
+    for (unicode, ascii) in [
+        ("münchen.de", "xn--mnchen-3ya.de"),
+        // the UTS #46 mapping folds the case of the characters.
+        ("MÜNCHEN.de.", "xn--mnchen-3ya.de"),
+        ("bücher.example", "xn--bcher-kva.example"),
+        // the ideographic full stop separates labels like a dot.
+        ("例え。テスト", "xn--r8jz45g.xn--zckzah"),
+    ] {
+        let parsed = unicode.parse::<DomainName>().unwrap();
+        assert_eq!(parsed.to_string(), ascii, "{}", unicode);
+        assert_eq!(parsed, name(ascii), "{}", unicode);
+    }
+
+    assert_eq!(name("xn--mnchen-3ya.de").to_unicode(), "münchen.de");
+    assert_eq!(name("XN--MNCHEN-3YA.de").to_unicode(), "münchen.de");
+    // labels that aren't valid punycode are left as they are.
+    assert_eq!(
+        name("xn--a-ecp.xn--99.de").to_unicode(),
+        "xn--a-ecp.xn--99.de"
+    );
+    assert_eq!(DomainName::root().to_unicode(), ".");
+
+    // characters that aren't allowed in names are rejected.
+    for invalid in ["a\u{fffd}b.example", "\u{e000}.example"] {
+        let result = invalid.parse::<DomainName>();
+        assert!(result.is_err(), "{}: got {:?}", invalid, result);
+    }
+}
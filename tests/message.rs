@@ -0,0 +1,403 @@
+use std::time::Duration;
+
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::edns::{Edns, EdnsOption};
+use dns_resolver::message::{DNSMessage, MessageBuilder};
+use dns_resolver::query::{DNSQuestion, Opcode, ResponseCode};
+use dns_resolver::rdata::RData;
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::{DNSClass, RRType};
+use dns_resolver::zone_file::parse_zone;
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+fn name(name: &str) -> DomainName {
+    DomainName::new(name.to_string())
+}
+
+fn record(owner: &str, rdata: RData) -> DNSRecord {
+    let r_type = match &rdata {
+        RData::A(_) => RRType::A,
+        RData::NS(_) => RRType::NS,
+        RData::CNAME(_) => RRType::CNAME,
+        rdata => panic!("no type for {:?}", rdata),
+    };
+    DNSRecord::new(
+        name(owner),
+        r_type,
+        DNSClass::IN,
+        Duration::from_secs(300),
+        rdata,
+    )
+    .unwrap()
+}
+
+// Returns a response with records in every section, whose names are compressed.
+fn response() -> DNSMessage {
+    let question = DNSQuestion::new(name("www.example."), RRType::A, DNSClass::IN);
+    let query = MessageBuilder::query()
+        .id(1)
+        .question(question)
+        .build()
+        .unwrap();
+    DNSMessage::response_to(&query)
+        .answer(record("www.example.", RData::CNAME(name("web.example."))))
+        .answer(record(
+            "web.example.",
+            RData::A("192.0.2.1".parse().unwrap()),
+        ))
+        .authority(record("example.", RData::NS(name("ns.example."))))
+        .additional(record(
+            "ns.example.",
+            RData::A("192.0.2.2".parse().unwrap()),
+        ))
+        .build()
+        .unwrap()
+}
+
+// Returns a header with the provided number of questions and answers.
+fn header(questions: u8, answers: u8) -> Vec<u8> {
+    vec![0, 1, 0x81, 0, 0, questions, 0, answers, 0, 0, 0, 0]
+}
+
+#[test]
+fn rejects_truncated_messages() {
+    let wire = response().encode().unwrap();
+    assert!(DNSMessage::decode(&wire).is_ok());
+    for len in 0..wire.len() {
+        let result = DNSMessage::decode(&wire[..len]);
+        assert!(result.is_err(), "{} octets: got {:?}", len, result);
+    }
+}
+
+#[test]
+fn rejects_pointers_that_do_not_point_backwards() {
+    // the name of the question points to itself, after itself and into the header.
+    for pointer in [[0xc0, 12], [0xc0, 20], [0xc0, 0]] {
+        let mut wire = header(1, 0);
+        wire.extend(pointer);
+        wire.extend([0, 1, 0, 1]);
+        if pointer[1] == 0 {
+            // the header makes for a valid name, as long as the counts are small.
+            assert!(DNSMessage::decode(&wire).is_ok());
+            continue;
+        }
+        let result = DNSMessage::decode(&wire);
+        assert!(result.is_err(), "{:?}: got {:?}", pointer, result);
+    }
+
+    // a pointer can't loop back to the labels that precede it within the same name.
+    let mut wire = header(1, 0);
+    wire.extend(b"\x03www");
+    wire.extend([0xc0, 12, 0, 1, 0, 1]);
+    let result = DNSMessage::decode(&wire);
+    assert!(result.is_err(), "got {:?}", result);
+}
+
+// Returns a message whose answer has an owner at the end of a chain of the provided
+// number of pointers, each of which points to the one before it. The chain is in the
+// data of a record of an unknown type that comes first.
+fn pointer_chain(pointers: usize) -> Vec<u8> {
+    let mut wire = header(0, 2);
+    wire.extend([0, 0xff, 0, 0, 1, 0, 0, 0, 0]);
+    wire.extend(((1 + 2 * pointers) as u16).to_be_bytes());
+    let mut previous = wire.len();
+    wire.push(0);
+    for _ in 0..pointers {
+        let offset = wire.len();
+        wire.extend([0xc0, previous as u8]);
+        previous = offset;
+    }
+    // an address record owned by the name at the end of the chain.
+    wire.extend([0xc0, previous as u8]);
+    wire.extend([0, 1, 0, 1, 0, 0, 0, 0]);
+    wire.extend([0, 4, 192, 0, 2, 1]);
+    wire
+}
+
+#[test]
+fn limits_the_pointers_followed_for_a_name() {
+    let message = DNSMessage::decode(&pointer_chain(8)).unwrap();
+    assert!(message.answers()[1].name().is_root());
+    let result = DNSMessage::decode(&pointer_chain(40));
+    assert!(result.is_err(), "got {:?}", result);
+}
+
+#[test]
+fn bounds_record_data_by_its_length() {
+    let mut wire = header(0, 1);
+    wire.extend([0, 0, 1, 0, 1, 0, 0, 0, 0]);
+    // the data is said to be longer than the rest of the message.
+    wire.extend([0, 8, 192, 0, 2, 1]);
+    let result = DNSMessage::decode(&wire);
+    assert!(result.is_err(), "got {:?}", result);
+}
+
+#[test]
+fn survives_corrupted_messages() {
+    let wire = response().encode().unwrap();
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..20000 {
+        let mut corrupted = wire.clone();
+        for _ in 0..rng.gen_range(1..4) {
+            let i = rng.gen_range(0..corrupted.len());
+            corrupted[i] = rng.gen();
+        }
+        let len = rng.gen_range(0..=corrupted.len());
+        // decoding must not panic, whatever the outcome.
+        let _ = DNSMessage::decode(&corrupted[..len]);
+    }
+}
+
+#[test]
+fn compresses_names_across_the_message() {
+    let zone = "\
+www.example. 300 IN CNAME web.example.
+web.example. 300 IN MX 10 mail.example.
+example. 300 IN SOA ns.example. hostmaster.example. 1 7200 3600 1209600 300
+example. 300 IN NS ns.example.
+_sip._udp.example. 300 IN SRV 0 0 5060 sip.example.
+";
+    let records = parse_zone(zone, &DomainName::root()).unwrap();
+    let question = DNSQuestion::new(name("www.example."), RRType::A, DNSClass::IN);
+    let message = MessageBuilder::response()
+        .question(question.clone())
+        .answers(records[..2].to_vec())
+        .authorities(records[2..4].to_vec())
+        .additional(records[4].clone())
+        .build()
+        .unwrap();
+    let wire = message.encode().unwrap();
+
+    // the decoder only follows pointers to names earlier in the message.
+    let decoded = DNSMessage::decode(&wire).unwrap();
+    assert_eq!(decoded.questions()[0].name(), question.name());
+    let presented = |message: &DNSMessage| message.records().map(|r| r.to_string()).collect_vec();
+    assert_eq!(presented(&decoded), presented(&message));
+    assert_eq!(decoded.answers().len(), 2);
+    assert_eq!(decoded.authorities().len(), 2);
+
+    // the labels of example. are encoded once for the question, and again for the target
+    // of the SRV record, whose names aren't compressed.
+    let example = b"\x07example\x00";
+    let repeated = wire.windows(example.len()).filter(|w| w == example).count();
+    assert_eq!(repeated, 2);
+    // the question is only encoded in full once, with the owner of the CNAME record
+    // pointing to it.
+    let www = b"\x03www\x07example\x00";
+    assert_eq!(wire[12..12 + www.len()], www[..]);
+    assert_eq!(wire[12 + www.len() + 4..][..2], [0xc0, 12]);
+
+    let mut uncompressed = 12 + www.len() + 4;
+    for record in message.records() {
+        let mut encoded = vec![];
+        record.name().encode(&mut encoded).unwrap();
+        record
+            .rdata()
+            .as_ref()
+            .unwrap()
+            .encode(&mut encoded)
+            .unwrap();
+        uncompressed += encoded.len() + 10;
+    }
+    assert!(wire.len() < uncompressed, "{} octets", wire.len());
+}
+
+#[test]
+fn builds_queries() {
+    let question = DNSQuestion::new(name("example."), RRType::SOA, DNSClass::IN);
+    let mut edns = Edns::new(1232);
+    edns.set_dnssec_ok(true);
+    edns.add_option(EdnsOption {
+        code: 10,
+        data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+    });
+    let query = MessageBuilder::query()
+        .id(0x1234)
+        .recursion_desired(true)
+        .checking_disabled(true)
+        .question(question)
+        .edns(edns.clone())
+        .build()
+        .unwrap();
+    let wire = query.encode().unwrap();
+    // the ID, then QR clear, opcode QUERY, RD, CD and NOERROR, then the counts, of
+    // which the additionals count includes the OPT record.
+    assert_eq!(wire[..12], [0x12, 0x34, 0x01, 0x10, 0, 1, 0, 0, 0, 0, 0, 1]);
+
+    let decoded = DNSMessage::decode(&wire).unwrap();
+    let header = decoded.header();
+    assert!(!header.is_response());
+    assert_eq!(header.opcode(), Some(Opcode::Query));
+    assert!(header.recursion_desired() && header.checking_disabled());
+    assert!(!header.authoritative() && !header.truncated() && !header.authentic_data());
+    assert_eq!(decoded.questions()[0].q_type(), &RRType::SOA);
+    assert_eq!(decoded.edns(), Some(edns));
+
+    let update = MessageBuilder::query()
+        .opcode(Opcode::Update)
+        .build()
+        .unwrap();
+    assert_eq!(update.header().opcode(), Some(Opcode::Update));
+    assert_eq!(update.encode().unwrap()[2..4], [0x28, 0]);
+}
+
+#[test]
+fn builds_responses() {
+    let response = MessageBuilder::response()
+        .id(7)
+        .authoritative(true)
+        .truncated(true)
+        .recursion_available(true)
+        .authentic_data(true)
+        .response_code(ResponseCode::NXDomain)
+        .question(DNSQuestion::new(
+            name("www.example."),
+            RRType::A,
+            DNSClass::IN,
+        ))
+        .answers([
+            record("www.example.", RData::CNAME(name("web.example."))),
+            record("web.example.", RData::A("192.0.2.1".parse().unwrap())),
+        ])
+        .authorities([record("example.", RData::NS(name("ns.example.")))])
+        .build()
+        .unwrap();
+    let wire = response.encode().unwrap();
+    assert_eq!(wire[..12], [0, 7, 0x86, 0xa3, 0, 1, 0, 2, 0, 1, 0, 0]);
+
+    let decoded = DNSMessage::decode(&wire).unwrap();
+    let header = decoded.header();
+    assert!(header.is_response() && header.authoritative() && header.truncated());
+    assert!(header.recursion_available() && header.authentic_data());
+    assert!(!header.recursion_desired() && !header.checking_disabled());
+    assert_eq!(header.response_code(), ResponseCode::NXDomain);
+    assert_eq!(header.num_answers(), 2);
+    assert_eq!(decoded.edns(), None);
+}
+
+#[test]
+fn builds_responses_to_queries() {
+    for rd in [false, true] {
+        let query = MessageBuilder::query()
+            .id(0xbeef)
+            .opcode(Opcode::Notify)
+            .recursion_desired(rd)
+            .checking_disabled(true)
+            .question(DNSQuestion::new(
+                name("example."),
+                RRType::SOA,
+                DNSClass::IN,
+            ))
+            .build()
+            .unwrap();
+        let response = DNSMessage::response_to(&query)
+            .answer(record("example.", RData::NS(name("ns.example."))))
+            .build()
+            .unwrap();
+        let header = response.header();
+        assert!(header.is_response());
+        assert_eq!(header.id(), 0xbeef);
+        assert_eq!(header.opcode(), Some(Opcode::Notify));
+        assert_eq!(header.recursion_desired(), rd);
+        // the other flags of the query aren't carried over.
+        assert!(!header.checking_disabled());
+        assert_eq!(header.response_code(), ResponseCode::NoError);
+        assert_eq!(response.questions().len(), 1);
+        assert_eq!(response.questions()[0].name(), &name("example."));
+        assert_eq!(response.questions()[0].q_type(), &RRType::SOA);
+        assert_eq!((header.num_questions(), header.num_answers()), (1, 1));
+    }
+}
+
+#[test]
+fn finds_records_across_sections() {
+    let message = response();
+    let owners = |records: Vec<&DNSRecord>| {
+        records
+            .into_iter()
+            .map(|record| record.name().to_string())
+            .collect_vec()
+    };
+    assert_eq!(
+        owners(message.records().collect()),
+        ["www.example", "web.example", "example", "ns.example"]
+    );
+    assert_eq!(
+        owners(message.records_of_type(&RRType::A).collect()),
+        ["web.example", "ns.example"]
+    );
+    assert_eq!(
+        owners(message.answers_of_type(&RRType::A).collect()),
+        ["web.example"]
+    );
+    assert_eq!(
+        owners(message.authorities_of_type(&RRType::NS).collect()),
+        ["example"]
+    );
+    assert_eq!(
+        owners(message.additionals_of_type(&RRType::A).collect()),
+        ["ns.example"]
+    );
+    assert_eq!(message.authorities_of_type(&RRType::A).count(), 0);
+    let ns = name("NS.example.");
+    let named = message.records_named(&ns).collect_vec();
+    assert_eq!(named.len(), 1);
+    assert_eq!(named[0].r_type(), &RRType::A);
+
+    let (header, questions, answers, authorities, additionals) = message.into_parts();
+    assert_eq!(header.id(), 1);
+    assert_eq!(questions.len(), 1);
+    assert_eq!((answers.len(), authorities.len()), (2, 1));
+    assert_eq!(additionals.len(), 1);
+}
+
+#[test]
+fn encodes_sections_as_they_were_changed() {
+    let mut message = response();
+    message
+        .header_mut()
+        .set_response_code(ResponseCode::ServFail);
+    message.set_id(9);
+    message.set_authentic_data(true);
+    message.questions_mut().clear();
+    message.answers_mut().remove(0);
+    message
+        .authorities_mut()
+        .push(record("example.", RData::NS(name("ns2.example."))));
+    message
+        .additionals_mut()
+        .push(Edns::new(4096).to_record().unwrap());
+    assert_eq!(message.edns(), Some(Edns::new(4096)));
+
+    // the counts come from the sections, not from the header that was decoded.
+    let wire = message.encode().unwrap();
+    assert_eq!(wire[..12], [0, 9, 0x80, 0x22, 0, 0, 0, 1, 0, 2, 0, 2]);
+    let decoded = DNSMessage::decode(&wire).unwrap();
+    assert_eq!(decoded.header().response_code(), ResponseCode::ServFail);
+    assert!(decoded.questions().is_empty());
+    assert_eq!(decoded.answers()[0].name(), &name("web.example."));
+    assert_eq!(decoded.authorities().len(), 2);
+    assert_eq!(decoded.edns(), Some(Edns::new(4096)));
+}
+
+#[test]
+fn carries_response_codes_as_they_are() {
+    for (code, rcode, mnemonic) in [
+        (0, ResponseCode::NoError, "NOERROR"),
+        (3, ResponseCode::NXDomain, "NXDOMAIN"),
+        (5, ResponseCode::Refused, "REFUSED"),
+        (10, ResponseCode::NotZone, "NOTZONE"),
+        (11, ResponseCode::Unknown(11), "RCODE11"),
+        (15, ResponseCode::Unknown(15), "RCODE15"),
+    ] {
+        let mut wire = header(0, 0);
+        wire[3] = code;
+        let message = DNSMessage::decode(&wire).unwrap();
+        assert_eq!(message.header().response_code(), rcode);
+        assert_eq!(rcode.code(), code);
+        assert_eq!(rcode.to_string(), mnemonic);
+        assert_eq!(message.encode().unwrap(), wire);
+    }
+}
@@ -0,0 +1,310 @@
+use dns_resolver::cache::DNSCache;
+use dns_resolver::decoder::Decoder;
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::message::{DNSMessage, MessageBuilder};
+use dns_resolver::rdata::RData;
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::{DNSClass, RRType};
+use dns_resolver::zone_file::parse_zone;
+
+fn name(name: &str) -> DomainName {
+    DomainName::new(name.to_string())
+}
+
+// Parses a record of the provided type at www.example. from the presentation format of
+// its data, where names are relative to the root, the way that they're presented.
+fn record(r_type: &str, data: &str) -> DNSRecord {
+    let input = format!("www.example. 300 IN {} {}\n", r_type, data);
+    parse_zone(&input, &DomainName::root()).unwrap().remove(0)
+}
+
+// Sends the record through the wire format of a message and returns it as decoded.
+fn wire_round_trip(record: &DNSRecord) -> DNSRecord {
+    let message = MessageBuilder::query()
+        .answer(record.clone())
+        .build()
+        .unwrap();
+    let decoded = DNSMessage::decode(&message.encode().unwrap()).unwrap();
+    decoded.answers()[0].clone()
+}
+
+// Checks that the data of the record comes back the same from the wire format, and
+// that it's presented the way it was given, and returns it.
+fn round_trip(r_type: &str, data: &str) -> RData {
+    let record = record(r_type, data);
+    let decoded = wire_round_trip(&record);
+    assert_eq!(decoded.rdata(), record.rdata(), "{} {}", r_type, data);
+    let rdata = decoded.rdata().clone().unwrap();
+    assert_eq!(rdata.to_string(), data, "{}", r_type);
+    rdata
+}
+
+#[test]
+fn round_trips_caa_records() {
+    match round_trip("CAA", "128 issue \"ca.example.net; account=230123\"") {
+        RData::CAA(caa) => {
+            assert!(caa.critical());
+            assert_eq!(caa.tag(), b"issue");
+            assert_eq!(caa.value(), b"ca.example.net; account=230123");
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+    match round_trip("CAA", "0 iodef \"mailto:security@example.com\"") {
+        RData::CAA(caa) => assert!(!caa.critical()),
+        rdata => panic!("got {:?}", rdata),
+    }
+}
+
+#[test]
+fn round_trips_tlsa_records() {
+    let digest = "D2ABDE240D7CD3EE6B4B28C54DF034B97983A1D16E8A410E4561CB106618E971";
+    match round_trip("TLSA", &format!("3 1 1 {}", digest)) {
+        RData::TLSA(tlsa) => {
+            assert_eq!(tlsa.usage(), 3);
+            assert_eq!(tlsa.selector(), 1);
+            assert_eq!(tlsa.matching_type(), 1);
+            assert_eq!(tlsa.certificate_data().len(), 32);
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+}
+
+#[test]
+fn round_trips_sshfp_records() {
+    match round_trip("SSHFP", "4 2 0123456789ABCDEF0123456789ABCDEF") {
+        RData::SSHFP(sshfp) => {
+            assert_eq!(sshfp.algorithm(), 4);
+            assert_eq!(sshfp.fingerprint_type(), 2);
+            assert_eq!(sshfp.fingerprint()[..2], [0x01, 0x23]);
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+}
+
+#[test]
+fn round_trips_naptr_records() {
+    let data = r#"100 10 "U" "E2U+sip" "!^.*$!sip:info@example.com!" ."#;
+    match round_trip("NAPTR", data) {
+        RData::NAPTR(naptr) => {
+            assert_eq!(naptr.order(), 100);
+            assert_eq!(naptr.preference(), 10);
+            assert_eq!(naptr.flags(), b"U");
+            assert_eq!(naptr.services(), b"E2U+sip");
+            assert_eq!(naptr.regexp(), b"!^.*$!sip:info@example.com!");
+            assert!(naptr.replacement().is_root());
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+    match round_trip("NAPTR", r#"10 0 "S" "SIP+D2U" "" _sip._udp.example"#) {
+        RData::NAPTR(naptr) => {
+            assert_eq!(naptr.regexp(), b"");
+            assert_eq!(naptr.replacement(), &name("_sip._udp.example."));
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+}
+
+#[test]
+fn round_trips_hinfo_records() {
+    match round_trip("HINFO", r#""INTEL-386" "Linux \"custom\"""#) {
+        RData::HINFO(hinfo) => {
+            assert_eq!(hinfo.cpu(), b"INTEL-386");
+            assert_eq!(hinfo.os(), b"Linux \"custom\"");
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+}
+
+#[test]
+fn round_trips_uri_records() {
+    match round_trip("URI", r#"10 1 "ftp://ftp1.example.com/public""#) {
+        RData::URI(uri) => {
+            assert_eq!(uri.priority(), 10);
+            assert_eq!(uri.weight(), 1);
+            assert_eq!(uri.target(), b"ftp://ftp1.example.com/public");
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+}
+
+// Returns the wire format of a SVCB record in ServiceMode for the root, with the provided
+// SvcParams given as keys and values.
+fn svcb_wire(params: &[(u16, &[u8])]) -> Vec<u8> {
+    let mut wire = vec![0, 1, 0];
+    for (key, value) in params {
+        wire.extend(key.to_be_bytes());
+        wire.extend((value.len() as u16).to_be_bytes());
+        wire.extend(*value);
+    }
+    wire
+}
+
+#[test]
+fn rejects_svc_params_out_of_order() {
+    let alpn: &[u8] = b"\x02h2";
+    let port: &[u8] = &[1, 187];
+    let decode = |wire: Vec<u8>| RData::decode(&RRType::SVCB, &mut Decoder::new(&wire));
+    match decode(svcb_wire(&[(1, alpn), (3, port)])) {
+        Ok(RData::SVCB(svcb)) => assert_eq!(svcb.to_string(), "1 . alpn=h2 port=443"),
+        result => panic!("got {:?}", result),
+    }
+    for params in [[(3, port), (1, alpn)], [(3, port), (3, port)]] {
+        let result = decode(svcb_wire(&params));
+        assert!(result.is_err(), "{:?}: got {:?}", params, result);
+    }
+}
+
+#[test]
+fn passes_unknown_types_through() {
+    let mut wire = vec![0x12, 0x34, 0x81, 0x80, 0, 1, 0, 0, 0, 0, 0, 1];
+    wire.extend(b"\x03www\x07example\x00\x00\x01\x00\x01");
+    // a record of type 65280 at www.example., whose owner is compressed.
+    wire.extend([0xc0, 0x0c, 0xff, 0x00, 0, 1, 0, 0, 0x01, 0x2c, 0, 3]);
+    wire.extend([0xab, 0xcd, 0xef]);
+
+    let message = DNSMessage::decode(&wire).unwrap();
+    let unknown = &message.additionals()[0];
+    assert_eq!(unknown.r_type(), &RRType::Unknown(65280));
+    assert_eq!(
+        unknown.rdata(),
+        &Some(RData::Unknown(vec![0xab, 0xcd, 0xef]))
+    );
+    assert_eq!(
+        unknown.to_string(),
+        "www.example 300 IN TYPE65280 \\# 3 ABCDEF"
+    );
+    assert_eq!(message.encode().unwrap(), wire);
+
+    // the generic presentation format gives the same record.
+    let parsed = record("TYPE65280", "\\# 3 ABCDEF");
+    assert_eq!(parsed.r_type(), unknown.r_type());
+    assert_eq!(parsed.rdata(), unknown.rdata());
+
+    let mut cache = DNSCache::new(10);
+    let owner = name("www.example.");
+    cache.insert(
+        &owner,
+        &RRType::Unknown(65280),
+        &DNSClass::IN,
+        vec![unknown.clone()],
+        false,
+    );
+    let cached = cache
+        .get(&owner, &RRType::Unknown(65280), &DNSClass::IN)
+        .unwrap()
+        .data();
+    assert_eq!(cached[0].raw_data(), [0xab, 0xcd, 0xef]);
+}
+
+// Returns a response with a single answer at the root of the provided type and data.
+fn answer_wire(r_type: u8, rdata: &[u8]) -> Vec<u8> {
+    let mut wire = vec![0x12, 0x34, 0x81, 0x80, 0, 0, 0, 1, 0, 0, 0, 0];
+    wire.extend([0, 0, r_type, 0, 1, 0, 0, 0, 0]);
+    wire.extend((rdata.len() as u16).to_be_bytes());
+    wire.extend(rdata);
+    wire
+}
+
+#[test]
+fn rejects_known_types_whose_data_does_not_parse() {
+    // an address that's too short, and a name that runs past the length of the data,
+    // even though the message carries the rest of it.
+    let mut overrun = answer_wire(2, b"\x03ns1");
+    overrun.push(0);
+    for wire in [answer_wire(1, &[192, 0, 2]), overrun] {
+        let result = DNSMessage::decode(&wire);
+        assert!(result.is_err(), "{:?}: got {:?}", wire, result);
+    }
+
+    // updates use empty data to refer to whole RRsets.
+    let message = DNSMessage::decode(&answer_wire(1, &[])).unwrap();
+    let empty = &message.answers()[0];
+    assert_eq!(empty.rdata(), &None);
+    assert!(empty.raw_data().is_empty());
+    assert_eq!(empty.to_string(), ". 0 IN A \\# 0");
+    assert_eq!(message.encode().unwrap(), answer_wire(1, &[]));
+}
+
+#[test]
+fn round_trips_dnssec_records() {
+    let key = "AwEAAcw5QLr0jXVsVmtgQV4NdQ0qoE7nkX1Nd5V5cNhDb8xC5NPQ7yvm";
+    match round_trip("DNSKEY", &format!("257 3 8 {}", key)) {
+        RData::DNSKEY(dnskey) => {
+            assert!(dnskey.is_zone_key());
+            assert!(dnskey.is_secure_entry_point());
+            assert_eq!(dnskey.algorithm(), 8);
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+    let digest = "E2D3C916F6DEEAC73294E8268FB5885044A833FC5459588F4A9184CFC41A5766";
+    match round_trip("DS", &format!("20326 8 2 {}", digest)) {
+        RData::DS(ds) => {
+            assert_eq!(ds.key_tag(), 20326);
+            assert_eq!(ds.digest_type(), 2);
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+    let signature = "oJB1W6WNGv+ldvQ3WDG0MQkg5IEhjRip8WTrPYGv07h108dUKGMeDPKijVCHX3DDKdfb+v6oB9wfuh3DTJXUAfI/M0zmO/zz8bW0Rznl8O3tGNazPwQKkRN20XPXV6nwwfoXmJQbsLNrLfkGJ5D6fwFm8nN+6pBzeDQfsS3Ap3o=";
+    match round_trip(
+        "RRSIG",
+        &format!(
+            "A 8 2 300 20240102030405 20231203040506 12345 example {}",
+            signature
+        ),
+    ) {
+        RData::RRSIG(rrsig) => {
+            assert_eq!(rrsig.type_covered(), &RRType::A);
+            assert_eq!(rrsig.labels(), 2);
+            assert_eq!(rrsig.signer_name(), &name("example."));
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+    // the types span two windows of the bitmaps.
+    match round_trip("NSEC", "host.example A MX RRSIG NSEC CAA") {
+        RData::NSEC(nsec) => {
+            assert_eq!(nsec.next_domain_name(), &name("host.example."));
+            assert_eq!(
+                nsec.types(),
+                &[
+                    RRType::A,
+                    RRType::MX,
+                    RRType::RRSIG,
+                    RRType::NSEC,
+                    RRType::CAA
+                ]
+            );
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+    match round_trip(
+        "NSEC3",
+        "1 1 0 AABBCCDD 2T7B4G4VSA5SMI47K61MV5BV1A22BOJR A RRSIG",
+    ) {
+        RData::NSEC3(nsec3) => {
+            assert!(nsec3.opt_out());
+            assert_eq!(nsec3.salt(), [0xaa, 0xbb, 0xcc, 0xdd]);
+            assert_eq!(nsec3.next_hashed_owner_name().len(), 20);
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+    round_trip("NSEC3PARAM", "1 0 0 -");
+}
+
+#[test]
+fn rejects_type_bitmap_windows_out_of_order() {
+    // NSEC records for the root with bitmaps for A in window 0 and CAA in window 1.
+    let window_0: &[u8] = &[0, 1, 0x40];
+    let window_1: &[u8] = &[1, 1, 0x40];
+    let decode = |windows: &[&[u8]]| {
+        let wire = [&[0][..], &windows.concat()].concat();
+        RData::decode(&RRType::NSEC, &mut Decoder::new(&wire))
+    };
+    match decode(&[window_0, window_1]) {
+        Ok(RData::NSEC(nsec)) => assert_eq!(nsec.types(), &[RRType::A, RRType::CAA]),
+        result => panic!("got {:?}", result),
+    }
+    for windows in [[window_1, window_0], [window_0, window_0]] {
+        let result = decode(&windows);
+        assert!(result.is_err(), "{:?}: got {:?}", windows, result);
+    }
+}
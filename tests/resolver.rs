@@ -0,0 +1,362 @@
+mod common;
+
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::Nameserver;
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::error::DNSResolverError;
+use dns_resolver::message::DNSMessage;
+use dns_resolver::query::ResponseCode;
+use dns_resolver::rdata::RData;
+use dns_resolver::resolver::MailExchangers;
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::{DNSClass, RRType};
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+const EXAMPLE: &str = "
+$TTL 3600
+@           SOA     ns.example. hostmaster.example. 1 7200 900 1209600 300
+            NS      ns
+ns          A       192.0.2.1
+mail        MX      20 mx2
+            MX      10 mx1
+mx1         A       192.0.2.2
+mx2         A       192.0.2.3
+implicit    A       192.0.2.4
+alias       CNAME   implicit
+nothing     TXT     \"no mail here\"
+null        MX      0 .
+servfail    A       192.0.2.5
+_sip._tcp   SRV     20 0 5060 backup
+            SRV     10 90 5060 heavy
+            SRV     10 10 5060 light
+heavy       A       192.0.2.10
+light       A       192.0.2.11
+backup      AAAA    2001:db8::1
+_none._tcp  SRV     0 0 0 .
+";
+
+fn nameserver() -> Nameserver {
+    Nameserver::new()
+        .zone("example.", EXAMPLE)
+        .fail("servfail.example.", RRType::MX)
+}
+
+#[tokio::test]
+async fn lookup_mx_sorts_explicit_exchangers() {
+    let resolver = common::resolver(nameserver().start().await).await;
+    let exchangers = resolver
+        .lookup_mx(String::from("mail.example."))
+        .await
+        .unwrap();
+    let MailExchangers::Explicit(exchangers) = exchangers else {
+        panic!("expected explicit exchangers, got {:?}", exchangers);
+    };
+    let exchanges = exchangers
+        .iter()
+        .map(|mx| (mx.preference(), mx.exchange().to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        exchanges,
+        [
+            (10, String::from("mx1.example")),
+            (20, String::from("mx2.example"))
+        ]
+    );
+}
+
+#[tokio::test]
+async fn lookup_mx_recognises_null_mx() {
+    let resolver = common::resolver(nameserver().start().await).await;
+    let exchangers = resolver
+        .lookup_mx(String::from("null.example."))
+        .await
+        .unwrap();
+    assert!(matches!(exchangers, MailExchangers::NullMX));
+}
+
+#[tokio::test]
+async fn lookup_mx_falls_back_to_address_on_nodata() {
+    let resolver = common::resolver(nameserver().start().await).await;
+    let exchangers = resolver
+        .lookup_mx(String::from("implicit.example."))
+        .await
+        .unwrap();
+    let MailExchangers::Implicit(mx) = exchangers else {
+        panic!("expected an implicit exchanger, got {:?}", exchangers);
+    };
+    assert_eq!(mx.preference(), 0);
+    assert_eq!(
+        mx.exchange(),
+        &DomainName::new(String::from("implicit.example."))
+    );
+}
+
+#[tokio::test]
+async fn lookup_mx_falls_back_to_the_canonical_name() {
+    let resolver = common::resolver(nameserver().start().await).await;
+    let exchangers = resolver
+        .lookup_mx(String::from("alias.example."))
+        .await
+        .unwrap();
+    let MailExchangers::Implicit(mx) = exchangers else {
+        panic!("expected an implicit exchanger, got {:?}", exchangers);
+    };
+    assert_eq!(
+        mx.exchange(),
+        &DomainName::new(String::from("implicit.example."))
+    );
+}
+
+#[tokio::test]
+async fn lookup_mx_fails_on_nodata_without_address() {
+    let resolver = common::resolver(nameserver().start().await).await;
+    let result = resolver.lookup_mx(String::from("nothing.example.")).await;
+    assert!(result.is_err(), "got {:?}", result);
+}
+
+#[tokio::test]
+async fn lookup_mx_does_not_fall_back_on_nxdomain() {
+    let resolver = common::resolver(nameserver().start().await).await;
+    let result = resolver.lookup_mx(String::from("missing.example.")).await;
+    assert!(result.is_err(), "got {:?}", result);
+}
+
+#[tokio::test]
+async fn lookup_mx_does_not_fall_back_on_servfail() {
+    // the name has an address record, which must not be taken for an implicit exchanger
+    // when the MX lookup itself fails.
+    let resolver = common::resolver(nameserver().start().await).await;
+    let result = resolver.lookup_mx(String::from("servfail.example.")).await;
+    assert!(result.is_err(), "got {:?}", result);
+}
+
+#[tokio::test]
+async fn lookup_srv_orders_targets_by_priority_and_weight() {
+    let resolver = common::resolver(nameserver().start().await).await;
+    let mut heavy_first = 0;
+    for _ in 0..200 {
+        let targets = resolver
+            .lookup_srv(String::from("_sip._tcp.example."), false)
+            .await
+            .unwrap();
+        let order = targets
+            .iter()
+            .map(|target| target.srv.target().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(order.len(), 3);
+        assert_eq!(order[2], "backup.example");
+        if order[0] == "heavy.example" {
+            heavy_first += 1;
+        }
+    }
+    // the heavy target comes first about 9 times out of 10, but not every time.
+    assert!((120..200).contains(&heavy_first), "{}", heavy_first);
+}
+
+#[tokio::test]
+async fn lookup_srv_resolves_the_addresses_of_targets() {
+    let resolver = common::resolver(nameserver().start().await).await;
+    let targets = resolver
+        .lookup_srv(String::from("_sip._tcp.example."), true)
+        .await
+        .unwrap();
+    for target in targets {
+        let expected = match target.srv.target().to_string().as_str() {
+            "heavy.example" => "192.0.2.10",
+            "light.example" => "192.0.2.11",
+            _ => "2001:db8::1",
+        };
+        assert_eq!(target.addresses, [expected.parse::<IpAddr>().unwrap()]);
+        assert_eq!(target.srv.port(), 5060);
+    }
+}
+
+#[tokio::test]
+async fn lookup_srv_recognises_unavailable_services() {
+    let resolver = common::resolver(nameserver().start().await).await;
+    let targets = resolver
+        .lookup_srv(String::from("_none._tcp.example."), true)
+        .await
+        .unwrap();
+    assert!(targets.is_empty());
+}
+
+#[tokio::test]
+async fn find_zone_apex_takes_the_apex_from_negative_answers() {
+    let nameserver = nameserver().zone(
+        "sub.example.",
+        "$TTL 3600\n@ SOA ns.sub.example. hostmaster.example. 7 7200 900 1209600 300\n@ NS ns\nns A 192.0.2.20\n",
+    );
+    let resolver = common::resolver(nameserver.start().await).await;
+    for (name, apex, serial) in [
+        ("example.", "example.", 1),
+        // NODATA, NXDOMAIN and a name below a zone cut.
+        ("mx1.example.", "example.", 1),
+        ("missing.deeper.example.", "example.", 1),
+        ("www.sub.example.", "sub.example.", 7),
+        // the alias isn't followed to its target.
+        ("alias.example.", "example.", 1),
+    ] {
+        let found = resolver.find_zone_apex(String::from(name)).await.unwrap();
+        assert_eq!(found.name, DomainName::new(String::from(apex)), "{}", name);
+        assert_eq!(found.soa.serial(), serial, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn find_zone_apex_fails_on_servfail() {
+    // a failure isn't taken for the name not being the apex, which would go on to find
+    // the apex of the parent zone.
+    let nameserver = nameserver().fail("servfail.example.", RRType::SOA);
+    let resolver = common::resolver(nameserver.start().await).await;
+    let result = resolver
+        .find_zone_apex(String::from("servfail.example."))
+        .await;
+    assert!(result.is_err(), "got {:?}", result);
+}
+
+#[tokio::test]
+async fn qname_minimisation_falls_back_on_broken_nameservers() {
+    let zone = "$TTL 3600
+@           SOA     ns.example. hostmaster.example. 1 7200 900 1209600 300
+            NS      ns
+ns          A       192.0.2.1
+www.a.b     A       192.0.2.7
+";
+    // the nameserver gets the empty non-terminal b.example. wrong, which it should
+    // answer with NODATA.
+    for rcode in [ResponseCode::NXDomain, ResponseCode::Refused] {
+        let log = Arc::new(Mutex::new(vec![]));
+        let nameserver = Nameserver::new()
+            .zone("example.", zone)
+            .answer_with("b.example.", RRType::A, rcode.clone())
+            .log(log.clone());
+        let mut resolver = common::resolver(nameserver.start().await).await;
+        resolver.set_qname_minimisation(true);
+        let message = resolver
+            .resolve(String::from("www.a.b.example."), &RRType::A)
+            .await
+            .unwrap();
+        assert_eq!(
+            message.answers_data(&RRType::A),
+            ["192.0.2.7"],
+            "{:?}",
+            rcode
+        );
+
+        let asked = log
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            asked,
+            ["example", "b.example", "www.a.b.example"],
+            "{:?}",
+            rcode
+        );
+    }
+}
+
+// Returns a zone with a chain of the provided number of AliasMode HTTPS records, from
+// alias0.example. to the ServiceMode record at the end of it.
+fn alias_chain(aliases: usize) -> String {
+    let mut contents = String::from(
+        "$TTL 3600\n@ SOA ns.example. hostmaster.example. 1 7200 900 1209600 300\n@ NS ns\nns A 192.0.2.1\n",
+    );
+    for n in 0..aliases {
+        contents.push_str(&format!("alias{} HTTPS 0 alias{}\n", n, n + 1));
+    }
+    contents.push_str(&format!("alias{} HTTPS 1 . alpn=h2\n", aliases));
+    contents
+}
+
+#[tokio::test]
+async fn resolve_follows_aliases_up_to_the_limit() {
+    let nameserver = Nameserver::new().zone("example.", &alias_chain(8));
+    let resolver = common::resolver(nameserver.start().await).await;
+    let message = resolver
+        .resolve(String::from("alias0.example."), &RRType::HTTPS)
+        .await
+        .unwrap();
+    let owners = message
+        .answers()
+        .iter()
+        .map(|answer| answer.name().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(owners, ["alias8.example"]);
+}
+
+#[tokio::test]
+async fn resolve_fails_beyond_the_alias_limit() {
+    let nameserver = Nameserver::new().zone("example.", &alias_chain(9));
+    let resolver = common::resolver(nameserver.start().await).await;
+    let result = resolver
+        .resolve(String::from("alias0.example."), &RRType::HTTPS)
+        .await;
+    assert!(result.is_err(), "got {:?}", result);
+}
+
+#[tokio::test]
+async fn drops_replies_with_another_id() {
+    let zone = common::zone("example.", EXAMPLE);
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0; 512];
+        let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+        let query = DNSMessage::decode(&buf[..len]).unwrap();
+        let question = &query.questions()[0];
+        // a forged reply with another ID and another address arrives first.
+        let forged = DNSRecord::new(
+            question.name().clone(),
+            RRType::A,
+            DNSClass::IN,
+            Duration::from_secs(3600),
+            RData::A("192.0.2.66".parse().unwrap()),
+        )
+        .unwrap();
+        let forged = DNSMessage::response_to(&query)
+            .id(query.header().id().wrapping_add(1))
+            .authoritative(true)
+            .answer(forged)
+            .build()
+            .unwrap();
+        let reply = zone.answer(&query, question).build().unwrap();
+        for message in [forged, reply] {
+            let encoded = message.encode().unwrap();
+            socket.send_to(&encoded, peer).await.unwrap();
+        }
+    });
+
+    let resolver = common::resolver(addr).await;
+    let message = resolver
+        .resolve(String::from("implicit.example."), &RRType::A)
+        .await
+        .unwrap();
+    assert_eq!(message.answers_data(&RRType::A), ["192.0.2.4"]);
+}
+
+#[tokio::test]
+async fn gives_up_on_nameservers_that_do_not_respond() {
+    // the socket is never read from, so the query goes unanswered.
+    let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let mut resolver = common::resolver(silent.local_addr().unwrap()).await;
+    resolver.set_query_timeout(Duration::from_millis(200));
+    let result = timeout(
+        Duration::from_secs(5),
+        resolver.resolve(String::from("www.example."), &RRType::A),
+    )
+    .await
+    .expect("the resolver kept on waiting");
+    assert!(
+        matches!(result, Err(DNSResolverError::Timeout(_))),
+        "got {:?}",
+        result
+    );
+}
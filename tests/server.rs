@@ -0,0 +1,463 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::hierarchy::Hierarchy;
+use common::server::{zone_flag, Server};
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::edns::Edns;
+use dns_resolver::message::{DNSMessage, MessageBuilder};
+use dns_resolver::query::{DNSQuestion, ResponseCode};
+use dns_resolver::rr_types::{DNSClass, RRType};
+use dns_resolver::zone::Zone;
+use dns_resolver::{tcp, transfer};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::time::{sleep, timeout};
+
+// PrimaryState is what a primary serves and how it has been used.
+struct PrimaryState {
+    zone: Zone,
+    transfers: usize,
+    // The time that the primary takes before it answers a transfer.
+    delay: Duration,
+}
+
+// Primary is a primary of a zone for the server to transfer it from, which answers
+// queries for the SOA record of the zone over UDP and transfers over TCP, where IXFR is
+// answered like AXFR.
+struct Primary {
+    addr: SocketAddr,
+    state: Arc<Mutex<PrimaryState>>,
+}
+
+impl Primary {
+    async fn start(zone: Zone) -> Primary {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let state = Arc::new(Mutex::new(PrimaryState {
+            zone,
+            transfers: 0,
+            delay: Duration::ZERO,
+        }));
+
+        let udp_state = Arc::clone(&state);
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                let query = DNSMessage::decode(&buf[..len]).unwrap();
+                let soa = udp_state.lock().unwrap().zone.soa().clone();
+                let response = DNSMessage::response_to(&query)
+                    .authoritative(true)
+                    .answer(soa)
+                    .build()
+                    .unwrap();
+                socket
+                    .send_to(&response.encode().unwrap(), peer)
+                    .await
+                    .unwrap();
+            }
+        });
+        let tcp_state = Arc::clone(&state);
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let query = tcp::read_message(&mut stream).await.unwrap().unwrap();
+                let query = DNSMessage::decode(&query).unwrap();
+                let (responses, delay) = {
+                    let mut state = tcp_state.lock().unwrap();
+                    state.transfers += 1;
+                    let responses = transfer::axfr_responses(&state.zone, &query).unwrap();
+                    (responses, state.delay)
+                };
+                sleep(delay).await;
+                for response in responses {
+                    tcp::write_message(&mut stream, &response.encode().unwrap())
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+        Primary { addr, state }
+    }
+
+    fn transfers(&self) -> usize {
+        self.state.lock().unwrap().transfers
+    }
+
+    fn set_zone(&self, zone: Zone) {
+        self.state.lock().unwrap().zone = zone;
+    }
+
+    fn set_delay(&self, delay: Duration) {
+        self.state.lock().unwrap().delay = delay;
+    }
+}
+
+// Returns a version of example. with the provided serial and address of www.example.
+fn example(serial: u32, address: &str) -> Zone {
+    let contents = format!(
+        "$TTL 3600
+@           SOA     ns.example. hostmaster.example. {} 7200 900 1209600 300
+            NS      ns
+ns          A       192.0.2.1
+www         A       {}
+",
+        serial, address
+    );
+    common::zone("example.", &contents)
+}
+
+// Starts the server as a secondary of the primary for example.
+fn secondary_server(primary: &Primary) -> Server {
+    Server::start(&[
+        String::from("--secondary"),
+        format!("example.={}", primary.addr),
+    ])
+}
+
+// Queries the server until it answers with the provided address of www.example.
+async fn wait_for_address(server: &Server, address: &str) {
+    for _ in 0..100 {
+        let response = server.query("www.example.", RRType::A).await;
+        if response.answers_data(&RRType::A) == [address] {
+            return;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the server doesn't serve {} for www.example.", address);
+}
+
+// Starts the server, validating answers from the hierarchy of signed zones.
+async fn validating_server(hierarchy: &Hierarchy) -> Server {
+    let nameserver = hierarchy.nameserver().start().await;
+    let anchor = hierarchy.trust_anchor();
+    Server::start(&[
+        String::from("--root-server"),
+        nameserver.to_string(),
+        String::from("--trust-anchor"),
+        anchor.to_string(),
+    ])
+}
+
+// Returns a recursive query for the records of the type at the name, which asks for the
+// AD bit by setting it.
+fn ad_query(name: &str, r_type: RRType) -> MessageBuilder {
+    let question = DNSQuestion::new(DomainName::new(name.to_string()), r_type, DNSClass::IN);
+    MessageBuilder::query()
+        .id(7)
+        .recursion_desired(true)
+        .authentic_data(true)
+        .question(question)
+}
+
+#[tokio::test]
+async fn sets_ad_on_secure_answers() {
+    let hierarchy = Hierarchy::new();
+    let server = validating_server(&hierarchy).await;
+    for (name, r_type) in [
+        ("www.example.", RRType::A),
+        ("www.test.", RRType::A),
+        ("missing.example.", RRType::A),
+    ] {
+        // the answer comes from the cache the second time, if it has any records.
+        for _ in 0..2 {
+            let response = server.exchange(ad_query(name, r_type.clone())).await;
+            assert!(response.header().authentic_data(), "{}", name);
+        }
+    }
+}
+
+#[tokio::test]
+async fn sets_ad_only_for_clients_that_ask_for_it() {
+    let hierarchy = Hierarchy::new();
+    let server = validating_server(&hierarchy).await;
+    let response = server.query("www.example.", RRType::A).await;
+    assert_eq!(response.answers_of_type(&RRType::A).count(), 1);
+    assert!(!response.header().authentic_data());
+
+    // the DO bit asks for it as well.
+    let mut edns = Edns::new(1232);
+    edns.set_dnssec_ok(true);
+    let query = ad_query("www.example.", RRType::A)
+        .authentic_data(false)
+        .edns(edns);
+    let response = server.exchange(query).await;
+    assert!(response.header().authentic_data());
+}
+
+#[tokio::test]
+async fn clears_ad_on_insecure_answers() {
+    let hierarchy = Hierarchy::new();
+    let server = validating_server(&hierarchy).await;
+    let response = server
+        .exchange(ad_query("www.insecure.example.", RRType::A))
+        .await;
+    assert_eq!(response.header().response_code(), ResponseCode::NoError);
+    assert_eq!(response.answers().len(), 1);
+    assert!(!response.header().authentic_data());
+}
+
+#[tokio::test]
+async fn answers_bogus_answers_with_servfail() {
+    let hierarchy = Hierarchy::new();
+    let server = validating_server(&hierarchy).await;
+    let response = server.exchange(ad_query("bogus.example.", RRType::A)).await;
+    assert_eq!(response.header().response_code(), ResponseCode::ServFail);
+    assert!(response.answers().is_empty());
+    assert!(!response.header().authentic_data());
+}
+
+fn version_question() -> DNSQuestion {
+    DNSQuestion::new(
+        DomainName::new(String::from("version.bind.")),
+        RRType::TXT,
+        DNSClass::CH,
+    )
+}
+
+// Sends a query for the version of the server over the TCP connection, returning None
+// if the connection is closed instead of answering it.
+async fn query_version_over_tcp(stream: &mut TcpStream, id: u16) -> Option<DNSMessage> {
+    let query = MessageBuilder::query()
+        .id(id)
+        .question(version_question())
+        .build()
+        .unwrap();
+    tcp::write_message(stream, &query.encode().unwrap())
+        .await
+        .ok()?;
+    let response = tcp::read_message(stream).await.ok()??;
+    Some(DNSMessage::decode(&response).unwrap())
+}
+
+#[tokio::test]
+async fn serves_queries_while_tcp_connections_are_idle() {
+    let server = Server::start(&[]);
+    let _idle = TcpStream::connect(server.addr).await.unwrap();
+    // the idle connection has to be accepted before the other queries are sent.
+    sleep(Duration::from_millis(100)).await;
+
+    let response = timeout(Duration::from_secs(2), server.send(version_question()))
+        .await
+        .expect("the UDP query wasn't answered");
+    assert_eq!(response.answers().len(), 1);
+    let mut stream = TcpStream::connect(server.addr).await.unwrap();
+    let response = timeout(
+        Duration::from_secs(2),
+        query_version_over_tcp(&mut stream, 1),
+    )
+    .await
+    .expect("the TCP query wasn't answered");
+    assert_eq!(response.unwrap().answers().len(), 1);
+}
+
+#[tokio::test]
+async fn limits_the_queries_per_tcp_connection() {
+    let server = Server::start(&[]);
+    let mut stream = TcpStream::connect(server.addr).await.unwrap();
+    for id in 0..100 {
+        let response = query_version_over_tcp(&mut stream, id).await;
+        assert_eq!(response.unwrap().header().id(), id);
+    }
+    assert!(query_version_over_tcp(&mut stream, 100).await.is_none());
+}
+
+#[tokio::test]
+async fn checks_the_serial_before_transferring_secondary_zones() {
+    let primary = Primary::start(example(1, "192.0.2.2")).await;
+    let server = secondary_server(&primary);
+    wait_for_address(&server, "192.0.2.2").await;
+    assert_eq!(primary.transfers(), 1);
+
+    // the zone isn't transferred again if the primary's version is the same.
+    let response = server.notify().await;
+    assert_eq!(response.header().response_code(), ResponseCode::NoError);
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(primary.transfers(), 1);
+
+    primary.set_zone(example(2, "192.0.2.3"));
+    server.notify().await;
+    wait_for_address(&server, "192.0.2.3").await;
+    assert_eq!(primary.transfers(), 2);
+}
+
+#[tokio::test]
+async fn serves_secondary_zones_while_they_are_transferred() {
+    let primary = Primary::start(example(1, "192.0.2.2")).await;
+    let server = secondary_server(&primary);
+    wait_for_address(&server, "192.0.2.2").await;
+
+    primary.set_delay(Duration::from_secs(3));
+    primary.set_zone(example(2, "192.0.2.3"));
+    server.notify().await;
+    // the transfer is under way by now, while the old version is still served.
+    sleep(Duration::from_millis(500)).await;
+    let response = timeout(
+        Duration::from_secs(1),
+        server.query("www.example.", RRType::A),
+    )
+    .await
+    .expect("the query wasn't answered during the transfer");
+    assert_eq!(response.answers_data(&RRType::A), ["192.0.2.2"]);
+    wait_for_address(&server, "192.0.2.3").await;
+}
+
+#[tokio::test]
+async fn answers_chaos_queries_about_the_instance() {
+    let server = Server::start(&[
+        String::from("--version"),
+        String::from("1.2.3"),
+        String::from("--hostname"),
+        String::from("ns1.test"),
+    ]);
+    for (name, data) in [
+        ("version.bind.", "\"1.2.3\""),
+        ("VERSION.SERVER.", "\"1.2.3\""),
+        ("hostname.bind.", "\"ns1.test\""),
+        // the ID defaults to the hostname.
+        ("id.server.", "\"ns1.test\""),
+    ] {
+        let name = DomainName::new(name.to_string());
+        let response = server
+            .send(DNSQuestion::new(name, RRType::TXT, DNSClass::CH))
+            .await;
+        assert_eq!(response.answers_data(&RRType::TXT), [data]);
+        assert_eq!(response.answers()[0].class(), &DNSClass::CH);
+    }
+
+    for (name, r_type) in [("version.bind.", RRType::A), ("other.bind.", RRType::TXT)] {
+        let name = DomainName::new(name.to_string());
+        let response = server
+            .send(DNSQuestion::new(name.clone(), r_type, DNSClass::CH))
+            .await;
+        assert_eq!(
+            response.header().response_code(),
+            ResponseCode::Refused,
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn answers_from_zones_of_the_class_in_question() {
+    let zone = "$TTL 3600
+@   SOA ns.example. hostmaster.example. 1 7200 900 1209600 300
+    NS  ns
+ns  A   192.0.2.1
+www A   192.0.2.2
+";
+    let server = Server::start(&[String::from("--zone"), zone_flag("class", zone)]);
+    let www = DomainName::new(String::from("www.example."));
+    for class in [DNSClass::IN, DNSClass::ANY] {
+        let response = server
+            .send(DNSQuestion::new(www.clone(), RRType::A, class.clone()))
+            .await;
+        assert_eq!(
+            response.answers_data(&RRType::A),
+            ["192.0.2.2"],
+            "{}",
+            class
+        );
+    }
+    // the zone is of class IN, and the server doesn't recurse for other classes.
+    for class in [DNSClass::HS, DNSClass::CH] {
+        let response = server
+            .send(DNSQuestion::new(www.clone(), RRType::A, class.clone()))
+            .await;
+        assert_eq!(
+            response.header().response_code(),
+            ResponseCode::Refused,
+            "{}",
+            class
+        );
+        assert!(response.answers().is_empty());
+    }
+}
+
+// Sends the query over a TCP connection of its own and returns the response.
+async fn exchange_over_tcp(server: &Server, query: MessageBuilder) -> DNSMessage {
+    let mut stream = TcpStream::connect(server.addr).await.unwrap();
+    let query = query.build().unwrap();
+    tcp::write_message(&mut stream, &query.encode().unwrap())
+        .await
+        .unwrap();
+    let response = tcp::read_message(&mut stream).await.unwrap().unwrap();
+    DNSMessage::decode(&response).unwrap()
+}
+
+#[tokio::test]
+async fn truncates_responses_that_do_not_fit_over_udp() {
+    let mut zone = String::from(
+        "$TTL 3600
+@   SOA ns.example. hostmaster.example. 1 7200 900 1209600 300
+    NS  ns
+ns  A   192.0.2.1
+",
+    );
+    // 40 records of about 40 octets each.
+    for i in 0..40 {
+        zone.push_str(&format!("big TXT \"record {:02} of a large RRset\"\n", i));
+    }
+    let server = Server::start(&[String::from("--zone"), zone_flag("truncation", &zone)]);
+    let query = |edns: Option<u16>| {
+        let query = MessageBuilder::query().id(3).question(DNSQuestion::new(
+            DomainName::new(String::from("big.example.")),
+            RRType::TXT,
+            DNSClass::IN,
+        ));
+        match edns {
+            Some(size) => query.edns(Edns::new(size)),
+            None => query,
+        }
+    };
+
+    // clients that don't use EDNS only take 512 octets, and clients that do take what
+    // they advertise.
+    for edns in [None, Some(512), Some(1000)] {
+        let response = server.exchange(query(edns)).await;
+        assert!(response.header().truncated(), "{:?}", edns);
+        assert!(response.answers().is_empty(), "{:?}", edns);
+        assert_eq!(response.header().response_code(), ResponseCode::NoError);
+        assert_eq!(response.edns().is_some(), edns.is_some());
+    }
+    let response = server.exchange(query(Some(4096))).await;
+    assert!(!response.header().truncated());
+    assert_eq!(response.answers_of_type(&RRType::TXT).count(), 40);
+
+    // the client retries over TCP, which isn't limited.
+    let response = exchange_over_tcp(&server, query(None)).await;
+    assert!(!response.header().truncated());
+    assert_eq!(response.answers_of_type(&RRType::TXT).count(), 40);
+
+    // small responses fit as they are.
+    let response = server.query("ns.example.", RRType::A).await;
+    assert!(!response.header().truncated());
+    assert_eq!(response.answers_data(&RRType::A), ["192.0.2.1"]);
+}
+
+#[tokio::test]
+async fn answers_queries_over_udp_while_others_are_resolved() {
+    // a root server that never answers, which holds up the queries sent to it.
+    let unresponsive = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let server = Server::start(&[
+        String::from("--root-server"),
+        unresponsive.local_addr().unwrap().to_string(),
+    ]);
+    let resolved = server.query("www.example.", RRType::A);
+    let answered = async {
+        // the query to be resolved has to be received first.
+        sleep(Duration::from_millis(100)).await;
+        timeout(Duration::from_secs(2), server.send(version_question()))
+            .await
+            .expect("the query was held up by the one being resolved")
+    };
+    tokio::select! {
+        _ = resolved => panic!("the unresponsive root server was resolved against"),
+        response = answered => assert_eq!(response.answers().len(), 1),
+    }
+}
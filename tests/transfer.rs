@@ -0,0 +1,245 @@
+mod common;
+
+use std::fs;
+use std::net::SocketAddr;
+use std::process;
+use std::time::Duration;
+
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::journal::Journal;
+use dns_resolver::message::{DNSMessage, MessageBuilder};
+use dns_resolver::query::{DNSQuestion, Opcode};
+use dns_resolver::rdata::RData;
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::{DNSClass, RRType};
+use dns_resolver::tcp;
+use dns_resolver::transfer;
+use dns_resolver::update::{self, Change};
+use dns_resolver::zone::Zone;
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::time::timeout;
+
+const VERSION_1: &str = "
+$TTL 3600
+@           SOA     ns.example. hostmaster.example. 1 7200 900 1209600 300
+            NS      ns
+ns          A       192.0.2.1
+www         A       192.0.2.2
+";
+
+const VERSION_2: &str = "
+$TTL 3600
+@           SOA     ns.example. hostmaster.example. 2 7200 900 1209600 300
+            NS      ns
+ns          A       192.0.2.1
+www         A       192.0.2.3
+";
+
+fn name(name: &str) -> DomainName {
+    DomainName::new(name.to_string())
+}
+
+// Starts a primary that answers a single transfer with the provided messages, each of
+// which carries the provided records in its answer section.
+async fn primary(messages: Vec<Vec<DNSRecord>>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let query = tcp::read_message(&mut stream).await.unwrap().unwrap();
+        let query = DNSMessage::decode(&query).unwrap();
+        for records in messages {
+            let response = DNSMessage::response_to(&query)
+                .authoritative(true)
+                .answers(records)
+                .build()
+                .unwrap();
+            tcp::write_message(&mut stream, &response.encode().unwrap())
+                .await
+                .unwrap();
+        }
+    });
+    addr
+}
+
+fn www(zone: &Zone) -> Vec<DNSRecord> {
+    zone.records_at(&name("www.example."))
+        .iter()
+        .filter(|record| record.r_type() == &RRType::A)
+        .cloned()
+        .collect()
+}
+
+fn addresses(zone: &Zone) -> Vec<Option<RData>> {
+    www(zone)
+        .iter()
+        .map(|record| record.rdata().clone())
+        .collect()
+}
+
+// Works out the change that adds an address for the name to the zone.
+fn add_address(zone: &Zone, owner: &str, address: &str) -> Change {
+    let record = DNSRecord::new(
+        name(owner),
+        RRType::A,
+        DNSClass::IN,
+        Duration::from_secs(3600),
+        RData::A(address.parse().unwrap()),
+    )
+    .unwrap();
+    let update = MessageBuilder::query()
+        .opcode(Opcode::Update)
+        .question(DNSQuestion::new(
+            zone.origin().clone(),
+            RRType::SOA,
+            DNSClass::IN,
+        ))
+        .authorities([record])
+        .build()
+        .unwrap();
+    update::update(zone, &update).unwrap().unwrap()
+}
+
+// Returns the versions of the zone with serials 1 to 3, along with a journal of the
+// changes from the first one to the last one.
+fn journaled_versions(test: &str) -> (Vec<Zone>, Journal) {
+    let path = std::env::temp_dir().join(format!("dns-resolver-{}-{}.jnl", test, process::id()));
+    let _ = fs::remove_file(&path);
+    let journal = Journal::new(path);
+    let mut versions = vec![common::zone("example.", VERSION_1)];
+    for (owner, address) in [
+        ("mail.example.", "192.0.2.4"),
+        ("ftp.example.", "192.0.2.5"),
+    ] {
+        let current = versions.last().unwrap();
+        let change = add_address(current, owner, address);
+        journal.append(&change).unwrap();
+        let next = current.apply(change.records()).unwrap();
+        versions.push(next);
+    }
+    (versions, journal)
+}
+
+// Returns the records of the IXFR responses to a client with the provided version of the
+// zone.
+fn ixfr_records(zone: &Zone, client: &Zone, journal: Option<&Journal>) -> Vec<DNSRecord> {
+    let query = MessageBuilder::query()
+        .id(1)
+        .question(DNSQuestion::new(
+            zone.origin().clone(),
+            RRType::IXFR,
+            DNSClass::IN,
+        ))
+        .authorities([client.soa().clone()])
+        .build()
+        .unwrap();
+    transfer::ixfr_responses(zone, &query, journal)
+        .unwrap()
+        .into_iter()
+        .flat_map(|response| response.into_parts().2)
+        .collect()
+}
+
+fn soa_serial(record: &DNSRecord) -> Option<u32> {
+    match record.rdata() {
+        Some(RData::SOA(soa)) => Some(soa.serial()),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn ixfr_keeps_up_to_date_zones() {
+    let zone = common::zone("example.", VERSION_1);
+    let server = primary(vec![vec![zone.soa().clone()]]).await;
+    let transferred = transfer::ixfr(&server.to_string(), &zone).await.unwrap();
+    assert_eq!(transferred.serial(), 1);
+}
+
+#[tokio::test]
+async fn ixfr_reads_on_past_a_newer_soa_record() {
+    let old = common::zone("example.", VERSION_1);
+    let new = common::zone("example.", VERSION_2);
+    // the first message carries the SOA record of the new version alone, which doesn't
+    // mean that the client is up to date.
+    let mut changes = vec![old.soa().clone()];
+    changes.extend(www(&old));
+    changes.push(new.soa().clone());
+    changes.extend(www(&new));
+    changes.push(new.soa().clone());
+    let server = primary(vec![vec![new.soa().clone()], changes]).await;
+
+    let transferred = transfer::ixfr(&server.to_string(), &old).await.unwrap();
+    assert_eq!(transferred.serial(), 2);
+    assert_eq!(addresses(&transferred), addresses(&new));
+}
+
+#[tokio::test]
+async fn ixfr_accepts_whole_zones() {
+    let old = common::zone("example.", VERSION_1);
+    let new = common::zone("example.", VERSION_2);
+    let mut records = new
+        .records()
+        .filter(|record| record.r_type() != &RRType::SOA)
+        .cloned()
+        .collect::<Vec<_>>();
+    records.push(new.soa().clone());
+    let server = primary(vec![vec![new.soa().clone()], records]).await;
+
+    let transferred = transfer::ixfr(&server.to_string(), &old).await.unwrap();
+    assert_eq!(transferred.serial(), 2);
+    assert_eq!(addresses(&transferred), addresses(&new));
+}
+
+#[tokio::test]
+async fn gives_up_on_serial_queries_that_go_unanswered() {
+    // the socket is never read from, so the query goes unanswered.
+    let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let server = silent.local_addr().unwrap().to_string();
+    let result = timeout(
+        Duration::from_secs(10),
+        transfer::serial(&server, &name("example.")),
+    )
+    .await
+    .expect("the query for the serial kept on waiting");
+    assert!(result.is_err(), "got {:?}", result);
+}
+
+#[test]
+fn serves_ixfr_from_the_journal() {
+    let (versions, journal) = journaled_versions("ixfr-journal");
+    let latest = &versions[2];
+    for client in &versions[..2] {
+        let records = ixfr_records(latest, client, Some(&journal));
+        // the changes start at the client's version, rather than being the whole zone.
+        assert_eq!(soa_serial(&records[1]), Some(client.serial()));
+        let body = &records[1..records.len() - 1];
+        let applied = client.apply(body).unwrap();
+        assert_eq!(applied.serial(), 3);
+        assert_eq!(
+            applied.records().count(),
+            latest.records().count(),
+            "{}",
+            client.serial()
+        );
+    }
+
+    // an up to date client gets the SOA record alone.
+    let records = ixfr_records(latest, latest, Some(&journal));
+    assert_eq!(records.len(), 1);
+    let _ = fs::remove_file(journal.path());
+}
+
+#[test]
+fn serves_ixfr_like_axfr_without_the_changes() {
+    let (versions, journal) = journaled_versions("ixfr-axfr");
+    let latest = &versions[2];
+    let older = common::zone("example.", &VERSION_1.replace(" 1 7200", " 0 7200"));
+    // the journal doesn't go back to serial 0, and there's no journal at all for the
+    // other client.
+    for (journal, client) in [(Some(&journal), &older), (None, &versions[0])] {
+        let records = ixfr_records(latest, client, journal);
+        assert_eq!(soa_serial(&records[1]), None);
+        assert_eq!(records.len(), latest.records().count() + 1);
+    }
+    let _ = fs::remove_file(journal.path());
+}
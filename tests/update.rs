@@ -0,0 +1,388 @@
+mod common;
+
+use std::time::Duration;
+
+use common::server::{zone_flag, Server};
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::message::MessageBuilder;
+use dns_resolver::query::{DNSQuestion, Opcode, ResponseCode};
+use dns_resolver::rdata::RData;
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::{DNSClass, RRType};
+use dns_resolver::zone_file::parse_zone;
+
+const ZONE: &str = "$TTL 3600
+@   SOA ns.example. hostmaster.example. 1 7200 900 1209600 300
+    NS  ns
+ns  A   192.0.2.1
+www A   192.0.2.2
+    A   192.0.2.3
+    TXT \"hello\"
+";
+
+fn name(name: &str) -> DomainName {
+    DomainName::new(name.to_string())
+}
+
+// The meta type that matches the RRsets of every type.
+fn any() -> RRType {
+    RRType::from(255)
+}
+
+// Returns a server of the zone that accepts updates from the tests.
+fn server(test: &str) -> Server {
+    Server::start(&[
+        String::from("--zone"),
+        zone_flag(test, ZONE),
+        String::from("--allow-update"),
+        String::from("127.0.0.1"),
+    ])
+}
+
+// Returns a record of the zone's class, which adds the record when it's an update.
+fn record(owner: &str, r_type: RRType, rdata: RData) -> DNSRecord {
+    DNSRecord::new(
+        name(owner),
+        r_type,
+        DNSClass::IN,
+        Duration::from_secs(3600),
+        rdata,
+    )
+    .unwrap()
+}
+
+// Returns a record without data of the provided class, which refers to an RRset or to
+// all the RRsets of a name if the type is ANY.
+fn rrset(owner: &str, r_type: RRType, class: DNSClass) -> DNSRecord {
+    DNSRecord::new(
+        name(owner),
+        r_type,
+        class,
+        Duration::ZERO,
+        RData::Unknown(vec![]),
+    )
+    .unwrap()
+}
+
+// Returns a record of the zone's class without a TTL, which requires the RRset to exist
+// with the data of such records when it's a prerequisite.
+fn exists(owner: &str, r_type: RRType, rdata: RData) -> DNSRecord {
+    DNSRecord::new(name(owner), r_type, DNSClass::IN, Duration::ZERO, rdata).unwrap()
+}
+
+// Returns a record of the NONE class, which deletes the record with the same data.
+fn deletion(owner: &str, r_type: RRType, rdata: RData) -> DNSRecord {
+    DNSRecord::new(name(owner), r_type, DNSClass::NONE, Duration::ZERO, rdata).unwrap()
+}
+
+fn a(address: &str) -> RData {
+    RData::A(address.parse().unwrap())
+}
+
+// Sends an update of example. to the server and returns the RCODE of the response.
+async fn update(
+    server: &Server,
+    prerequisites: Vec<DNSRecord>,
+    updates: Vec<DNSRecord>,
+) -> ResponseCode {
+    let query = MessageBuilder::query()
+        .id(5)
+        .opcode(Opcode::Update)
+        .question(DNSQuestion::new(
+            name("example."),
+            RRType::SOA,
+            DNSClass::IN,
+        ))
+        .answers(prerequisites)
+        .authorities(updates);
+    let response = server.exchange(query).await;
+    assert_eq!(response.header().opcode(), Some(Opcode::Update));
+    response.header().response_code()
+}
+
+async fn serial(server: &Server) -> u32 {
+    let response = server.query("example.", RRType::SOA).await;
+    match response.answers()[0].rdata() {
+        Some(RData::SOA(soa)) => soa.serial(),
+        rdata => panic!("got {:?}", rdata),
+    }
+}
+
+// Returns the data of the records of the type at the name, in the order that they're
+// served in.
+async fn data(server: &Server, owner: &str, r_type: RRType) -> Vec<String> {
+    server
+        .query(owner, r_type.clone())
+        .await
+        .answers_data(&r_type)
+}
+
+#[tokio::test]
+async fn checks_prerequisites() {
+    let server = server("update-prerequisites");
+    for (prerequisite, rcode) in [
+        // the name has to be in use, or not in use.
+        (
+            rrset("nx.example.", any(), DNSClass::ANY),
+            ResponseCode::NXDomain,
+        ),
+        (
+            rrset("www.example.", any(), DNSClass::NONE),
+            ResponseCode::YXDomain,
+        ),
+        // the RRset has to exist, or not exist.
+        (
+            rrset("www.example.", RRType::MX, DNSClass::ANY),
+            ResponseCode::NXRRSet,
+        ),
+        (
+            rrset("www.example.", RRType::A, DNSClass::NONE),
+            ResponseCode::YXRRSet,
+        ),
+        // the RRset has to exist with exactly the data given.
+        (
+            exists("www.example.", RRType::A, a("192.0.2.2")),
+            ResponseCode::NXRRSet,
+        ),
+        (
+            rrset("www.other.", RRType::A, DNSClass::ANY),
+            ResponseCode::NotZone,
+        ),
+        // prerequisites don't have a TTL.
+        (
+            record(
+                "www.example.",
+                RRType::TXT,
+                RData::TXT(vec![b"hello".to_vec()]),
+            ),
+            ResponseCode::FormErr,
+        ),
+    ] {
+        let added = record("new.example.", RRType::A, a("192.0.2.9"));
+        let described = prerequisite.to_string();
+        assert_eq!(
+            update(&server, vec![prerequisite], vec![added]).await,
+            rcode,
+            "{}",
+            described
+        );
+    }
+    // none of the updates were made.
+    assert_eq!(serial(&server).await, 1);
+    assert!(data(&server, "new.example.", RRType::A).await.is_empty());
+
+    let prerequisites = vec![
+        rrset("www.example.", any(), DNSClass::ANY),
+        rrset("www.example.", RRType::A, DNSClass::ANY),
+        rrset("new.example.", any(), DNSClass::NONE),
+        exists("www.example.", RRType::A, a("192.0.2.3")),
+        exists("www.example.", RRType::A, a("192.0.2.2")),
+    ];
+    let added = record("new.example.", RRType::A, a("192.0.2.9"));
+    assert_eq!(
+        update(&server, prerequisites, vec![added]).await,
+        ResponseCode::NoError
+    );
+    assert_eq!(serial(&server).await, 2);
+    assert_eq!(
+        data(&server, "new.example.", RRType::A).await,
+        ["192.0.2.9"]
+    );
+}
+
+#[tokio::test]
+async fn rejects_malformed_updates() {
+    let server = server("update-prescan");
+    let mut with_ttl = rrset("www.example.", RRType::A, DNSClass::ANY);
+    with_ttl.set_ttl(Duration::from_secs(60));
+    let mut deletion_with_ttl = deletion("www.example.", RRType::A, a("192.0.2.2"));
+    deletion_with_ttl.set_ttl(Duration::from_secs(60));
+    let with_data = DNSRecord::new(
+        name("www.example."),
+        RRType::A,
+        DNSClass::ANY,
+        Duration::ZERO,
+        a("192.0.2.2"),
+    )
+    .unwrap();
+    let other_class = DNSRecord::new(
+        name("www.example."),
+        RRType::A,
+        DNSClass::CH,
+        Duration::from_secs(3600),
+        a("192.0.2.9"),
+    )
+    .unwrap();
+    for (malformed, rcode) in [
+        // RRsets are deleted without a TTL and without data.
+        (with_ttl, ResponseCode::FormErr),
+        (with_data, ResponseCode::FormErr),
+        (deletion_with_ttl, ResponseCode::FormErr),
+        // records of meta types can't be added, and records of other classes can't be
+        // added or deleted.
+        (
+            record("www.example.", any(), RData::Unknown(vec![])),
+            ResponseCode::FormErr,
+        ),
+        (other_class, ResponseCode::FormErr),
+        (
+            record("www.other.", RRType::A, a("192.0.2.9")),
+            ResponseCode::NotZone,
+        ),
+    ] {
+        // the update that would be made along with the malformed one.
+        let added = record("new.example.", RRType::A, a("192.0.2.9"));
+        let described = malformed.to_string();
+        assert_eq!(
+            update(&server, vec![], vec![added, malformed]).await,
+            rcode,
+            "{}",
+            described
+        );
+    }
+    assert_eq!(serial(&server).await, 1);
+    assert!(data(&server, "new.example.", RRType::A).await.is_empty());
+    assert_eq!(
+        data(&server, "www.example.", RRType::A).await,
+        ["192.0.2.2", "192.0.2.3"]
+    );
+}
+
+#[tokio::test]
+async fn deletes_records_rrsets_and_names() {
+    let server = server("update-deletions");
+    let deleted = deletion("www.example.", RRType::A, a("192.0.2.2"));
+    assert_eq!(
+        update(&server, vec![], vec![deleted]).await,
+        ResponseCode::NoError
+    );
+    assert_eq!(
+        data(&server, "www.example.", RRType::A).await,
+        ["192.0.2.3"]
+    );
+    assert_eq!(serial(&server).await, 2);
+
+    let deleted = rrset("www.example.", RRType::A, DNSClass::ANY);
+    assert_eq!(
+        update(&server, vec![], vec![deleted]).await,
+        ResponseCode::NoError
+    );
+    assert!(data(&server, "www.example.", RRType::A).await.is_empty());
+    assert_eq!(
+        data(&server, "www.example.", RRType::TXT).await,
+        ["\"hello\""]
+    );
+    assert_eq!(serial(&server).await, 3);
+
+    let deleted = rrset("www.example.", any(), DNSClass::ANY);
+    assert_eq!(
+        update(&server, vec![], vec![deleted]).await,
+        ResponseCode::NoError
+    );
+    let response = server.query("www.example.", RRType::TXT).await;
+    assert_eq!(response.header().response_code(), ResponseCode::NXDomain);
+    assert_eq!(serial(&server).await, 4);
+
+    // deleting what isn't there doesn't change the zone, nor its serial.
+    let deleted = rrset("www.example.", any(), DNSClass::ANY);
+    assert_eq!(
+        update(&server, vec![], vec![deleted]).await,
+        ResponseCode::NoError
+    );
+    assert_eq!(serial(&server).await, 4);
+}
+
+#[tokio::test]
+async fn never_deletes_the_soa_and_the_last_ns_records() {
+    let server = server("update-apex");
+    for deleted in [
+        rrset("example.", any(), DNSClass::ANY),
+        rrset("example.", RRType::SOA, DNSClass::ANY),
+        rrset("example.", RRType::NS, DNSClass::ANY),
+        deletion("example.", RRType::NS, RData::NS(name("ns.example."))),
+    ] {
+        let described = deleted.to_string();
+        assert_eq!(
+            update(&server, vec![], vec![deleted]).await,
+            ResponseCode::NoError,
+            "{}",
+            described
+        );
+        assert_eq!(
+            data(&server, "example.", RRType::NS).await,
+            ["ns.example"],
+            "{}",
+            described
+        );
+    }
+    // there are no other records at the origin, so the zone didn't change.
+    assert_eq!(serial(&server).await, 1);
+
+    // the NS record can go once there's another one.
+    let updates = vec![
+        record("example.", RRType::NS, RData::NS(name("ns2.example."))),
+        deletion("example.", RRType::NS, RData::NS(name("ns.example."))),
+    ];
+    assert_eq!(
+        update(&server, vec![], updates).await,
+        ResponseCode::NoError
+    );
+    assert_eq!(data(&server, "example.", RRType::NS).await, ["ns2.example"]);
+    assert_eq!(serial(&server).await, 2);
+}
+
+#[tokio::test]
+async fn increments_the_serial_unless_the_update_sets_it() {
+    let server = server("update-serial");
+    let added = record("new.example.", RRType::A, a("192.0.2.9"));
+    assert_eq!(
+        update(&server, vec![], vec![added]).await,
+        ResponseCode::NoError
+    );
+    assert_eq!(serial(&server).await, 2);
+
+    let soa = |serial: &str| {
+        let zone = format!(
+            "example. 3600 IN SOA ns.example. hostmaster.example. {} 7200 900 1209600 300\n",
+            serial
+        );
+        parse_zone(&zone, &DomainName::root()).unwrap().remove(0)
+    };
+    assert_eq!(
+        update(&server, vec![], vec![soa("10")]).await,
+        ResponseCode::NoError
+    );
+    assert_eq!(serial(&server).await, 10);
+    // an older serial is ignored.
+    assert_eq!(
+        update(&server, vec![], vec![soa("5")]).await,
+        ResponseCode::NoError
+    );
+    assert_eq!(serial(&server).await, 10);
+}
+
+#[tokio::test]
+async fn refuses_updates_from_other_addresses() {
+    let added = || record("new.example.", RRType::A, a("192.0.2.9"));
+    let zone = zone_flag("update-acl", ZONE);
+    for acl in [
+        vec![],
+        vec![String::from("--allow-update"), String::from("192.0.2.1")],
+    ] {
+        let server = Server::start(&[vec![String::from("--zone"), zone.clone()], acl].concat());
+        assert_eq!(
+            update(&server, vec![], vec![added()]).await,
+            ResponseCode::Refused
+        );
+        assert!(data(&server, "new.example.", RRType::A).await.is_empty());
+    }
+
+    // zones that aren't served can't be updated.
+    let server = server("update-notauth");
+    let query = MessageBuilder::query()
+        .opcode(Opcode::Update)
+        .question(DNSQuestion::new(name("other."), RRType::SOA, DNSClass::IN))
+        .authorities([record("www.other.", RRType::A, a("192.0.2.9"))]);
+    let response = server.exchange(query).await;
+    assert_eq!(response.header().response_code(), ResponseCode::NotAuth);
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::hierarchy::Hierarchy;
+use common::signer::{Algorithm, Key};
+use common::Nameserver;
+use dns_resolver::dnssec::{self, ValidationStatus};
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::query::ResponseCode;
+use dns_resolver::resolver::{Resolver, ValidatedAnswer};
+use dns_resolver::rr_types::RRType;
+
+async fn resolver(hierarchy: &Hierarchy, nameserver: Nameserver) -> Resolver {
+    let mut resolver = common::resolver(nameserver.start().await).await;
+    resolver.set_trust_anchors(vec![hierarchy.trust_anchor()]);
+    resolver
+}
+
+async fn resolve(resolver: &Resolver, name: &str, r_type: RRType) -> ValidatedAnswer {
+    resolver
+        .resolve_validated(name.to_string(), &r_type)
+        .await
+        .unwrap()
+}
+
+fn assert_bogus(answer: &ValidatedAnswer) {
+    assert!(
+        matches!(answer.status, ValidationStatus::Bogus(_)),
+        "got {:?}",
+        answer.status
+    );
+}
+
+#[tokio::test]
+async fn validates_answers_signed_with_each_algorithm() {
+    let hierarchy = Hierarchy::new();
+    let resolver = resolver(&hierarchy, hierarchy.nameserver()).await;
+    // the root zone is signed using RSASHA256, example. using ECDSAP256SHA256 and test.
+    // using ED25519.
+    for name in ["www.example.", "www.test."] {
+        let answer = resolve(&resolver, name, RRType::A).await;
+        assert_eq!(answer.status, ValidationStatus::Secure, "{}", name);
+        assert_eq!(answer.message.answers_of_type(&RRType::A).count(), 1);
+    }
+}
+
+#[tokio::test]
+async fn validates_nxdomain_proofs() {
+    let hierarchy = Hierarchy::new();
+    let resolver = resolver(&hierarchy, hierarchy.nameserver()).await;
+    // the NSEC3 records of test. are Opt-Out, so the name may be an unsigned delegation
+    // rather than not exist.
+    for (name, status) in [
+        ("missing.example.", ValidationStatus::Secure),
+        ("missing.test.", ValidationStatus::Insecure),
+    ] {
+        let answer = resolve(&resolver, name, RRType::A).await;
+        assert_eq!(answer.status, status, "{}", name);
+        assert_eq!(
+            answer.message.header().response_code(),
+            ResponseCode::NXDomain
+        );
+    }
+}
+
+#[tokio::test]
+async fn validates_nodata_proofs() {
+    let hierarchy = Hierarchy::new();
+    let resolver = resolver(&hierarchy, hierarchy.nameserver()).await;
+    for name in ["www.example.", "www.test."] {
+        let answer = resolve(&resolver, name, RRType::MX).await;
+        assert_eq!(answer.status, ValidationStatus::Secure, "{}", name);
+        assert!(answer.message.answers().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn validates_wildcard_answers() {
+    let hierarchy = Hierarchy::new();
+    let resolver = resolver(&hierarchy, hierarchy.nameserver()).await;
+    for name in ["anything.wild.example.", "anything.wild.test."] {
+        let answer = resolve(&resolver, name, RRType::TXT).await;
+        assert_eq!(answer.status, ValidationStatus::Secure, "{}", name);
+        assert_eq!(answer.message.answers_of_type(&RRType::TXT).count(), 1);
+    }
+}
+
+#[tokio::test]
+async fn rejects_answers_without_proofs() {
+    let hierarchy = Hierarchy::new();
+    let resolver = resolver(&hierarchy, hierarchy.nameserver().without_proofs()).await;
+    assert_bogus(&resolve(&resolver, "missing.example.", RRType::A).await);
+    assert_bogus(&resolve(&resolver, "www.test.", RRType::MX).await);
+    // a wildcard answer is only valid if the query name is proven not to exist.
+    assert_bogus(&resolve(&resolver, "anything.wild.example.", RRType::TXT).await);
+}
+
+#[tokio::test]
+async fn treats_unsigned_delegations_as_insecure() {
+    let hierarchy = Hierarchy::new();
+    let resolver = resolver(&hierarchy, hierarchy.nameserver()).await;
+    // the absence of the DS records is proven by a NSEC record at the delegation.
+    let answer = resolve(&resolver, "www.insecure.example.", RRType::A).await;
+    assert_eq!(answer.status, ValidationStatus::Insecure);
+    // the delegation falls within an Opt-Out span of the NSEC3 records.
+    let answer = resolve(&resolver, "www.unsigned.test.", RRType::A).await;
+    assert_eq!(answer.status, ValidationStatus::Insecure);
+}
+
+#[tokio::test]
+async fn rejects_tampered_records() {
+    let hierarchy = Hierarchy::new();
+    let resolver = resolver(&hierarchy, hierarchy.nameserver()).await;
+    assert_bogus(&resolve(&resolver, "bogus.example.", RRType::A).await);
+}
+
+#[tokio::test]
+async fn rejects_expired_signatures() {
+    let mut hierarchy = Hierarchy::new();
+    let now = dnssec::now();
+    hierarchy
+        .example_mut()
+        .set_validity(now - 2 * 86400, now - 86400);
+    let resolver = resolver(&hierarchy, hierarchy.nameserver()).await;
+    assert_bogus(&resolve(&resolver, "www.example.", RRType::A).await);
+    // the other zones are unaffected.
+    let answer = resolve(&resolver, "www.test.", RRType::A).await;
+    assert_eq!(answer.status, ValidationStatus::Secure);
+}
+
+#[tokio::test]
+async fn rejects_signatures_that_are_not_valid_yet() {
+    let mut hierarchy = Hierarchy::new();
+    let now = dnssec::now();
+    hierarchy
+        .example_mut()
+        .set_validity(now + 86400, now + 2 * 86400);
+    let resolver = resolver(&hierarchy, hierarchy.nameserver()).await;
+    assert_bogus(&resolve(&resolver, "www.example.", RRType::A).await);
+}
+
+#[tokio::test]
+async fn rejects_keys_that_do_not_match_the_trust_anchor() {
+    let hierarchy = Hierarchy::new();
+    let mut resolver = common::resolver(hierarchy.nameserver().start().await).await;
+    let other = Key::generate(Algorithm::EcdsaP256Sha256);
+    resolver.set_trust_anchors(vec![other.ds(&DomainName::root())]);
+    assert_bogus(&resolve(&resolver, "www.example.", RRType::A).await);
+}
@@ -0,0 +1,301 @@
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+use dns_resolver::domain_name::DomainName;
+use dns_resolver::error::DNSResolverError;
+use dns_resolver::rdata::RData;
+use dns_resolver::resource_record::DNSRecord;
+use dns_resolver::rr_types::{DNSClass, RRType};
+use dns_resolver::zone_file::{parse_zone, read_zone};
+
+fn name(name: &str) -> DomainName {
+    DomainName::new(name.to_string())
+}
+
+fn parse(input: &str) -> Vec<DNSRecord> {
+    parse_zone(input, &name("example.")).unwrap()
+}
+
+// Returns the line and the reason of the error that parsing the input fails with.
+fn parse_error(input: &str) -> (usize, String) {
+    match parse_zone(input, &name("example.")) {
+        Err(DNSResolverError::ZoneFile(_, line, reason)) => (line, reason),
+        result => panic!("got {:?}", result),
+    }
+}
+
+fn txt(record: &DNSRecord) -> Vec<Vec<u8>> {
+    match record.rdata() {
+        Some(RData::TXT(strings)) => strings.clone(),
+        rdata => panic!("got {:?}", rdata),
+    }
+}
+
+// Returns a directory of its own for the files of a test, which is emptied first.
+fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dns-resolver-{}-{}", test, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn applies_origin_and_ttl_directives() {
+    let records = parse(
+        "$TTL 300
+www         A       192.0.2.1
+$ORIGIN sub.example.
+www         A       192.0.2.2
+@           A       192.0.2.3
+$TTL 1h
+mail.other. A       192.0.2.4
+",
+    );
+    let owners = records.iter().map(DNSRecord::name).collect::<Vec<_>>();
+    assert_eq!(
+        owners,
+        [
+            &name("www.example."),
+            &name("www.sub.example."),
+            &name("sub.example."),
+            &name("mail.other.")
+        ]
+    );
+    assert_eq!(records[0].ttl(), Duration::from_secs(300));
+    assert_eq!(records[3].ttl(), Duration::from_secs(3600));
+}
+
+#[test]
+fn inherits_owner_ttl_and_class_from_the_previous_record() {
+    let records = parse(
+        "www 600 CH A 192.0.2.1
+    A 192.0.2.2
+mail IN 900 A 192.0.2.3
+    A 192.0.2.4
+",
+    );
+    assert_eq!(records[1].name(), &name("www.example."));
+    assert_eq!(records[1].ttl(), Duration::from_secs(600));
+    assert_eq!(records[1].class(), &DNSClass::CH);
+    // the TTL and the class may come in either order.
+    assert_eq!(records[2].ttl(), Duration::from_secs(900));
+    assert_eq!(records[2].class(), &DNSClass::IN);
+    assert_eq!(records[3].name(), &name("mail.example."));
+    assert_eq!(records[3].ttl(), Duration::from_secs(900));
+
+    let (line, reason) = parse_error("    600 A 192.0.2.1\n");
+    assert_eq!(line, 1);
+    assert_eq!(reason, "the first record needs an owner");
+    let (_, reason) = parse_error("www A 192.0.2.1\n");
+    assert_eq!(reason, "no TTL given and no $TTL directive");
+}
+
+#[test]
+fn parses_records_spanning_several_lines() {
+    let records = parse(
+        "$TTL 300
+@   SOA ns hostmaster (
+            2024010101 ; serial
+            2h         ; refresh
+            30m        ; retry
+            2w         ; expire
+            1d )       ; minimum
+www A   192.0.2.1
+",
+    );
+    assert_eq!(records.len(), 2);
+    match records[0].rdata() {
+        Some(RData::SOA(soa)) => {
+            assert_eq!(soa.mname(), &name("ns.example."));
+            assert_eq!(soa.serial(), 2024010101);
+            assert_eq!(soa.refresh(), 7200);
+            assert_eq!(soa.retry(), 1800);
+            assert_eq!(soa.expire(), 14 * 86400);
+            assert_eq!(soa.minimum(), 86400);
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+
+    let (line, reason) = parse_error("$TTL 300\n@ SOA ns hostmaster ( 1 2 3 4 5\n");
+    assert_eq!(line, 3);
+    assert_eq!(reason, "unbalanced parentheses");
+}
+
+#[test]
+fn parses_ttls_with_units() {
+    let records = parse(
+        "a 1w2d3h4m5s A 192.0.2.1
+b 90M         A 192.0.2.2
+c 2147483647  A 192.0.2.3
+",
+    );
+    let week = 7 * 86400;
+    assert_eq!(
+        records[0].ttl(),
+        Duration::from_secs(week + 2 * 86400 + 3 * 3600 + 4 * 60 + 5)
+    );
+    assert_eq!(records[1].ttl(), Duration::from_secs(90 * 60));
+    assert_eq!(records[2].ttl(), Duration::from_secs(2147483647));
+
+    for ttl in ["1x", "1h30", "1hh", "99999999999"] {
+        let (line, reason) = parse_error(&format!("$TTL 300\nwww {} A 192.0.2.1\n", ttl));
+        assert_eq!(line, 2, "{}", ttl);
+        assert!(reason.contains("TTL"), "{}: {}", ttl, reason);
+    }
+}
+
+#[test]
+fn unescapes_quoted_character_strings() {
+    let records = parse(
+        r#"$TTL 300
+www TXT "two words" plain "a \"quote\"" "\065\066\067" "semi;colon"
+"#,
+    );
+    assert_eq!(
+        txt(&records[0]),
+        [
+            b"two words".to_vec(),
+            b"plain".to_vec(),
+            b"a \"quote\"".to_vec(),
+            b"ABC".to_vec(),
+            b"semi;colon".to_vec(),
+        ]
+    );
+
+    let (line, reason) = parse_error("$TTL 300\nwww TXT \"\\256\"\n");
+    assert_eq!(line, 2);
+    assert_eq!(reason, "\\DDD escapes can't be larger than 255");
+    let (_, reason) = parse_error("$TTL 300\nwww TXT \"\\06\"\n");
+    assert_eq!(reason, "\\DDD escapes need three digits");
+    let (line, reason) = parse_error("$TTL 300\n\nwww TXT \"open\n");
+    assert_eq!(line, 3);
+    assert_eq!(reason, "unterminated quoted string");
+}
+
+#[test]
+fn parses_rrsig_timestamps() {
+    let records = parse(
+        "$TTL 300
+www RRSIG A 13 2 300 20240102030405 1700000000 12345 example. AAAA
+",
+    );
+    match records[0].rdata() {
+        Some(RData::RRSIG(rrsig)) => {
+            assert_eq!(rrsig.type_covered(), &RRType::A);
+            // 2024-01-02T03:04:05Z
+            assert_eq!(rrsig.expiration(), 1704164645);
+            assert_eq!(rrsig.inception(), 1700000000);
+            assert_eq!(rrsig.signer_name(), &name("example."));
+        }
+        rdata => panic!("got {:?}", rdata),
+    }
+
+    let (_, reason) = parse_error(
+        "$TTL 300\nwww RRSIG A 13 2 300 20241302030405 1700000000 12345 example. AAAA\n",
+    );
+    assert_eq!(reason, "invalid timestamp 20241302030405");
+}
+
+#[test]
+fn parses_data_in_the_generic_format() {
+    let records = parse(
+        r"$TTL 300
+www A   \# 4 C0000201
+txt TXT \# 6 05 68656C6C6F
+nul TXT \# 0
+",
+    );
+    assert_eq!(
+        records[0].rdata(),
+        &Some(RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+    );
+    assert_eq!(txt(&records[1]), [b"hello".to_vec()]);
+    assert_eq!(records[2].raw_data(), b"");
+
+    let (line, reason) = parse_error("$TTL 300\nwww A \\# 4 C00002\n");
+    assert_eq!(line, 2);
+    assert_eq!(reason, "data length is 4 but the data has 3 octets");
+}
+
+#[test]
+fn reports_the_line_of_errors() {
+    let (line, reason) = parse_error(
+        "$TTL 300
+; a comment
+
+www A   192.0.2.1
+www AAA 192.0.2.2
+",
+    );
+    assert_eq!(line, 5);
+    assert_eq!(reason, "invalid record type AAA");
+
+    let (line, reason) = parse_error("$TTL 300\n$FOO bar\n");
+    assert_eq!(line, 2);
+    assert_eq!(reason, "unknown directive $FOO");
+    let (line, reason) = parse_error("$TTL 300\nwww A 192.0.2.1 extra\n");
+    assert_eq!(line, 2);
+    assert_eq!(reason, "unexpected field extra");
+}
+
+#[test]
+fn includes_files() {
+    let dir = temp_dir("includes-files");
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(
+        dir.join("example.zone"),
+        "$TTL 300
+www                 A       192.0.2.1
+$INCLUDE sub/hosts  sub.example.
+after               A       192.0.2.3
+",
+    )
+    .unwrap();
+    // included files are looked up relative to the file that includes them.
+    fs::write(
+        dir.join("sub").join("hosts"),
+        "mail A 192.0.2.2\n$INCLUDE more\n",
+    )
+    .unwrap();
+    fs::write(dir.join("sub").join("more"), "@ A 192.0.2.4\n").unwrap();
+
+    let records = read_zone(&dir.join("example.zone"), &name("example.")).unwrap();
+    let owners = records.iter().map(DNSRecord::name).collect::<Vec<_>>();
+    // the origin of the including file is restored after the included file.
+    assert_eq!(
+        owners,
+        [
+            &name("www.example."),
+            &name("mail.sub.example."),
+            &name("sub.example."),
+            &name("after.example.")
+        ]
+    );
+
+    fs::write(dir.join("sub").join("more"), "@ A 192.0.2.4\nbad A x\n").unwrap();
+    match read_zone(&dir.join("example.zone"), &name("example.")) {
+        Err(DNSResolverError::ZoneFile(source, line, _)) => {
+            assert!(source.ends_with("more"), "{}", source);
+            assert_eq!(line, 2);
+        }
+        result => panic!("got {:?}", result),
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn limits_the_depth_of_included_files() {
+    let dir = temp_dir("include-depth");
+    fs::write(dir.join("loop.zone"), "$INCLUDE loop.zone\n").unwrap();
+    match read_zone(&dir.join("loop.zone"), &name("example.")) {
+        Err(DNSResolverError::ZoneFile(_, line, reason)) => {
+            assert_eq!(line, 1);
+            assert_eq!(reason, "too many nested $INCLUDE directives");
+        }
+        result => panic!("got {:?}", result),
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}